@@ -0,0 +1,207 @@
+//This file contains the rotating snapshots of config files kept in the data directory.
+//Before a config is overwritten its current contents are copied into a folder for that file,
+//so a bad save, such as a RESET followed by "Save changes? Yes", can be restored from the edit menu.
+
+use crate::config::{Config, config_file_key, get_data_dir, read_config_file};
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The number of snapshots kept for each config file; older ones are deleted.
+pub const MAX_BACKUPS: usize = 20;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+// Define one snapshot of a config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub saved_at: NaiveDateTime, // When the contents were replaced by a save
+}
+
+impl Backup {
+    /// Returns the label shown in the restore list.
+    #[must_use]
+    pub fn label(&self) -> String {
+        self.saved_at.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// Loads the snapshot as a config, upgrading an older format in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the snapshot cannot be read or parsed.
+    pub fn load(&self) -> anyhow::Result<Config> {
+        read_config_file(&self.path)
+    }
+}
+
+/// Returns a unified diff from the `current` config text to the text of a backup.
+#[must_use]
+pub fn restore_diff(current: &str, backup: &str, label: &str) -> String {
+    TextDiff::from_lines(current, backup)
+        .unified_diff()
+        .context_radius(3)
+        .header("current", &format!("backup {label}"))
+        .to_string()
+}
+
+/// Returns the folder holding the snapshots of the config at `config_path`.
+///
+/// # Errors
+///
+/// Returns an error when the data directory cannot be resolved.
+pub fn backup_dir(config_path: &Path) -> Result<PathBuf, String> {
+    Ok(backup_dir_in(&get_data_dir()?, config_path))
+}
+
+/// Returns the snapshot folder of the config at `config_path` inside `data_dir`.
+///
+/// Each config file gets its own folder, named after the file and a hash of its full path.
+#[must_use]
+pub fn backup_dir_in(data_dir: &Path, config_path: &Path) -> PathBuf {
+    data_dir.join("backups").join(config_file_key(config_path))
+}
+
+/// Copies the current contents of `config_path` into `dir` before the file is overwritten.
+///
+/// Nothing is written when the file does not exist yet or matches the newest snapshot.
+/// Returns the new snapshot, if any.
+///
+/// # Errors
+///
+/// Returns an error when the file cannot be read or the snapshot cannot be written.
+pub fn snapshot_config(dir: &Path, config_path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    if !config_path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(config_path)
+        .with_context(|| format!("unable to read config file {}", config_path.display()))?;
+    let newest = list_backups(dir)?.into_iter().next();
+    if let Some(newest) = newest
+        && fs::read_to_string(&newest.path).is_ok_and(|previous| previous == contents)
+    {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(dir)
+        .with_context(|| format!("unable to create backup directory {}", dir.display()))?;
+    let extension = config_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("json");
+    let snapshot = dir.join(format!(
+        "{}.{extension}",
+        chrono::Local::now().format(TIMESTAMP_FORMAT)
+    ));
+    fs::write(&snapshot, contents)
+        .with_context(|| format!("unable to write backup {}", snapshot.display()))?;
+    prune_backups(dir, MAX_BACKUPS)?;
+    Ok(Some(snapshot))
+}
+
+/// Returns the snapshots in `dir`, newest first.
+///
+/// # Errors
+///
+/// Returns an error when the folder exists but cannot be read.
+pub fn list_backups(dir: &Path) -> anyhow::Result<Vec<Backup>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("unable to read backup directory {}", dir.display()))?;
+
+    let mut backups: Vec<Backup> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let stem = path.file_stem()?.to_str()?;
+            let saved_at = NaiveDateTime::parse_from_str(stem, TIMESTAMP_FORMAT).ok()?;
+            Some(Backup { path, saved_at })
+        })
+        .collect();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.saved_at));
+    Ok(backups)
+}
+
+/// Deletes all but the newest `keep` snapshots in `dir`.
+///
+/// # Errors
+///
+/// Returns an error when the folder cannot be read or a snapshot cannot be deleted.
+pub fn prune_backups(dir: &Path, keep: usize) -> anyhow::Result<()> {
+    for backup in list_backups(dir)?.into_iter().skip(keep) {
+        fs::remove_file(&backup.path)
+            .with_context(|| format!("unable to delete old backup {}", backup.path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_skips_missing_and_unchanged_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let backups = dir.path().join("backups");
+        let config = dir.path().join("menu.toml");
+
+        assert_eq!(snapshot_config(&backups, &config).unwrap(), None);
+
+        fs::write(&config, "commands = []\n").unwrap();
+        let snapshot = snapshot_config(&backups, &config).unwrap().unwrap();
+        assert_eq!(snapshot.extension().unwrap(), "toml");
+        assert_eq!(fs::read_to_string(&snapshot).unwrap(), "commands = []\n");
+        assert_eq!(snapshot_config(&backups, &config).unwrap(), None);
+        assert_eq!(list_backups(&backups).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_backups_are_listed_newest_first_and_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        for stem in [
+            "20240102-080000-000",
+            "20240101-080000-000",
+            "20240103-080000-000",
+        ] {
+            fs::write(dir.path().join(format!("{stem}.json")), stem).unwrap();
+        }
+        fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let labels: Vec<String> = list_backups(dir.path())
+            .unwrap()
+            .iter()
+            .map(Backup::label)
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "2024-01-03 08:00:00",
+                "2024-01-02 08:00:00",
+                "2024-01-01 08:00:00"
+            ]
+        );
+
+        prune_backups(dir.path(), 2).unwrap();
+
+        assert!(!dir.path().join("20240101-080000-000.json").exists());
+        assert_eq!(list_backups(dir.path()).unwrap().len(), 2);
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_restore_diff_shows_what_a_restore_brings_back() {
+        let diff = restore_diff(
+            "{\n  \"commands\": []\n}\n",
+            "{\n  \"commands\": [\"ls\"]\n}\n",
+            "2024-01-01 08:00:00",
+        );
+
+        assert!(diff.contains("+  \"commands\": [\"ls\"]"), "{diff}");
+        assert!(diff.contains("-  \"commands\": []"), "{diff}");
+    }
+}
@@ -0,0 +1,934 @@
+use crate::{
+    completions::CompletionShell,
+    config::{
+        CommandLocation, Config, ShellSpec, backup_path, load_config, save_config, validate_config,
+    },
+    formats::ConfigFormat,
+    history::run_and_record,
+    layers::layer_name,
+    menu_edit::{add_command_to_config, delete_command_at},
+    menu_main::pick_command,
+    placeholders::{command_placeholders, fill_placeholders},
+    report::{
+        self, ChangeAction, ChangeReport, CommandDetailReport, CommandListReport, CommandReport,
+        SCHEMA_VERSION, ValidationReport,
+    },
+    state::{self, CommandStatus, CompletionState},
+    trust,
+};
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+//This file contains the command line argument parsing and the non-interactive subcommands.
+
+pub const USAGE: &str = "\
+Usage: shell_command_menu [--config <path>] [<subcommand>]
+
+Without a subcommand the interactive menu is shown.
+
+Subcommands:
+  list [--names]                         List the commands with their numbers, or only their paths
+  run <target> [--set <name=value>]...   Run a command and exit with its status
+  show <target>                          Show how a command is configured
+  add <name> <command> [--group <path>]  Add a command, creating the group path if needed
+  remove <target>                        Remove a command
+  validate                               Check the config and list any problems
+  pick [--output <file>]                 Choose a command in the menu and print it instead of running it
+  allow [--yes]                          Review changes to a project config and trust it
+  convert <json|jsonc|toml|yaml>         Rewrite the config in another format, keeping a backup
+  shell-init <bash|zsh|fish>             Print key bindings (Alt-M) that insert a picked command
+  completions <bash|zsh|fish>            Print a shell completion script
+
+A <target> is the number shown by `list`, a command ID, a Group/Name path or a display name.
+
+Without --config the config is $CLI_MENU_CONFIG, else the nearest .cli_menu.json in the
+current directory or a parent, else cli_menu_cmd.json in $XDG_CONFIG_HOME or the OS config directory.
+Config files may also be .jsonc, .toml or .yaml; the extension decides how they are read and saved.
+Any config other than the global one has to be trusted with `allow` before its commands are shown.
+
+Options:
+  -c, --config <path>    Use an alternate config file
+      --json             Print list, show, add, remove and validate results as JSON
+      --run-once <cmd>   Run a raw shell command once
+      --print-schema     Print the JSON Schema of the config file
+  -V, --version          Print the version
+  -h, --help             Print this help";
+
+// Define the CliArgs struct holding the parsed command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliArgs {
+    pub config: Option<PathBuf>, // The --config override
+    pub format: OutputFormat,    // How subcommands print their results
+    pub action: Action,          // What to do once the config path is known
+}
+
+// Define the OutputFormat enum selected with --json
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Define the Action enum for what the binary was asked to do
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Action {
+    #[default]
+    Menu,
+    Help,
+    Version,
+    PrintSchema,
+    RunOnce(String),
+    Completions(CompletionShell),
+    ShellInit(CompletionShell),
+    Subcommand(Subcommand),
+}
+
+// Define the Subcommand enum for the non-interactive operations on the stored commands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subcommand {
+    List {
+        names_only: bool, // One Group/Name path per line, as used by the shell completions
+    },
+    Run {
+        target: String,
+        values: BTreeMap<String, String>, // Placeholder values given with --set
+    },
+    Show {
+        target: String,
+    },
+    Add {
+        display_name: String,
+        command: String,
+        group: Option<String>, // Group/Sub path, created when missing
+    },
+    Remove {
+        target: String,
+    },
+    Validate,
+    Pick {
+        output: Option<PathBuf>, // Written instead of stdout, for shells that cannot capture it
+    },
+    Allow {
+        assume_yes: bool, // Approve after showing the changes, without asking
+    },
+    Convert {
+        format: ConfigFormat,
+    },
+}
+
+impl Subcommand {
+    // Reviewing or validating a config does not show or run its commands
+    fn requires_trust(&self) -> bool {
+        !matches!(self, Self::Validate | Self::Allow { .. })
+    }
+}
+
+/// Parses the command line arguments, without the program name.
+///
+/// `--version`, `--help`, `--print-schema` and `--run-once` act immediately, so anything
+/// after them is ignored.
+///
+/// # Errors
+///
+/// Returns a user-facing message for unknown, missing or misplaced arguments.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<CliArgs, String> {
+    let mut args = args.into_iter();
+    let mut config = None;
+    let mut format = OutputFormat::Text;
+    let mut group = None;
+    let mut names_only = false;
+    let mut output = None;
+    let mut assume_yes = false;
+    let mut values = BTreeMap::new();
+    let mut positionals = Vec::new();
+    let mut options_ended = false;
+
+    while let Some(arg) = args.next() {
+        if options_ended {
+            positionals.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "--" => options_ended = true,
+            "--version" | "-V" => {
+                return Ok(CliArgs {
+                    config,
+                    format,
+                    action: Action::Version,
+                });
+            }
+            "--help" | "-h" => {
+                return Ok(CliArgs {
+                    config,
+                    format,
+                    action: Action::Help,
+                });
+            }
+            "--print-schema" => {
+                return Ok(CliArgs {
+                    config,
+                    format,
+                    action: Action::PrintSchema,
+                });
+            }
+            "--run-once" => {
+                let command = args.next().ok_or("Missing command for --run-once")?;
+                return Ok(CliArgs {
+                    config,
+                    format,
+                    action: Action::RunOnce(command),
+                });
+            }
+            "--config" | "-c" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| format!("Missing path for {arg}"))?;
+                config = Some(PathBuf::from(path));
+            }
+            "--json" => format = OutputFormat::Json,
+            "--names" => names_only = true,
+            "--yes" | "-y" => assume_yes = true,
+            "--output" | "-o" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| format!("Missing path for {arg}"))?;
+                output = Some(PathBuf::from(path));
+            }
+            "--group" | "-g" => {
+                group = Some(
+                    args.next()
+                        .ok_or_else(|| format!("Missing group for {arg}"))?,
+                );
+            }
+            "--set" => {
+                let assignment = args.next().ok_or("Missing name=value for --set")?;
+                let Some((name, value)) = assignment.split_once('=') else {
+                    return Err(format!("--set expects name=value, got '{assignment}'"));
+                };
+                values.insert(name.trim().to_string(), value.to_string());
+            }
+            _ if arg.len() > 1 && arg.starts_with('-') => {
+                return Err(format!("Unknown argument: {arg}"));
+            }
+            _ => positionals.push(arg),
+        }
+    }
+
+    let mut positionals = positionals.into_iter();
+    let action = match positionals.next() {
+        None => Action::Menu,
+        Some(name) if name == "completions" || name == "shell-init" => {
+            let shell = positionals
+                .next()
+                .ok_or_else(|| format!("Missing shell for {name}"))?;
+            let shell = CompletionShell::from_name(&shell)?;
+            if name == "completions" {
+                Action::Completions(shell)
+            } else {
+                Action::ShellInit(shell)
+            }
+        }
+        Some(name) => {
+            let mut required = |what: &str| {
+                positionals
+                    .next()
+                    .ok_or_else(|| format!("Missing {what} for {name}"))
+            };
+            let subcommand = match name.as_str() {
+                "list" => Subcommand::List {
+                    names_only: std::mem::take(&mut names_only),
+                },
+                "run" => Subcommand::Run {
+                    target: required("target")?,
+                    values: std::mem::take(&mut values),
+                },
+                "show" => Subcommand::Show {
+                    target: required("target")?,
+                },
+                "add" => Subcommand::Add {
+                    display_name: required("display name")?,
+                    command: required("command")?,
+                    group: group.take(),
+                },
+                "remove" | "rm" => Subcommand::Remove {
+                    target: required("target")?,
+                },
+                "validate" => Subcommand::Validate,
+                "pick" => Subcommand::Pick {
+                    output: output.take(),
+                },
+                "allow" => Subcommand::Allow {
+                    assume_yes: std::mem::take(&mut assume_yes),
+                },
+                "convert" => Subcommand::Convert {
+                    format: ConfigFormat::from_name(&required("format")?)?,
+                },
+                _ => return Err(format!("Unknown argument: {name}")),
+            };
+            Action::Subcommand(subcommand)
+        }
+    };
+
+    if let Some(extra) = positionals.next() {
+        return Err(format!("Unexpected argument: {extra}"));
+    }
+    if group.is_some() {
+        return Err("--group can only be used with add".to_string());
+    }
+    if !values.is_empty() {
+        return Err("--set can only be used with run".to_string());
+    }
+    if names_only {
+        return Err("--names can only be used with list".to_string());
+    }
+    if output.is_some() {
+        return Err("--output can only be used with pick".to_string());
+    }
+    if assume_yes {
+        return Err("--yes can only be used with allow".to_string());
+    }
+    // The command's own output would be mixed into the JSON document
+    if format == OutputFormat::Json
+        && matches!(
+            action,
+            Action::Subcommand(
+                Subcommand::Run { .. }
+                    | Subcommand::Pick { .. }
+                    | Subcommand::Allow { .. }
+                    | Subcommand::Convert { .. }
+            )
+        )
+    {
+        return Err("--json cannot be used with run, pick, allow or convert".to_string());
+    }
+
+    Ok(CliArgs {
+        config,
+        format,
+        action,
+    })
+}
+
+/// Runs a subcommand against the config at `config_path` and returns the process exit code.
+///
+/// `run` exits with the command's own status; other failures print a message and return 1.
+/// Errors are always printed as text on stderr, also in JSON mode.
+/// `config_given` tells whether `config_path` came from `--config` rather than discovery.
+#[must_use]
+pub fn run_subcommand(
+    config_path: &Path,
+    subcommand: &Subcommand,
+    format: OutputFormat,
+    config_given: bool,
+) -> i32 {
+    if subcommand.requires_trust() {
+        match trust::ensure_trusted(config_path, false) {
+            Ok(true) => {}
+            Ok(false) => return 1,
+            Err(e) => {
+                eprintln!("❌  {e:#}");
+                return 1;
+            }
+        }
+    }
+
+    let result = match subcommand {
+        Subcommand::List { names_only } => list_commands(config_path, *names_only, format),
+        Subcommand::Run { target, values } => run_stored_command(config_path, target, values),
+        Subcommand::Show { target } => show_command(config_path, target, format),
+        Subcommand::Add {
+            display_name,
+            command,
+            group,
+        } => add_stored_command(config_path, display_name, command, group.as_deref(), format),
+        Subcommand::Remove { target } => remove_stored_command(config_path, target, format),
+        Subcommand::Validate => validate_stored_config(config_path, format),
+        Subcommand::Pick { output } => pick_command_line(config_path, output.as_deref()),
+        Subcommand::Allow { assume_yes } => trust::allow(config_path, *assume_yes),
+        Subcommand::Convert { format } => {
+            convert_config_file(config_path, *format, pinned_by(config_given))
+        }
+    };
+
+    result.unwrap_or_else(|e| {
+        eprintln!("❌  {e:#}");
+        1
+    })
+}
+
+/// Finds the command a `run`, `show` or `remove` target refers to.
+///
+/// A number selects by position in `list`; otherwise the target is matched against
+/// command IDs, then `Group/Name` paths, then display names, ignoring case.
+///
+/// # Errors
+///
+/// Returns a user-facing message when nothing or more than one command matches.
+pub fn resolve_target<'a>(config: &'a Config, target: &str) -> Result<CommandLocation<'a>, String> {
+    let target = target.trim();
+    let locations = config.all_commands();
+
+    if let Ok(number) = target.parse::<usize>() {
+        return number
+            .checked_sub(1)
+            .and_then(|index| locations.get(index).cloned())
+            .ok_or_else(|| {
+                format!("There is no command number {number}. Run `list` to see them.")
+            });
+    }
+
+    if let Some(location) = locations
+        .iter()
+        .find(|location| location.command.id == target)
+    {
+        return Ok(location.clone());
+    }
+
+    let by_path: Vec<&CommandLocation> = locations
+        .iter()
+        .filter(|location| location.path().eq_ignore_ascii_case(target))
+        .collect();
+    let matches = if by_path.is_empty() {
+        locations
+            .iter()
+            .filter(|location| location.command.display_name.eq_ignore_ascii_case(target))
+            .collect()
+    } else {
+        by_path
+    };
+
+    match matches.as_slice() {
+        [] => Err(format!("No command named '{target}'.")),
+        [location] => Ok((*location).clone()),
+        _ => Err(format!(
+            "'{target}' matches several commands: {}. Use the Group/Name path or the number.",
+            matches
+                .iter()
+                .map(|location| location.path())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+// Returns the setting that named the config path, which has to follow a converted config
+fn pinned_by(config_given: bool) -> Option<&'static str> {
+    if config_given {
+        Some("--config")
+    } else if std::env::var_os("CLI_MENU_CONFIG").is_some_and(|path| !path.is_empty()) {
+        Some("CLI_MENU_CONFIG")
+    } else {
+        None
+    }
+}
+
+// Writes the config next to itself in another format and moves the original to a backup
+fn convert_config_file(
+    config_path: &Path,
+    format: ConfigFormat,
+    pinned_by: Option<&str>,
+) -> anyhow::Result<i32> {
+    if ConfigFormat::from_path(config_path) == format {
+        println!(
+            "✅ {} is already in {} format.",
+            config_path.display(),
+            format.extension().to_uppercase()
+        );
+        return Ok(0);
+    }
+    let target = config_path.with_extension(format.extension());
+    if target.exists() {
+        anyhow::bail!(
+            "{} already exists. Move it away before converting.",
+            target.display()
+        );
+    }
+
+    let config = load_config(config_path)?;
+    save_config(&target, &config)?;
+    let backup = backup_path(config_path, "");
+    std::fs::rename(config_path, &backup).with_context(|| {
+        format!(
+            "unable to move {} to {}",
+            config_path.display(),
+            backup.display()
+        )
+    })?;
+    // The original was trusted to get here, and the new file holds the same commands
+    let trust_store = trust::trust_store_path().map_err(anyhow::Error::msg)?;
+    trust::approve_created_config(&trust_store, &target)?;
+
+    println!(
+        "✅ Converted {} to {}. The original was moved to {}",
+        config_path.display(),
+        target.display(),
+        backup.display()
+    );
+    // Discovery finds the new file by itself, but a path given explicitly now points at nothing
+    if let Some(setting) = pinned_by {
+        eprintln!(
+            "⚠️  {setting} still names {}; point it at {} from now on.",
+            config_path.display(),
+            target.display()
+        );
+    }
+    Ok(0)
+}
+
+fn list_commands(
+    config_path: &Path,
+    names_only: bool,
+    format: OutputFormat,
+) -> anyhow::Result<i32> {
+    let config = load_config(config_path)?;
+    let locations = config.all_commands();
+    if names_only {
+        for location in &locations {
+            println!("{}", location.path());
+        }
+        return Ok(0);
+    }
+    if format == OutputFormat::Json {
+        let completion = todays_completion(config_path, &config);
+        report::print_json(&CommandListReport {
+            schema_version: SCHEMA_VERSION,
+            day: completion.day,
+            commands: locations
+                .iter()
+                .enumerate()
+                .map(|(index, location)| {
+                    CommandReport::new(index + 1, location, &completion.statuses)
+                })
+                .collect(),
+        })?;
+        return Ok(0);
+    }
+
+    let max_number_width = locations.len().to_string().len();
+    for (index, location) in locations.iter().enumerate() {
+        let number = index + 1;
+        println!("{number: >max_number_width$}. {}", location.path());
+    }
+    Ok(0)
+}
+
+fn show_command(config_path: &Path, target: &str, format: OutputFormat) -> anyhow::Result<i32> {
+    let config = load_config(config_path)?;
+    let location = resolve_target(&config, target).map_err(anyhow::Error::msg)?;
+    let command = location.command;
+    if format == OutputFormat::Json {
+        let completion = todays_completion(config_path, &config);
+        report::print_json(&CommandDetailReport {
+            schema_version: SCHEMA_VERSION,
+            command: CommandReport::new(
+                list_number(&config, &command.id),
+                &location,
+                &completion.statuses,
+            ),
+        })?;
+        return Ok(0);
+    }
+
+    println!("Name:         {}", location.path());
+    println!("ID:           {}", command.id);
+    if let Some(layer) = &command.layer {
+        println!(
+            "Layer:        {} (read-only, {})",
+            layer_name(layer),
+            layer.display()
+        );
+    }
+    println!("Command:      {}", command.command_text());
+    if command.argv.is_empty() {
+        let shell = config.shell_for(command).map_or("sh", ShellSpec::program);
+        println!("Shell:        {shell}");
+    } else {
+        println!("Shell:        none (argv)");
+    }
+    if let Some(cwd) = &command.cwd {
+        println!("Directory:    {cwd}");
+    }
+    if !command.env.is_empty() {
+        let names: Vec<&str> = command.env.keys().map(String::as_str).collect();
+        println!("Environment:  {}", names.join(", "));
+    }
+    if let Some(env_file) = &command.env_file {
+        println!("Env file:     {env_file}");
+    }
+    let placeholders = command_placeholders(command);
+    if !placeholders.is_empty() {
+        println!("Placeholders: {}", placeholders.join(", "));
+    }
+    Ok(0)
+}
+
+fn run_stored_command(
+    config_path: &Path,
+    target: &str,
+    values: &BTreeMap<String, String>,
+) -> anyhow::Result<i32> {
+    let config = load_config(config_path)?;
+    let command = resolve_target(&config, target)
+        .map_err(anyhow::Error::msg)?
+        .command;
+    let mut request = config
+        .command_request(command)
+        .map_err(anyhow::Error::msg)?;
+    if !fill_placeholders(command, &mut request, values) {
+        anyhow::bail!("'{}' was not run.", command.display_name);
+    }
+
+    let result = run_and_record(&request, Some(command));
+    // Runs from scripts count towards today's progress in the menu as well
+    if let Err(e) = state::record_completion(
+        config_path,
+        config.daily_reset_time.as_deref(),
+        &command.id,
+        CommandStatus::from_run_result(&result),
+    ) {
+        eprintln!("⚠️  Could not save today's progress: {e}");
+    }
+    Ok(result?.code().unwrap_or(1))
+}
+
+fn add_stored_command(
+    config_path: &Path,
+    display_name: &str,
+    command: &str,
+    group: Option<&str>,
+    format: OutputFormat,
+) -> anyhow::Result<i32> {
+    let mut config = load_config(config_path)?;
+    let group_path = group.map_or_else(Vec::new, |group| config.ensure_group_path(group));
+    let mut changes_made = false;
+    add_command_to_config(
+        &mut config,
+        &group_path,
+        display_name.to_string(),
+        command.to_string(),
+        &mut changes_made,
+    );
+    save_validated(config_path, &config)?;
+
+    if format == OutputFormat::Json {
+        // The new command is the last one in its group
+        let locations = config.all_commands();
+        let (number, location) = locations
+            .iter()
+            .enumerate()
+            .rfind(|(_, location)| location.group == group_path)
+            .context("added command is missing")?;
+        report::print_json(&ChangeReport {
+            schema_version: SCHEMA_VERSION,
+            action: ChangeAction::Added,
+            command: CommandReport::new(number + 1, location, &BTreeMap::new()),
+        })?;
+        return Ok(0);
+    }
+
+    let mut name = config.group_names(&group_path).join("/");
+    if !name.is_empty() {
+        name.push('/');
+    }
+    name.push_str(display_name);
+    println!("✅ Added '{name}'.");
+    Ok(0)
+}
+
+fn remove_stored_command(
+    config_path: &Path,
+    target: &str,
+    format: OutputFormat,
+) -> anyhow::Result<i32> {
+    let mut config = load_config(config_path)?;
+    let original = config.clone();
+    let location = resolve_target(&original, target).map_err(anyhow::Error::msg)?;
+    if let Some(layer) = &location.command.layer {
+        anyhow::bail!(
+            "'{}' comes from the read-only layer {}; remove it there",
+            location.path(),
+            layer.display()
+        );
+    }
+    let mut changes_made = false;
+    delete_command_at(
+        &mut config,
+        &location.group,
+        location.index,
+        &mut changes_made,
+    );
+    save_validated(config_path, &config)?;
+
+    if format == OutputFormat::Json {
+        report::print_json(&ChangeReport {
+            schema_version: SCHEMA_VERSION,
+            action: ChangeAction::Removed,
+            command: CommandReport::new(
+                list_number(&original, &location.command.id),
+                &location,
+                &BTreeMap::new(),
+            ),
+        })?;
+    } else {
+        println!("✅ Removed '{}'.", location.path());
+    }
+    Ok(0)
+}
+
+fn validate_stored_config(config_path: &Path, format: OutputFormat) -> anyhow::Result<i32> {
+    let config = load_config(config_path)?;
+    let report = ValidationReport::new(config_path, validate_config(&config));
+    if format == OutputFormat::Json {
+        report::print_json(&report)?;
+    } else if report.valid {
+        println!("✅ {} is valid.", config_path.display());
+    } else {
+        println!("❌ {} has problems:", config_path.display());
+        for error in &report.errors {
+            println!("  - {error}");
+        }
+    }
+    Ok(i32::from(!report.valid))
+}
+
+fn pick_command_line(config_path: &Path, output: Option<&Path>) -> anyhow::Result<i32> {
+    let config = load_config(config_path)?;
+    let completion = todays_completion(config_path, &config);
+    let Some(command) = pick_command(&config, &completion.statuses) else {
+        return Ok(1);
+    };
+    let mut request = config
+        .command_request(command)
+        .map_err(anyhow::Error::msg)?;
+    if !fill_placeholders(command, &mut request, &BTreeMap::new()) {
+        return Ok(1);
+    }
+
+    let line = request.shell_line();
+    match output {
+        Some(path) => std::fs::write(path, format!("{line}\n"))
+            .with_context(|| format!("unable to write the picked command to {}", path.display()))?,
+        None => println!("{line}"),
+    }
+    Ok(0)
+}
+
+// Returns today's completion state, without yesterday's results after the daily reset
+fn todays_completion(config_path: &Path, config: &Config) -> CompletionState {
+    let mut completion = state::state_file_path(config_path)
+        .map_err(anyhow::Error::msg)
+        .and_then(|path| state::load_state(&path))
+        .unwrap_or_else(|e| {
+            eprintln!("⚠️  Could not load today's progress: {e}");
+            CompletionState::default()
+        });
+    let reset_time =
+        state::parse_reset_time(config.daily_reset_time.as_deref()).unwrap_or_default();
+    completion.roll_over(state::current_completion_day(reset_time));
+    completion
+}
+
+// Returns the 1-based `list` number of the command with `id`
+fn list_number(config: &Config, id: &str) -> usize {
+    config
+        .all_commands()
+        .iter()
+        .position(|location| location.command.id == id)
+        .map_or(0, |index| index + 1)
+}
+
+// Saves the config only when it passes the same validation as the edit menu
+fn save_validated(config_path: &Path, config: &Config) -> anyhow::Result<()> {
+    if let Err(errors) = validate_config(config) {
+        anyhow::bail!(
+            "Config validation failed. Changes not saved:\n  - {}",
+            errors.join("\n  - ")
+        );
+    }
+    save_config(config_path, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CommandGroup, CommandOption};
+
+    fn args(values: &[&str]) -> Result<CliArgs, String> {
+        parse_args(values.iter().map(ToString::to_string))
+    }
+
+    fn command(id: &str, display_name: &str) -> CommandOption {
+        CommandOption {
+            id: id.into(),
+            display_name: display_name.into(),
+            command: format!("echo {id}"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_args_reads_subcommands_and_options() {
+        assert_eq!(args(&[]).unwrap().action, Action::Menu);
+        assert_eq!(
+            args(&["--print-schema", "--bogus"]).unwrap().action,
+            Action::PrintSchema
+        );
+        assert_eq!(
+            args(&["list", "--config", "menu.json"]).unwrap(),
+            CliArgs {
+                config: Some(PathBuf::from("menu.json")),
+                format: OutputFormat::Text,
+                action: Action::Subcommand(Subcommand::List { names_only: false }),
+            }
+        );
+        assert_eq!(
+            args(&["run", "Deploy/Ship", "--set", "branch=main"])
+                .unwrap()
+                .action,
+            Action::Subcommand(Subcommand::Run {
+                target: "Deploy/Ship".into(),
+                values: BTreeMap::from([("branch".into(), "main".into())]),
+            })
+        );
+        assert_eq!(
+            args(&["add", "-g", "Tools", "--", "Neg", "-1"])
+                .unwrap()
+                .action,
+            Action::Subcommand(Subcommand::Add {
+                display_name: "Neg".into(),
+                command: "-1".into(),
+                group: Some("Tools".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_args_reads_json_flag_anywhere() {
+        assert_eq!(
+            args(&["--json", "list"]).unwrap().format,
+            OutputFormat::Json
+        );
+        assert_eq!(
+            args(&["validate", "--json"]).unwrap(),
+            CliArgs {
+                config: None,
+                format: OutputFormat::Json,
+                action: Action::Subcommand(Subcommand::Validate),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_args_reads_completions_and_names() {
+        assert_eq!(
+            args(&["completions", "fish"]).unwrap().action,
+            Action::Completions(CompletionShell::Fish)
+        );
+        assert_eq!(
+            args(&["list", "--names"]).unwrap().action,
+            Action::Subcommand(Subcommand::List { names_only: true })
+        );
+    }
+
+    #[test]
+    fn test_parse_args_reads_pick_and_shell_init() {
+        assert_eq!(
+            args(&["pick", "-o", "/tmp/picked"]).unwrap().action,
+            Action::Subcommand(Subcommand::Pick {
+                output: Some(PathBuf::from("/tmp/picked")),
+            })
+        );
+        assert_eq!(
+            args(&["shell-init", "zsh"]).unwrap().action,
+            Action::ShellInit(CompletionShell::Zsh)
+        );
+        assert!(args(&["list", "--output", "x"]).is_err());
+        assert!(args(&["pick", "--json"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_reads_allow() {
+        assert_eq!(
+            args(&["allow", "--yes"]).unwrap().action,
+            Action::Subcommand(Subcommand::Allow { assume_yes: true })
+        );
+        assert!(args(&["list", "-y"]).is_err());
+        assert!(args(&["allow", "--json"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_reads_convert() {
+        assert_eq!(
+            args(&["convert", "YAML"]).unwrap().action,
+            Action::Subcommand(Subcommand::Convert {
+                format: ConfigFormat::Yaml
+            })
+        );
+        assert_eq!(
+            args(&["convert"]).unwrap_err(),
+            "Missing format for convert"
+        );
+        assert!(args(&["convert", "ini"]).is_err());
+        assert!(args(&["convert", "toml", "--json"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_rejects_bad_arguments() {
+        assert_eq!(args(&["--bogus"]).unwrap_err(), "Unknown argument: --bogus");
+        assert_eq!(
+            args(&["frobnicate"]).unwrap_err(),
+            "Unknown argument: frobnicate"
+        );
+        assert_eq!(args(&["run"]).unwrap_err(), "Missing target for run");
+        assert!(args(&["list", "extra"]).is_err());
+        assert!(args(&["list", "--group", "Tools"]).is_err());
+        assert!(args(&["run", "1", "--set", "novalue"]).is_err());
+        assert!(args(&["run", "1", "--json"]).is_err());
+        assert!(args(&["show", "1", "--names"]).is_err());
+        assert_eq!(
+            args(&["completions", "tcsh"]).unwrap_err(),
+            "Unknown shell 'tcsh'. Use bash, zsh or fish."
+        );
+    }
+
+    #[test]
+    fn test_resolve_target_by_number_id_path_and_name() {
+        let mut deploy = CommandGroup::new("Deploy");
+        deploy.commands = vec![command("ship", "Ship"), command("deploy-list", "List")];
+        let config = Config {
+            commands: vec![command("list", "List")],
+            groups: vec![deploy],
+            ..Default::default()
+        };
+
+        let id_of = |target: &str| {
+            resolve_target(&config, target).map(|location| location.command.id.clone())
+        };
+
+        assert_eq!(id_of("2").unwrap(), "ship");
+        assert_eq!(id_of("deploy-list").unwrap(), "deploy-list");
+        assert_eq!(id_of("deploy/list").unwrap(), "deploy-list");
+        assert_eq!(id_of("list").unwrap(), "list");
+        assert_eq!(id_of("SHIP").unwrap(), "ship");
+        assert!(id_of("4").is_err());
+        assert!(id_of("Missing").is_err());
+    }
+
+    #[test]
+    fn test_resolve_target_reports_ambiguous_names() {
+        let mut a = CommandGroup::new("A");
+        a.commands = vec![command("a-build", "Build")];
+        let mut b = CommandGroup::new("B");
+        b.commands = vec![command("b-build", "Build")];
+        let config = Config {
+            groups: vec![a, b],
+            ..Default::default()
+        };
+
+        let error = resolve_target(&config, "build").unwrap_err();
+
+        assert!(error.contains("A/Build, B/Build"), "{error}");
+    }
+}
@@ -0,0 +1,367 @@
+//This file contains the shell scripts printed by the completions and shell-init subcommands.
+//The completion scripts complete stored command paths by calling `list --names` with the same --config.
+//The shell-init scripts bind Alt-M to `pick` and insert the chosen command into the command line.
+
+// Define the CompletionShell enum for the shells completion scripts are generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl CompletionShell {
+    /// Parses a shell name such as `bash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a user-facing message naming the supported shells.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(format!("Unknown shell '{name}'. Use bash, zsh or fish.")),
+        }
+    }
+
+    /// Returns the completion script for this shell.
+    #[must_use]
+    pub fn script(self) -> &'static str {
+        match self {
+            Self::Bash => BASH_SCRIPT,
+            Self::Zsh => ZSH_SCRIPT,
+            Self::Fish => FISH_SCRIPT,
+        }
+    }
+
+    /// Returns the key binding script for this shell, as printed by `shell-init`.
+    #[must_use]
+    pub fn widget_script(self) -> &'static str {
+        match self {
+            Self::Bash => BASH_WIDGET,
+            Self::Zsh => ZSH_WIDGET,
+            Self::Fish => FISH_WIDGET,
+        }
+    }
+}
+
+// Load with: source <(shell_command_menu completions bash)
+const BASH_SCRIPT: &str = r#"# bash completion for shell_command_menu
+_shell_command_menu() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local config=() subcommand="" positionals=0 i
+
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -c|--config) config=(--config "${COMP_WORDS[i+1]/#\~/$HOME}"); ((i++)) ;;
+            -g|--group|--set|--run-once|-o|--output) ((i++)) ;;
+            -*) ;;
+            *) [[ -z "$subcommand" ]] && subcommand="${COMP_WORDS[i]}"; ((positionals++)) ;;
+        esac
+    done
+
+    case "$prev" in
+        -c|--config|-o|--output) COMPREPLY=($(compgen -f -- "$cur")); return ;;
+        -g|--group|--set|--run-once) return ;;
+    esac
+
+    if [[ "$cur" == -* ]]; then
+        local flags="--config --json --help --version --run-once --print-schema"
+        case "$subcommand" in
+            run) flags+=" --set" ;;
+            add) flags+=" --group" ;;
+            list) flags+=" --names" ;;
+            pick) flags+=" --output" ;;
+            allow) flags+=" --yes" ;;
+        esac
+        COMPREPLY=($(compgen -W "$flags" -- "$cur"))
+        return
+    fi
+
+    case "$subcommand" in
+        "")
+            COMPREPLY=($(compgen -W "list run show add remove validate pick allow convert completions shell-init" -- "$cur"))
+            ;;
+        run|show|remove|rm)
+            if ((positionals == 1)); then
+                local IFS=$'\n'
+                local names
+                names="$(shell_command_menu "${config[@]}" list --names 2>/dev/null)"
+                COMPREPLY=($(compgen -W "$names" -- "$cur"))
+                COMPREPLY=($(printf '%q\n' "${COMPREPLY[@]}"))
+            fi
+            ;;
+        convert)
+            ((positionals == 1)) && COMPREPLY=($(compgen -W "json jsonc toml yaml" -- "$cur"))
+            ;;
+        completions|shell-init)
+            ((positionals == 1)) && COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur"))
+            ;;
+    esac
+}
+complete -F _shell_command_menu shell_command_menu
+"#;
+
+// Load with: source <(shell_command_menu completions zsh), or save as _shell_command_menu in $fpath
+const ZSH_SCRIPT: &str = r#"#compdef shell_command_menu
+
+_shell_command_menu_targets() {
+    local -a config targets
+    if [[ -n $config_path ]]; then
+        config=(--config ${~${(Q)config_path}})
+    fi
+    targets=("${(@f)$(shell_command_menu $config list --names 2>/dev/null)}")
+    compadd -a targets
+}
+
+_shell_command_menu() {
+    local curcontext="$curcontext" state line
+    typeset -A opt_args
+
+    _arguments -C \
+        '(-c --config)'{-c,--config}'[use an alternate config file]:config file:_files' \
+        '--json[print results as JSON]' \
+        '(- *)'{-h,--help}'[print help]' \
+        '(- *)'{-V,--version}'[print the version]' \
+        '(- *)--run-once[run a raw shell command once]:shell command:' \
+        '(- *)--print-schema[print the JSON Schema of the config file]' \
+        '1: :->subcommand' \
+        '*:: :->args'
+
+    local config_path=${opt_args[--config]:-$opt_args[-c]}
+    case $state in
+        subcommand)
+            local -a subcommands
+            subcommands=(
+                'list:list the commands with their numbers'
+                'run:run a stored command'
+                'show:show how a command is configured'
+                'add:add a command'
+                'remove:remove a command'
+                'validate:check the config'
+                'pick:choose a command and print it instead of running it'
+                'allow:review and trust a project config'
+                'convert:rewrite the config in another format'
+                'completions:print a shell completion script'
+                'shell-init:print key bindings that insert a picked command'
+            )
+            _describe -t subcommands 'subcommand' subcommands
+            ;;
+        args)
+            case $line[1] in
+                run)
+                    _arguments '1: :_shell_command_menu_targets' '*--set[placeholder value]:name=value:'
+                    ;;
+                show|remove|rm)
+                    _arguments '1: :_shell_command_menu_targets'
+                    ;;
+                add)
+                    _arguments '1:display name:' '2:command:' '(-g --group)'{-g,--group}'[group path]:group:'
+                    ;;
+                list)
+                    _arguments '--names[print only the command paths]'
+                    ;;
+                allow)
+                    _arguments '(-y --yes)'{-y,--yes}'[trust without asking]'
+                    ;;
+                convert)
+                    _arguments '1:format:(json jsonc toml yaml)'
+                    ;;
+                pick)
+                    _arguments '(-o --output)'{-o,--output}'[write the command to a file]:output file:_files'
+                    ;;
+                completions|shell-init)
+                    _arguments '1:shell:(bash zsh fish)'
+                    ;;
+            esac
+            ;;
+    esac
+}
+
+if [[ $zsh_eval_context[-1] == loadautofunc ]]; then
+    _shell_command_menu "$@"
+else
+    compdef _shell_command_menu shell_command_menu
+fi
+"#;
+
+// Load with: shell_command_menu completions fish | source
+const FISH_SCRIPT: &str = r#"# fish completion for shell_command_menu
+function __shell_command_menu_config
+    set -l tokens (commandline -opc)
+    set -l index (contains -i -- --config $tokens; or contains -i -- -c $tokens)
+    if test -n "$index"; and test (count $tokens) -gt $index
+        echo --config
+        echo $tokens[(math $index + 1)]
+    end
+end
+
+function __shell_command_menu_targets
+    shell_command_menu (__shell_command_menu_config) list --names 2>/dev/null
+end
+
+set -l subcommands list run show add remove rm validate pick allow convert completions shell-init
+complete -c shell_command_menu -f
+complete -c shell_command_menu -s c -l config -r -F -d 'Use an alternate config file'
+complete -c shell_command_menu -l json -d 'Print results as JSON'
+complete -c shell_command_menu -s h -l help -d 'Print help'
+complete -c shell_command_menu -s V -l version -d 'Print the version'
+complete -c shell_command_menu -l run-once -x -d 'Run a raw shell command once'
+complete -c shell_command_menu -l print-schema -d 'Print the JSON Schema of the config file'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a list -d 'List the commands with their numbers'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a run -d 'Run a stored command'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a show -d 'Show how a command is configured'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a add -d 'Add a command'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a remove -d 'Remove a command'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a validate -d 'Check the config'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a pick -d 'Choose a command and print it instead of running it'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a allow -d 'Review and trust a project config'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a convert -d 'Rewrite the config in another format'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a completions -d 'Print a shell completion script'
+complete -c shell_command_menu -n "not __fish_seen_subcommand_from $subcommands" -a shell-init -d 'Print key bindings that insert a picked command'
+complete -c shell_command_menu -n "__fish_seen_subcommand_from run show remove rm" -a "(__shell_command_menu_targets)"
+complete -c shell_command_menu -n "__fish_seen_subcommand_from run" -l set -x -d 'Placeholder value as name=value'
+complete -c shell_command_menu -n "__fish_seen_subcommand_from add" -s g -l group -x -d 'Group path'
+complete -c shell_command_menu -n "__fish_seen_subcommand_from list" -l names -d 'Print only the command paths'
+complete -c shell_command_menu -n "__fish_seen_subcommand_from allow" -s y -l yes -d 'Trust without asking'
+complete -c shell_command_menu -n "__fish_seen_subcommand_from pick" -s o -l output -r -F -d 'Write the command to a file'
+complete -c shell_command_menu -n "__fish_seen_subcommand_from convert" -a "json jsonc toml yaml"
+complete -c shell_command_menu -n "__fish_seen_subcommand_from completions shell-init" -a "bash zsh fish"
+"#;
+
+// A picked `cd` runs right away so it changes this shell's directory; anything else is inserted.
+// It is only run when it has no command separators, redirections, substitutions or control
+// characters, so nothing but `cd` can run, while `~` and quotes still work.
+// Load with: eval "$(shell_command_menu shell-init bash)"
+const BASH_WIDGET: &str = r#"# Alt-M picks a stored command with shell_command_menu and inserts it at the cursor
+__shell_command_menu_pick() {
+    local picked cd_only='^cd [^;&|<>()$`[:cntrl:]]*$'
+    picked="$(shell_command_menu pick </dev/tty)" || return
+    if [[ "$picked" =~ $cd_only ]]; then
+        eval "$picked"
+    else
+        READLINE_LINE="${READLINE_LINE:0:READLINE_POINT}${picked}${READLINE_LINE:READLINE_POINT}"
+        READLINE_POINT=$((READLINE_POINT + ${#picked}))
+    fi
+}
+bind -x '"\em": __shell_command_menu_pick'
+"#;
+
+// Load with: eval "$(shell_command_menu shell-init zsh)"
+const ZSH_WIDGET: &str = r#"# Alt-M picks a stored command with shell_command_menu and inserts it at the cursor
+__shell_command_menu_pick() {
+    local picked
+    if picked="$(shell_command_menu pick </dev/tty)" && [[ -n $picked ]]; then
+        if [[ $picked =~ '^cd [^;&|<>()$`[:cntrl:]]*$' ]]; then
+            eval "$picked"
+        else
+            LBUFFER+=$picked
+        fi
+    fi
+    zle reset-prompt
+}
+zle -N __shell_command_menu_pick
+bindkey '\em' __shell_command_menu_pick
+"#;
+
+// Load with: shell_command_menu shell-init fish | source
+const FISH_WIDGET: &str = r#"# Alt-M picks a stored command with shell_command_menu and inserts it at the cursor
+function __shell_command_menu_pick
+    set -l file (mktemp)
+    if shell_command_menu pick --output $file </dev/tty
+        set -l picked (string collect <$file)
+        if string match -q -r '^cd [^;&|<>()$`[:cntrl:]]*$' -- $picked
+            eval $picked
+        else
+            commandline -i -- $picked
+        end
+    end
+    rm -f $file
+    commandline -f repaint
+end
+bind \em __shell_command_menu_pick
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CD_ONLY_PATTERN: &str = "^cd [^;&|<>()$`[:cntrl:]]*$";
+
+    #[test]
+    fn test_from_name_accepts_supported_shells() {
+        assert_eq!(CompletionShell::from_name("Zsh"), Ok(CompletionShell::Zsh));
+        assert!(CompletionShell::from_name("tcsh").is_err());
+    }
+
+    #[test]
+    fn test_scripts_complete_targets_from_the_binary() {
+        for shell in [
+            CompletionShell::Bash,
+            CompletionShell::Zsh,
+            CompletionShell::Fish,
+        ] {
+            let script = shell.script();
+            assert!(script.contains("shell_command_menu "), "{shell:?}");
+            assert!(script.contains("list --names"), "{shell:?}");
+            assert!(script.contains("--config"), "{shell:?}");
+            assert!(script.contains("shell-init"), "{shell:?}");
+        }
+    }
+
+    #[test]
+    fn test_widgets_insert_a_picked_command() {
+        for shell in [
+            CompletionShell::Bash,
+            CompletionShell::Zsh,
+            CompletionShell::Fish,
+        ] {
+            let script = shell.widget_script();
+            assert!(script.contains("shell_command_menu pick"), "{shell:?}");
+            assert!(script.contains(CD_ONLY_PATTERN), "{shell:?}");
+            assert!(script.contains("\\em"), "{shell:?}");
+        }
+    }
+
+    // Runs the bash widget with `picked` as the chosen command; returns the directory and line
+    fn run_bash_widget(dir: &std::path::Path, picked: &str) -> (String, String) {
+        let widget = CompletionShell::Bash
+            .widget_script()
+            .replace("</dev/tty", "</dev/null");
+        let script = format!(
+            "shell_command_menu() {{ printf '%s' \"$PICKED\"; }}\nbind() {{ :; }}\n{widget}\n\
+             READLINE_LINE=''; READLINE_POINT=0\n__shell_command_menu_pick\n\
+             printf '%s\\n%s' \"$PWD\" \"$READLINE_LINE\""
+        );
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(script)
+            .current_dir(dir)
+            .env("PICKED", picked)
+            .output()
+            .expect("bash should run");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let (pwd, line) = stdout.split_once('\n').unwrap_or_default();
+        (pwd.to_string(), line.to_string())
+    }
+
+    #[test]
+    fn test_bash_widget_only_runs_plain_cd() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = std::fs::canonicalize(dir.path()).unwrap();
+        std::fs::create_dir(dir.join("sub dir")).unwrap();
+
+        let (pwd, line) = run_bash_widget(&dir, "cd sub\\ dir");
+        assert_eq!(pwd, dir.join("sub dir").display().to_string());
+        assert_eq!(line, "");
+
+        for picked in ["cd $(touch x)", "cd `touch x`", "cd sub\ntouch x", "cd >x"] {
+            let (pwd, line) = run_bash_widget(&dir, picked);
+            assert_eq!(pwd, dir.display().to_string(), "{picked:?}");
+            assert_eq!(line, picked);
+            assert!(!dir.join("x").exists(), "{picked:?} ran more than cd");
+        }
+    }
+}
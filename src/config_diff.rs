@@ -0,0 +1,557 @@
+//This file contains the structured comparison of two versions of a config.
+//Commands are matched by ID, so the edit menu can tell added, removed, modified and moved
+//commands apart when it summarizes an UNDO step or asks whether to save the session.
+
+use crate::config::{CommandLocation, CommandOption, Config};
+use std::collections::HashMap;
+use termion::color;
+
+// Define the kinds of change, which decide the marker and color of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Modified,
+    Moved,
+}
+
+// Define one line of a rendered diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String, // Starts in lower case, such as "added 'Tools/Build'"
+}
+
+impl DiffLine {
+    fn new(kind: DiffKind, text: String) -> Self {
+        Self { kind, text }
+    }
+
+    /// Returns the text with its first letter capitalized.
+    #[must_use]
+    pub fn sentence(&self) -> String {
+        capitalize(&self.text)
+    }
+
+    fn marker(&self) -> &'static str {
+        match self.kind {
+            DiffKind::Added => "+",
+            DiffKind::Removed => "-",
+            DiffKind::Modified => "~",
+            DiffKind::Moved => "→",
+        }
+    }
+}
+
+// Define a command whose settings changed, by its path after the change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandChange {
+    pub path: String,
+    pub fields: Vec<String>, // What changed, such as "command 'make' → 'make all'"
+}
+
+// Define a global setting that changed, with both values as shown to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingChange {
+    pub name: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+// Define the differences between two versions of a config
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub groups_added: Vec<String>,
+    pub groups_removed: Vec<String>,
+    pub groups_renamed: Vec<(String, String)>,
+    pub added: Vec<String>,   // Paths of new commands
+    pub removed: Vec<String>, // Paths of deleted commands
+    pub modified: Vec<CommandChange>,
+    pub moved: Vec<(String, String)>, // Commands now in another group, from and to
+    pub reordered: Vec<String>, // Menu levels whose commands changed order; "" is the top level
+    pub settings: Vec<SettingChange>,
+    pub other_changes: bool, // Something else differs, such as env or includes
+}
+
+impl ConfigDiff {
+    /// Compares two versions of a config.
+    #[must_use]
+    pub fn between(before: &Config, after: &Config) -> Self {
+        let mut diff = Self::default();
+        diff.compare_groups(before, after);
+        diff.compare_commands(before, after);
+        diff.compare_settings(before, after);
+        diff.other_changes = diff.is_empty() && before != after;
+        diff
+    }
+
+    /// Returns `true` when nothing changed.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lines().is_empty()
+    }
+
+    /// Returns one line per change: groups, then added, modified, moved and removed commands,
+    /// then settings.
+    #[must_use]
+    pub fn lines(&self) -> Vec<DiffLine> {
+        let mut lines = Vec::new();
+        for (from, to) in &self.groups_renamed {
+            lines.push(DiffLine::new(
+                DiffKind::Modified,
+                format!("renamed group '{from}' to '{to}'"),
+            ));
+        }
+        for name in &self.groups_added {
+            lines.push(DiffLine::new(
+                DiffKind::Added,
+                format!("created group '{name}'"),
+            ));
+        }
+        for name in &self.groups_removed {
+            lines.push(DiffLine::new(
+                DiffKind::Removed,
+                format!("deleted group '{name}'"),
+            ));
+        }
+        for path in &self.added {
+            lines.push(DiffLine::new(DiffKind::Added, format!("added '{path}'")));
+        }
+        for change in &self.modified {
+            lines.push(DiffLine::new(
+                DiffKind::Modified,
+                format!("edited '{}': {}", change.path, change.fields.join(", ")),
+            ));
+        }
+        for (from, to) in &self.moved {
+            lines.push(DiffLine::new(
+                DiffKind::Moved,
+                format!("moved '{from}' to '{to}'"),
+            ));
+        }
+        for path in &self.removed {
+            lines.push(DiffLine::new(
+                DiffKind::Removed,
+                format!("deleted '{path}'"),
+            ));
+        }
+        for level in &self.reordered {
+            lines.push(DiffLine::new(
+                DiffKind::Moved,
+                if level.is_empty() {
+                    "reordered commands".to_string()
+                } else {
+                    format!("reordered commands in '{level}'")
+                },
+            ));
+        }
+        for setting in &self.settings {
+            lines.push(DiffLine::new(
+                DiffKind::Modified,
+                format!(
+                    "changed {}: {} → {}",
+                    setting.name, setting.before, setting.after
+                ),
+            ));
+        }
+        if self.other_changes {
+            lines.push(DiffLine::new(
+                DiffKind::Modified,
+                "changed other settings".to_string(),
+            ));
+        }
+        lines
+    }
+
+    /// Returns a one-line summary of up to three changes, such as `Deleted 'A' and 2 more`.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let lines = self.lines();
+        let mut summary = lines
+            .iter()
+            .take(3)
+            .map(|line| line.text.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if lines.len() > 3 {
+            summary.push_str(&format!(" and {} more", lines.len() - 3));
+        }
+        capitalize(&summary)
+    }
+
+    /// Returns the changes as colored lines for the terminal.
+    #[must_use]
+    pub fn render(&self) -> String {
+        self.lines()
+            .iter()
+            .map(|line| {
+                let marker = line.marker();
+                let sentence = line.sentence();
+                let reset = color::Fg(color::Reset);
+                match line.kind {
+                    DiffKind::Added => {
+                        format!("  {}{marker} {sentence}{reset}", color::Fg(color::Green))
+                    }
+                    DiffKind::Removed => {
+                        format!("  {}{marker} {sentence}{reset}", color::Fg(color::Red))
+                    }
+                    DiffKind::Modified => {
+                        format!("  {}{marker} {sentence}{reset}", color::Fg(color::Yellow))
+                    }
+                    DiffKind::Moved => {
+                        format!("  {}{marker} {sentence}{reset}", color::Fg(color::Cyan))
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn compare_groups(&mut self, before: &Config, after: &Config) {
+        let before_groups = group_names(before);
+        let after_groups = group_names(after);
+        let removed: Vec<String> = before_groups
+            .iter()
+            .filter(|name| !after_groups.contains(name))
+            .cloned()
+            .collect();
+        let added: Vec<String> = after_groups
+            .iter()
+            .filter(|name| !before_groups.contains(name))
+            .cloned()
+            .collect();
+
+        // One group gone and one new is a rename; nested groups move along with it
+        let renamed = match (removed.as_slice(), added.as_slice()) {
+            ([from], [to]) => Some((from.clone(), to.clone())),
+            _ => {
+                let top_level = |names: &[String]| -> Vec<String> {
+                    names
+                        .iter()
+                        .filter(|name| {
+                            !names
+                                .iter()
+                                .any(|parent| name.starts_with(&format!("{parent}/")))
+                        })
+                        .cloned()
+                        .collect()
+                };
+                match (top_level(&removed).as_slice(), top_level(&added).as_slice()) {
+                    ([from], [to])
+                        if removed.len() == added.len()
+                            && removed
+                                .iter()
+                                .all(|name| added.contains(&rename_path(name, from, to))) =>
+                    {
+                        Some((from.clone(), to.clone()))
+                    }
+                    _ => None,
+                }
+            }
+        };
+        match renamed {
+            Some(renamed) => self.groups_renamed.push(renamed),
+            None => {
+                self.groups_added = added;
+                self.groups_removed = removed;
+            }
+        }
+    }
+
+    fn compare_commands(&mut self, before: &Config, after: &Config) {
+        let before_commands: HashMap<&str, CommandLocation> = before
+            .all_commands()
+            .into_iter()
+            .map(|location| (location.command.id.as_str(), location))
+            .collect();
+        let after_commands = after.all_commands();
+
+        for location in &after_commands {
+            let Some(old) = before_commands.get(location.command.id.as_str()) else {
+                self.added.push(location.path());
+                continue;
+            };
+            let fields = changed_fields(old.command, location.command);
+            if !fields.is_empty() {
+                self.modified.push(CommandChange {
+                    path: location.path(),
+                    fields,
+                });
+            }
+            // A renamed group changes the path of every command in it without moving them
+            let old_group = self
+                .groups_renamed
+                .iter()
+                .fold(old.groups.join("/"), |path, (from, to)| {
+                    rename_path(&path, from, to)
+                });
+            if old_group != location.groups.join("/") {
+                self.moved.push((old.path(), location.path()));
+            }
+        }
+        for location in before.all_commands() {
+            let still_there = after_commands
+                .iter()
+                .any(|current| current.command.id == location.command.id);
+            if !still_there {
+                self.removed.push(location.path());
+            }
+        }
+
+        // Compare the order of the commands that stayed in the same level
+        let levels = |config: &Config| -> Vec<(String, Vec<String>)> {
+            config
+                .group_paths()
+                .iter()
+                .filter_map(|path| {
+                    let (commands, _) = config.level(path)?;
+                    Some((
+                        config.group_names(path).join("/"),
+                        commands.iter().map(|command| command.id.clone()).collect(),
+                    ))
+                })
+                .collect()
+        };
+        let before_levels: HashMap<String, Vec<String>> = levels(before)
+            .into_iter()
+            .map(|(name, ids)| {
+                let name = self
+                    .groups_renamed
+                    .iter()
+                    .fold(name, |name, (from, to)| rename_path(&name, from, to));
+                (name, ids)
+            })
+            .collect();
+        for (name, ids) in levels(after) {
+            let Some(old_ids) = before_levels.get(&name) else {
+                continue;
+            };
+            let kept: Vec<&String> = ids.iter().filter(|id| old_ids.contains(id)).collect();
+            let old_kept: Vec<&String> = old_ids.iter().filter(|id| ids.contains(id)).collect();
+            if kept != old_kept {
+                self.reordered.push(name);
+            }
+        }
+    }
+
+    fn compare_settings(&mut self, before: &Config, after: &Config) {
+        let mut compare = |name: &'static str, old: String, new: String| {
+            if old != new {
+                self.settings.push(SettingChange {
+                    name,
+                    before: old,
+                    after: new,
+                });
+            }
+        };
+        let path = |path: Option<&std::path::Path>| {
+            path.map_or_else(
+                || "none".to_string(),
+                |path| format!("'{}'", path.display()),
+            )
+        };
+        let text = |text: Option<&str>| {
+            text.map_or_else(|| "none".to_string(), |text| format!("'{text}'"))
+        };
+
+        compare(
+            "sound file",
+            path(before.cmd_sound.as_deref()),
+            path(after.cmd_sound.as_deref()),
+        );
+        compare(
+            "window title support",
+            on_off(before.window_title_support),
+            on_off(after.window_title_support),
+        );
+        compare(
+            "window title",
+            text(before.window_title.as_deref()),
+            text(after.window_title.as_deref()),
+        );
+        compare(
+            "pause after run",
+            format!("{:?}", before.pause_after),
+            format!("{:?}", after.pause_after),
+        );
+        compare(
+            "daily reset time",
+            text(before.daily_reset_time.as_deref()),
+            text(after.daily_reset_time.as_deref()),
+        );
+    }
+}
+
+// Describes what differs between two versions of the same command
+fn changed_fields(before: &CommandOption, after: &CommandOption) -> Vec<String> {
+    let mut fields = Vec::new();
+    if before.display_name != after.display_name {
+        fields.push(format!("renamed from '{}'", before.display_name));
+    }
+    if before.command_text() != after.command_text() {
+        fields.push(format!(
+            "command '{}' → '{}'",
+            before.command_text(),
+            after.command_text()
+        ));
+    }
+    let others = [
+        ("working directory", before.cwd != after.cwd),
+        (
+            "environment",
+            before.env != after.env || before.env_file != after.env_file,
+        ),
+        ("shell", before.shell != after.shell),
+        ("pause after run", before.pause_after != after.pause_after),
+        ("placeholders", before.placeholders != after.placeholders),
+    ];
+    fields.extend(
+        others
+            .iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| format!("{name} changed")),
+    );
+    if fields.is_empty() && before != after {
+        fields.push("other settings changed".to_string());
+    }
+    fields
+}
+
+fn group_names(config: &Config) -> Vec<String> {
+    config
+        .group_paths()
+        .iter()
+        .skip(1)
+        .map(|path| config.group_names(path).join("/"))
+        .collect()
+}
+
+// Replaces the `from` group at the start of `path` with `to`
+fn rename_path(path: &str, from: &str, to: &str) -> String {
+    if path == from {
+        to.to_string()
+    } else if let Some(rest) = path.strip_prefix(&format!("{from}/")) {
+        format!("{to}/{rest}")
+    } else {
+        path.to_string()
+    }
+}
+
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandGroup;
+    use std::path::PathBuf;
+
+    fn command(id: &str, name: &str) -> CommandOption {
+        CommandOption {
+            id: id.to_string(),
+            display_name: name.to_string(),
+            command: format!("echo {name}"),
+            ..CommandOption::default()
+        }
+    }
+
+    fn sample() -> Config {
+        Config {
+            commands: vec![command("a", "A"), command("b", "B")],
+            groups: vec![CommandGroup {
+                name: "Tools".to_string(),
+                commands: vec![command("c", "C")],
+                groups: vec![CommandGroup::new("Web")],
+                ..CommandGroup::default()
+            }],
+            ..Config::default()
+        }
+    }
+
+    fn texts(diff: &ConfigDiff) -> Vec<String> {
+        diff.lines().into_iter().map(|line| line.text).collect()
+    }
+
+    #[test]
+    fn test_diff_finds_added_removed_modified_and_moved_commands() {
+        let before = sample();
+        let mut after = before.clone();
+        let a = after.commands.remove(0);
+        after.groups[0].commands.push(a);
+        after.commands[0].command = "echo b2".to_string();
+        after.groups[0].commands.remove(0);
+        after.commands.push(command("d", "D"));
+
+        let diff = ConfigDiff::between(&before, &after);
+
+        assert_eq!(
+            texts(&diff),
+            [
+                "added 'D'",
+                "edited 'B': command 'echo B' → 'echo b2'",
+                "moved 'A' to 'Tools/A'",
+                "deleted 'Tools/C'"
+            ]
+        );
+        assert_eq!(diff.lines()[0].kind, DiffKind::Added);
+    }
+
+    #[test]
+    fn test_diff_reports_group_renames_without_moving_commands() {
+        let before = sample();
+        let mut after = before.clone();
+        after.groups[0].name = "Dev".to_string();
+
+        let diff = ConfigDiff::between(&before, &after);
+
+        assert_eq!(texts(&diff), ["renamed group 'Tools' to 'Dev'"]);
+    }
+
+    #[test]
+    fn test_diff_reports_reorders_and_settings() {
+        let before = sample();
+        let mut after = before.clone();
+        after.commands.swap(0, 1);
+        after.cmd_sound = Some(PathBuf::from("/tmp/done.wav"));
+        after.window_title_support = true;
+        after.window_title = Some("Menu".to_string());
+
+        let diff = ConfigDiff::between(&before, &after);
+
+        assert_eq!(
+            texts(&diff),
+            [
+                "reordered commands",
+                "changed sound file: none → '/tmp/done.wav'",
+                "changed window title support: off → on",
+                "changed window title: none → 'Menu'"
+            ]
+        );
+        assert!(
+            diff.render()
+                .contains("~ Changed window title support: off → on")
+        );
+        assert!(ConfigDiff::between(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_summary_lists_three_changes() {
+        let before = sample();
+
+        let diff = ConfigDiff::between(&before, &Config::default());
+
+        assert_eq!(
+            diff.summary(),
+            "Deleted group 'Tools', deleted group 'Tools/Web', deleted 'A' and 2 more"
+        );
+    }
+}
@@ -1,11 +1,143 @@
 use crate::{
-    config::{CommandOption, Config}, // Importing Config struct
+    config::{CommandLocation, CommandOption, Config, PausePolicy, ShellSpec, ensure_command_ids}, // Importing Config struct
+    layers::{clear_own_entries, own_layer},
     menu_edit::print_commands,
     utils::pause,
 };
+use anyhow::Context;
 use inquire::{Select, Text}; // Importing prompts from inquire crate
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use std::{env, fs, path::Path}; // Importing necessary modules from standard library // Importing functions and structs from other modules
 
+// The columns written on export, in order
+const CSV_HEADERS: [&str; 11] = [
+    "display_name",
+    "command",
+    "id",
+    "group",
+    "argv",
+    "cwd",
+    "env",
+    "env_file",
+    "shell",
+    "placeholders",
+    "pause_after",
+];
+
+// Define the CSV record layout; every column but display_name is optional so older exports still import.
+// group is the `Group/Sub` path, empty for top-level commands; argv, env and placeholders hold JSON;
+// shell holds a program name or JSON, pause_after a policy name
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvCommand {
+    display_name: String,
+    #[serde(default)]
+    command: String,
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    group: String,
+    #[serde(default)]
+    argv: String,
+    #[serde(default)]
+    cwd: String,
+    #[serde(default)]
+    env: String,
+    #[serde(default)]
+    env_file: String,
+    #[serde(default)]
+    shell: String,
+    #[serde(default)]
+    placeholders: String,
+    #[serde(default)]
+    pause_after: String,
+}
+
+impl TryFrom<CsvCommand> for CommandOption {
+    type Error = anyhow::Error;
+
+    fn try_from(record: CsvCommand) -> anyhow::Result<Self> {
+        let shell = match record.shell.trim() {
+            "" => None,
+            shell if shell.starts_with('{') => Some(parse_json_column("shell", shell)?),
+            shell => Some(ShellSpec::Program(shell.to_string())),
+        };
+        let pause_after = match record.pause_after.trim() {
+            "" => None,
+            policy => Some(
+                serde_json::from_value::<PausePolicy>(serde_json::Value::from(policy))
+                    .with_context(|| format!("invalid pause_after '{policy}'"))?,
+            ),
+        };
+        Ok(Self {
+            id: record.id,
+            display_name: record.display_name,
+            command: record.command,
+            argv: parse_json_column("argv", &record.argv)?,
+            cwd: non_empty(record.cwd),
+            env: parse_json_column("env", &record.env)?,
+            env_file: non_empty(record.env_file),
+            shell,
+            placeholders: parse_json_column("placeholders", &record.placeholders)?,
+            pause_after,
+            ..Default::default()
+        })
+    }
+}
+
+// Define a command read from a CSV file together with the group it belongs in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupedCommand {
+    pub group: String, // `Group/Sub` path; empty for top-level commands
+    pub command: CommandOption,
+}
+
+impl From<&CommandLocation<'_>> for CsvCommand {
+    fn from(location: &CommandLocation<'_>) -> Self {
+        let command = location.command;
+        Self {
+            display_name: command.display_name.clone(),
+            command: command.command.clone(),
+            id: command.id.clone(),
+            group: location.groups.join("/"),
+            argv: json_column(&command.argv, command.argv.is_empty()),
+            cwd: command.cwd.clone().unwrap_or_default(),
+            env: json_column(&command.env, command.env.is_empty()),
+            env_file: command.env_file.clone().unwrap_or_default(),
+            shell: match &command.shell {
+                None => String::new(),
+                Some(ShellSpec::Program(program)) => program.clone(),
+                Some(shell) => json_column(shell, false),
+            },
+            placeholders: json_column(&command.placeholders, command.placeholders.is_empty()),
+            pause_after: command
+                .pause_after
+                .map(|policy| json_column(&policy, false).trim_matches('"').to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// Writes a value as compact JSON, or nothing when it is empty
+fn json_column<T: Serialize>(value: &T, empty: bool) -> String {
+    if empty {
+        return String::new();
+    }
+    // Command settings always serialize, as their maps have string keys
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+// Reads a JSON column, where an empty cell means the default
+fn parse_json_column<T: DeserializeOwned + Default>(column: &str, text: &str) -> anyhow::Result<T> {
+    if text.trim().is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_str(text).with_context(|| format!("invalid {column} '{text}'"))
+}
+
+fn non_empty(text: String) -> Option<String> {
+    (!text.trim().is_empty()).then_some(text)
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum ImportStrategy {
     Append,
@@ -58,7 +190,14 @@ pub fn import_commands(config: &mut Config, changes_made: &mut bool) {
 
     let num_commands = commands.len();
     println!("Found {num_commands} commands from {path}");
-    print_commands(&commands);
+    let preview: Vec<CommandOption> = commands
+        .iter()
+        .map(|grouped| CommandOption {
+            display_name: grouped_path(grouped),
+            ..grouped.command.clone()
+        })
+        .collect();
+    print_commands(&preview);
 
     let menu_options = vec![
         "a. APPEND to current commands",
@@ -89,7 +228,7 @@ pub fn import_commands(config: &mut Config, changes_made: &mut bool) {
     }
 }
 
-/// Prompts for a CSV destination and exports the current commands.
+/// Prompts for a CSV destination and exports the config's own commands from every group.
 pub fn export_commands(config: &Config) {
     let path = match Text::new("Enter the CSV file path to export to:")
         .with_initial_value("commands.csv")
@@ -108,48 +247,63 @@ pub fn export_commands(config: &Config) {
         return;
     }
 
-    match write_commands_to_csv(path, &config.commands) {
-        Ok(()) => println!("✅ Exported {} commands to {path}.", config.commands.len()),
+    match write_commands_to_csv(path, config) {
+        Ok(count) => println!("✅ Exported {count} commands to {path}."),
         Err(e) => println!("❌ Could not export commands: {e}"),
     }
 }
 
-/// Reads command entries from a CSV file.
+/// Reads command entries from a CSV file, each with the group it belongs in.
 ///
 /// # Errors
 ///
 /// Returns an error when the CSV file cannot be opened or a record cannot be
 /// deserialized as a [`CommandOption`].
-pub fn read_commands_from_csv<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<CommandOption>> {
+pub fn read_commands_from_csv<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<GroupedCommand>> {
     let mut reader = csv::Reader::from_path(path)?;
     let mut commands = Vec::new();
 
     for result in reader.deserialize() {
-        let command: CommandOption = result?;
-        commands.push(command);
+        let record: CsvCommand = result?;
+        let display_name = record.display_name.clone();
+        let group = record.group.trim().to_string();
+        let command = record
+            .try_into()
+            .with_context(|| format!("unable to import '{display_name}'"))?;
+        commands.push(GroupedCommand { group, command });
     }
 
     Ok(commands)
 }
 
-/// Writes command entries to a CSV file.
+/// Writes the config's own commands from every group to a CSV file; included ones are left out.
+///
+/// Returns the number of commands written.
 ///
 /// # Errors
 ///
 /// Returns an error when the CSV file cannot be created, written, or flushed.
-pub fn write_commands_to_csv<P: AsRef<Path>>(
-    path: P,
-    commands: &[CommandOption],
-) -> anyhow::Result<()> {
+pub fn write_commands_to_csv<P: AsRef<Path>>(path: P, config: &Config) -> anyhow::Result<usize> {
+    let own = own_layer(config);
+    let locations = own.all_commands();
     let mut writer = csv::WriterBuilder::new()
         .has_headers(false)
         .from_path(path)?;
-    writer.write_record(["display_name", "command"])?;
-    for command in commands {
-        writer.serialize(command)?;
+    writer.write_record(CSV_HEADERS)?;
+    for location in &locations {
+        writer.serialize(CsvCommand::from(location))?;
     }
     writer.flush()?;
-    Ok(())
+    Ok(locations.len())
+}
+
+// Returns the `Group/Sub/Name` path of an imported command
+fn grouped_path(grouped: &GroupedCommand) -> String {
+    if grouped.group.is_empty() {
+        grouped.command.display_name.clone()
+    } else {
+        format!("{}/{}", grouped.group, grouped.command.display_name)
+    }
 }
 
 // Function to list CSV files in a directory
@@ -175,30 +329,45 @@ fn list_csv_files(dir_path: &Path) -> anyhow::Result<Vec<String>> {
     Ok(files)
 }
 
-// Extracted for testable merging logic
+// Extracted for testable merging logic; overwriting replaces the config's own groups too
 fn merge_imported_commands(
     config: &mut Config,
-    mut new_commands: Vec<CommandOption>,
+    new_commands: Vec<GroupedCommand>,
     strategy: ImportStrategy,
     changes_made: &mut bool,
 ) {
     match strategy {
-        ImportStrategy::Append => {
-            config.commands.append(&mut new_commands);
-            *changes_made = true;
-        }
+        ImportStrategy::Append => {}
         ImportStrategy::Overwrite => {
-            config.commands = new_commands;
-            *changes_made = true;
+            clear_own_entries(config);
         }
-        ImportStrategy::Cancel => {}
+        ImportStrategy::Cancel => return,
     }
+    for GroupedCommand { group, command } in new_commands {
+        let group_path = config.ensure_group_path(&group);
+        if let Some((commands, _)) = config.level_mut(&group_path) {
+            commands.push(command);
+        }
+    }
+    *changes_made = true;
+    // Keep IDs from the CSV where possible and generate the missing or clashing ones
+    ensure_command_ids(config);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::Config;
+    use crate::config::{CommandGroup, Config};
+
+    fn top_level(commands: Vec<CommandOption>) -> Vec<GroupedCommand> {
+        commands
+            .into_iter()
+            .map(|command| GroupedCommand {
+                group: String::new(),
+                command,
+            })
+            .collect()
+    }
 
     #[test]
     fn test_list_csv_files_returns_csvs_only() {
@@ -222,19 +391,28 @@ mod tests {
             commands: vec![CommandOption {
                 display_name: "Existing".into(),
                 command: "echo existing".into(),
+                ..Default::default()
             }],
             ..Default::default()
         };
         let new = vec![CommandOption {
             display_name: "New".into(),
             command: "echo new".into(),
+            ..Default::default()
         }];
         let mut changed = false;
 
-        merge_imported_commands(&mut config, new, ImportStrategy::Append, &mut changed);
+        merge_imported_commands(
+            &mut config,
+            top_level(new),
+            ImportStrategy::Append,
+            &mut changed,
+        );
 
         assert_eq!(config.commands.len(), 2);
         assert_eq!(config.commands[1].display_name, "New");
+        assert!(!config.commands[1].id.is_empty());
+        assert_ne!(config.commands[0].id, config.commands[1].id);
         assert!(changed);
     }
 
@@ -244,16 +422,23 @@ mod tests {
             commands: vec![CommandOption {
                 display_name: "Old".into(),
                 command: "echo old".into(),
+                ..Default::default()
             }],
             ..Default::default()
         };
         let new = vec![CommandOption {
             display_name: "Overwrite".into(),
             command: "echo overwrite".into(),
+            ..Default::default()
         }];
         let mut changed = false;
 
-        merge_imported_commands(&mut config, new, ImportStrategy::Overwrite, &mut changed);
+        merge_imported_commands(
+            &mut config,
+            top_level(new),
+            ImportStrategy::Overwrite,
+            &mut changed,
+        );
 
         assert_eq!(config.commands.len(), 1);
         assert_eq!(config.commands[0].display_name, "Overwrite");
@@ -266,16 +451,23 @@ mod tests {
             commands: vec![CommandOption {
                 display_name: "Keep".into(),
                 command: "echo keep".into(),
+                ..Default::default()
             }],
             ..Default::default()
         };
         let new = vec![CommandOption {
             display_name: "ShouldNotAdd".into(),
             command: "echo nope".into(),
+            ..Default::default()
         }];
         let mut changed = true;
 
-        merge_imported_commands(&mut config, new, ImportStrategy::Cancel, &mut changed);
+        merge_imported_commands(
+            &mut config,
+            top_level(new),
+            ImportStrategy::Cancel,
+            &mut changed,
+        );
 
         assert_eq!(config.commands.len(), 1);
         assert_eq!(config.commands[0].display_name, "Keep");
@@ -288,16 +480,23 @@ mod tests {
             commands: vec![CommandOption {
                 display_name: "Keep".into(),
                 command: "echo keep".into(),
+                ..Default::default()
             }],
             ..Default::default()
         };
         let new = vec![CommandOption {
             display_name: "ShouldNotAdd".into(),
             command: "echo nope".into(),
+            ..Default::default()
         }];
         let mut changed = false;
 
-        merge_imported_commands(&mut config, new, ImportStrategy::Cancel, &mut changed);
+        merge_imported_commands(
+            &mut config,
+            top_level(new),
+            ImportStrategy::Cancel,
+            &mut changed,
+        );
 
         assert_eq!(config.commands.len(), 1);
         assert_eq!(config.commands[0].display_name, "Keep");
@@ -310,8 +509,9 @@ mod tests {
             read_commands_from_csv("tests/fixtures/commands.csv").expect("Should parse CSV");
 
         assert_eq!(commands.len(), 2);
-        assert_eq!(commands[0].display_name, "List Files");
-        assert_eq!(commands[0].command, "ls -la");
+        assert_eq!(commands[0].group, "");
+        assert_eq!(commands[0].command.display_name, "List Files");
+        assert_eq!(commands[0].command.command, "ls -la");
     }
 
     #[test]
@@ -331,13 +531,138 @@ mod tests {
     fn test_write_commands_to_csv_roundtrip() {
         let file = tempfile::NamedTempFile::new().expect("temp file");
         let commands = vec![CommandOption {
+            id: "list-files".into(),
             display_name: "List Files".into(),
             command: "ls -la".into(),
+            ..Default::default()
         }];
 
-        write_commands_to_csv(file.path(), &commands).expect("Should write CSV");
+        let config = Config {
+            commands: commands.clone(),
+            ..Default::default()
+        };
+        assert_eq!(
+            write_commands_to_csv(file.path(), &config).unwrap(),
+            commands.len()
+        );
+        let loaded = read_commands_from_csv(file.path()).expect("Should parse written CSV");
+
+        assert_eq!(loaded, top_level(commands));
+    }
+
+    #[test]
+    fn test_csv_roundtrip_keeps_every_command_setting() {
+        let file = tempfile::NamedTempFile::new().expect("temp file");
+        let commands = vec![
+            CommandOption {
+                id: "greet".into(),
+                display_name: "Greet".into(),
+                argv: vec!["printf".into(), "hello, %s".into(), "{{name}}".into()],
+                cwd: Some("~/src".into()),
+                env: [("GREETING".to_string(), "hi".to_string())].into(),
+                env_file: Some(".env".into()),
+                shell: Some(ShellSpec::Program("bash".into())),
+                placeholders: [(
+                    "name".to_string(),
+                    crate::placeholders::PlaceholderSpec {
+                        default: Some("world".into()),
+                        ..Default::default()
+                    },
+                )]
+                .into(),
+                pause_after: Some(PausePolicy::Always),
+                ..Default::default()
+            },
+            CommandOption {
+                id: "py".into(),
+                display_name: "Python".into(),
+                command: "print(1)".into(),
+                shell: Some(ShellSpec::Custom {
+                    program: "python3".into(),
+                    args: vec!["-c".into()],
+                }),
+                ..Default::default()
+            },
+        ];
+
+        let config = Config {
+            commands: commands.clone(),
+            ..Default::default()
+        };
+        assert_eq!(
+            write_commands_to_csv(file.path(), &config).unwrap(),
+            commands.len()
+        );
         let loaded = read_commands_from_csv(file.path()).expect("Should parse written CSV");
 
-        assert_eq!(loaded, commands);
+        assert_eq!(loaded, top_level(commands));
+    }
+
+    fn command(id: &str, name: &str) -> CommandOption {
+        CommandOption {
+            id: id.into(),
+            display_name: name.into(),
+            command: format!("echo {name}"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_grouped_commands_roundtrip_through_csv() {
+        let file = tempfile::NamedTempFile::new().expect("temp file");
+        let mut team = command("team", "Team");
+        team.layer = Some("team.json".into());
+        let config = Config {
+            commands: vec![command("top", "Top"), team],
+            groups: vec![CommandGroup {
+                name: "Ops".into(),
+                commands: vec![command("deploy", "Deploy")],
+                groups: vec![CommandGroup {
+                    name: "Db".into(),
+                    commands: vec![command("backup", "Backup")],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(write_commands_to_csv(file.path(), &config).unwrap(), 3);
+        let loaded = read_commands_from_csv(file.path()).unwrap();
+        let paths: Vec<String> = loaded.iter().map(grouped_path).collect();
+        assert_eq!(paths, ["Top", "Ops/Deploy", "Ops/Db/Backup"]);
+
+        let mut imported = Config::default();
+        let mut changed = false;
+        merge_imported_commands(&mut imported, loaded, ImportStrategy::Append, &mut changed);
+        assert_eq!(imported, own_layer(&config));
+    }
+
+    #[test]
+    fn test_overwrite_replaces_own_groups_too() {
+        let mut team = command("team", "Team");
+        team.layer = Some("team.json".into());
+        let mut config = Config {
+            commands: vec![command("old", "Old"), team.clone()],
+            groups: vec![CommandGroup {
+                name: "Ops".into(),
+                commands: vec![command("deploy", "Deploy")],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let new = vec![GroupedCommand {
+            group: "Tools".into(),
+            command: command("new", "New"),
+        }];
+        let mut changed = false;
+
+        merge_imported_commands(&mut config, new, ImportStrategy::Overwrite, &mut changed);
+
+        assert_eq!(config.commands, [team]);
+        assert_eq!(config.groups.len(), 1);
+        assert_eq!(config.groups[0].name, "Tools");
+        assert_eq!(config.groups[0].commands[0].display_name, "New");
+        assert!(changed);
     }
 }
@@ -0,0 +1,130 @@
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+//This file contains the dotenv parser used for the env_file settings.
+
+/// Parses dotenv formatted text into variables.
+///
+/// Supports blank lines, `#` comments, an optional `export ` prefix, unquoted values
+/// with trailing comments, single-quoted literal values and double-quoted values with
+/// `\n`, `\t`, `\"` and `\\` escapes.
+///
+/// # Errors
+///
+/// Returns a user-facing message naming the first line that cannot be parsed.
+pub fn parse_dotenv(contents: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut variables = BTreeMap::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("Line {line_number} is missing '='."));
+        };
+        let key = key.trim();
+        if !is_valid_env_name(key) {
+            return Err(format!(
+                "Line {line_number} has an invalid variable name '{key}'."
+            ));
+        }
+
+        let value = parse_value(value.trim())
+            .ok_or_else(|| format!("Line {line_number} has an unterminated quoted value."))?;
+        variables.insert(key.to_string(), value);
+    }
+
+    Ok(variables)
+}
+
+/// Reads and parses a dotenv file.
+///
+/// # Errors
+///
+/// Returns an error when the file cannot be read or parsed.
+pub fn load_env_file(path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read env file located at {}", path.display()))?;
+    parse_dotenv(&contents)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("unable to parse env file located at {}", path.display()))
+}
+
+/// Returns `true` when `name` can be used as an environment variable name.
+#[must_use]
+pub fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_value(value: &str) -> Option<String> {
+    if let Some(rest) = value.strip_prefix('\'') {
+        return rest.find('\'').map(|end| rest[..end].to_string());
+    }
+
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Some(parsed),
+                '\\' => match chars.next()? {
+                    'n' => parsed.push('\n'),
+                    't' => parsed.push('\t'),
+                    other => parsed.push(other),
+                },
+                _ => parsed.push(c),
+            }
+        }
+        return None;
+    }
+
+    // Unquoted values end at an inline comment
+    let value = value
+        .find(" #")
+        .map_or(value, |comment_start| &value[..comment_start]);
+    Some(value.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv_supports_common_syntax() {
+        let variables = parse_dotenv(
+            "# comment\n\
+             AWS_PROFILE=dev # trailing\n\
+             export RUST_LOG=debug\n\
+             GREETING=\"hello\\nworld\"\n\
+             LITERAL='$HOME stays'\n\
+             EMPTY=\n",
+        )
+        .expect("should parse");
+
+        assert_eq!(variables["AWS_PROFILE"], "dev");
+        assert_eq!(variables["RUST_LOG"], "debug");
+        assert_eq!(variables["GREETING"], "hello\nworld");
+        assert_eq!(variables["LITERAL"], "$HOME stays");
+        assert_eq!(variables["EMPTY"], "");
+    }
+
+    #[test]
+    fn test_parse_dotenv_reports_bad_lines() {
+        assert!(parse_dotenv("JUST_A_NAME").unwrap_err().contains("Line 1"));
+        assert!(parse_dotenv("1BAD=x").unwrap_err().contains("invalid"));
+        assert!(
+            parse_dotenv("OK=1\nOPEN=\"never closed")
+                .unwrap_err()
+                .contains("Line 2")
+        );
+    }
+}
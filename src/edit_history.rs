@@ -0,0 +1,189 @@
+//This file contains the undo and redo history of an edit session.
+//Each change made in the edit menu is kept as the config before and after it, so UNDO and REDO
+//can step back and forth one change at a time and say what each one did.
+
+use crate::config::Config;
+use crate::config_diff::ConfigDiff;
+
+/// The number of changes kept for UNDO; the oldest are dropped first.
+pub const MAX_UNDO_STEPS: usize = 100;
+
+// Define one reversible change, kept as the whole config on either side of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EditStep {
+    description: String,
+    before: Config,
+    after: Config,
+}
+
+// Define the changes that can be undone and the undone changes that can be redone
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EditHistory {
+    undo: Vec<EditStep>,
+    redo: Vec<EditStep>,
+}
+
+impl EditHistory {
+    /// Records the change from `before` to `after`, forgetting anything that could be redone.
+    ///
+    /// Returns the description of the change, or `None` when nothing changed.
+    pub fn record(&mut self, before: Config, after: &Config) -> Option<&str> {
+        if before == *after {
+            return None;
+        }
+        if self.undo.len() == MAX_UNDO_STEPS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.undo.push(EditStep {
+            description: describe_change(&before, after),
+            before,
+            after: after.clone(),
+        });
+        self.undo.last().map(|step| step.description.as_str())
+    }
+
+    /// Puts `config` back to how it was before the last change and returns its description.
+    pub fn undo(&mut self, config: &mut Config) -> Option<String> {
+        let step = self.undo.pop()?;
+        config.clone_from(&step.before);
+        let description = step.description.clone();
+        self.redo.push(step);
+        Some(description)
+    }
+
+    /// Applies the last undone change to `config` again and returns its description.
+    pub fn redo(&mut self, config: &mut Config) -> Option<String> {
+        let step = self.redo.pop()?;
+        config.clone_from(&step.after);
+        let description = step.description.clone();
+        self.undo.push(step);
+        Some(description)
+    }
+
+    /// Returns the description of the change UNDO would revert.
+    #[must_use]
+    pub fn next_undo(&self) -> Option<&str> {
+        self.undo.last().map(|step| step.description.as_str())
+    }
+
+    /// Returns the description of the change REDO would apply.
+    #[must_use]
+    pub fn next_redo(&self) -> Option<&str> {
+        self.redo.last().map(|step| step.description.as_str())
+    }
+}
+
+/// Describes what changed between two versions of a config, such as `Deleted 'Tools/Build'`.
+#[must_use]
+pub fn describe_change(before: &Config, after: &Config) -> String {
+    ConfigDiff::between(before, after).summary()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CommandGroup, CommandOption};
+
+    fn command(id: &str, name: &str) -> CommandOption {
+        CommandOption {
+            id: id.to_string(),
+            display_name: name.to_string(),
+            command: format!("echo {name}"),
+            ..CommandOption::default()
+        }
+    }
+
+    fn sample() -> Config {
+        Config {
+            commands: vec![command("a", "A"), command("b", "B")],
+            groups: vec![CommandGroup {
+                name: "Tools".to_string(),
+                commands: vec![command("c", "C")],
+                ..CommandGroup::default()
+            }],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_undo_and_redo_step_through_changes() {
+        let mut history = EditHistory::default();
+        let original = sample();
+        let mut config = original.clone();
+
+        config.commands.remove(0);
+        assert_eq!(
+            history.record(original.clone(), &config),
+            Some("Deleted 'A'")
+        );
+        let after_delete = config.clone();
+        config.commands[0].command = "echo changed".to_string();
+        history.record(after_delete.clone(), &config);
+        let after_edit = config.clone();
+        assert_eq!(history.record(after_edit.clone(), &config), None);
+
+        assert_eq!(
+            history.undo(&mut config).as_deref(),
+            Some("Edited 'B': command 'echo B' → 'echo changed'")
+        );
+        assert_eq!(config, after_delete);
+        assert_eq!(history.undo(&mut config).as_deref(), Some("Deleted 'A'"));
+        assert_eq!(config, original);
+        assert_eq!(history.undo(&mut config), None);
+
+        assert_eq!(history.redo(&mut config).as_deref(), Some("Deleted 'A'"));
+        assert_eq!(config, after_delete);
+        assert_eq!(
+            history.next_redo(),
+            Some("Edited 'B': command 'echo B' → 'echo changed'")
+        );
+    }
+
+    #[test]
+    fn test_new_change_clears_redo() {
+        let mut history = EditHistory::default();
+        let original = sample();
+        let mut config = original.clone();
+        config.commands.push(command("d", "D"));
+        history.record(original, &config);
+        history.undo(&mut config);
+
+        let before = config.clone();
+        config.window_title_support = true;
+        history.record(before, &config);
+
+        assert_eq!(history.next_redo(), None);
+        assert_eq!(
+            history.next_undo(),
+            Some("Changed window title support: off → on")
+        );
+    }
+
+    #[test]
+    fn test_describe_change_names_moves_renames_and_reorders() {
+        let before = sample();
+
+        let mut moved = before.clone();
+        let a = moved.commands.remove(0);
+        moved.groups[0].commands.push(a);
+        assert_eq!(describe_change(&before, &moved), "Moved 'A' to 'Tools/A'");
+
+        let mut renamed = before.clone();
+        renamed.groups[0].name = "Dev".to_string();
+        assert_eq!(
+            describe_change(&before, &renamed),
+            "Renamed group 'Tools' to 'Dev'"
+        );
+
+        let mut reordered = before.clone();
+        reordered.commands.swap(0, 1);
+        assert_eq!(describe_change(&before, &reordered), "Reordered commands");
+
+        let cleared = Config::default();
+        assert_eq!(
+            describe_change(&before, &cleared),
+            "Deleted group 'Tools', deleted 'A', deleted 'B' and 1 more"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//This file contains the file formats a config can be written in.
+//The format is chosen by the file extension, and every format maps onto the same `Config` struct,
+//so includes, migrations and the menu work the same whichever one a file uses.
+
+use anyhow::Context;
+use serde::{Serialize, de::DeserializeOwned};
+use std::io::Read;
+use std::path::Path;
+
+/// The extensions recognized as config files, in the order they are looked for.
+pub const CONFIG_EXTENSIONS: [&str; 5] = ["json", "jsonc", "toml", "yaml", "yml"];
+
+// Define the ConfigFormat enum for the supported config file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Jsonc, // JSON with // and /* */ comments
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Returns the format for a file extension, ignoring case.
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "jsonc" => Some(Self::Jsonc),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the file at `path`; unknown extensions are read as JSON.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Self::from_extension)
+            .unwrap_or(Self::Json)
+    }
+
+    /// Parses a format name such as `toml`, as given to `convert`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a user-facing message naming the supported formats.
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        Self::from_extension(name.trim_start_matches('.')).ok_or_else(|| {
+            format!("Unknown config format '{name}'. Use json, jsonc, toml or yaml.")
+        })
+    }
+
+    /// Returns the file extension written for this format.
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Jsonc => "jsonc",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+        }
+    }
+
+    /// Parses the text of a config file in this format.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error describing where the text is not valid in this format.
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> anyhow::Result<T> {
+        let parsed = match self {
+            Self::Json => serde_json::from_str(text).map_err(anyhow::Error::from),
+            Self::Jsonc => {
+                // Comments are replaced by spaces, so line numbers in errors still match the file
+                let mut stripped = String::with_capacity(text.len());
+                json_comments::StripComments::new(text.as_bytes())
+                    .read_to_string(&mut stripped)
+                    .context("unable to strip comments")?;
+                serde_json::from_str(&stripped).map_err(anyhow::Error::from)
+            }
+            Self::Toml => toml::from_str(text).map_err(anyhow::Error::from),
+            Self::Yaml => serde_yaml_ng::from_str(text).map_err(anyhow::Error::from),
+        };
+        parsed.with_context(|| format!("unable to parse {}", self.extension().to_uppercase()))
+    }
+
+    /// Serializes a value as the text of a file in this format.
+    ///
+    /// JSONC files are written as plain JSON, which is also valid JSONC.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the value cannot be represented in this format, such as
+    /// a `null` in TOML.
+    pub fn serialize<T: Serialize>(self, value: &T) -> anyhow::Result<String> {
+        let text = match self {
+            Self::Json | Self::Jsonc => serde_json::to_string_pretty(value)?,
+            Self::Toml => toml::to_string_pretty(value)?,
+            Self::Yaml => serde_yaml_ng::to_string(value)?,
+        };
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn sample_config() -> Config {
+        serde_json::from_value(serde_json::json!({
+            "version": crate::migrations::CONFIG_VERSION,
+            "env": {"GREETING": "hi"},
+            "commands": [{"id": "greet", "display_name": "Greet", "command": "echo $GREETING"}],
+            "groups": [{
+                "name": "Tools",
+                "commands": [{
+                    "id": "say",
+                    "display_name": "Say",
+                    "command": "echo {{word}}",
+                    "placeholders": {"word": {"default": "hello"}}
+                }]
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_format_is_detected_by_extension() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("menu.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new(".cli_menu.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("menu.jsonc")),
+            ConfigFormat::Jsonc
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("menu")),
+            ConfigFormat::Json
+        );
+        assert_eq!(ConfigFormat::from_name(".yaml"), Ok(ConfigFormat::Yaml));
+        assert!(ConfigFormat::from_name("ini").is_err());
+    }
+
+    #[test]
+    fn test_every_format_round_trips_a_config() {
+        let config = sample_config();
+        for format in [
+            ConfigFormat::Json,
+            ConfigFormat::Jsonc,
+            ConfigFormat::Toml,
+            ConfigFormat::Yaml,
+        ] {
+            let text = format.serialize(&config).unwrap();
+            let parsed: Config = format.parse(&text).unwrap();
+            assert_eq!(parsed, config, "{format:?}:\n{text}");
+        }
+    }
+
+    #[test]
+    fn test_jsonc_comments_are_ignored() {
+        let text = "{\n  // Shown first\n  \"commands\": [] /* none yet */\n}";
+
+        let parsed: serde_json::Value = ConfigFormat::Jsonc.parse(text).unwrap();
+
+        assert_eq!(parsed, serde_json::json!({"commands": []}));
+        assert!(ConfigFormat::Json.parse::<serde_json::Value>(text).is_err());
+    }
+}
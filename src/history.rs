@@ -0,0 +1,209 @@
+use crate::{
+    config::{CommandOption, get_data_dir},
+    utils::{CommandRequest, run_request},
+};
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Instant;
+
+//This file contains the run history log that records every executed command as JSON Lines.
+
+// Define the HistoryEntry struct written once per command run
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")] // Rename fields to camelCase in JSON
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,   // When the command started
+    pub command_id: Option<String>,   // The stable ID when run from the config
+    pub display_name: Option<String>, // The display name when run from the config
+    pub command: String,              // The command text that was executed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub argv: Vec<String>, // The program and arguments when run without a shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>, // The working directory when one was configured
+    pub exit_code: Option<i32>,       // None when the command could not start or was killed
+    pub duration_ms: u64,             // Wall clock run time
+}
+
+impl HistoryEntry {
+    /// Returns the request that runs this entry again in the same directory.
+    #[must_use]
+    pub fn request(&self) -> CommandRequest {
+        CommandRequest {
+            command: self.command.clone(),
+            argv: self.argv.clone(),
+            cwd: self.cwd.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the display name, falling back to the command text for ad-hoc runs.
+    #[must_use]
+    pub fn label(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.command)
+    }
+}
+
+/// Returns the history file path in the data directory.
+///
+/// # Errors
+///
+/// Returns an error when the data directory cannot be resolved.
+pub fn history_file_path() -> Result<PathBuf, String> {
+    Ok(get_data_dir()?.join("history.jsonl"))
+}
+
+/// Appends one entry to the history file, creating it when needed.
+///
+/// # Errors
+///
+/// Returns an error when the entry cannot be serialized or written.
+pub fn append_entry(path: &Path, entry: &HistoryEntry) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| {
+            format!(
+                "unable to create history directory located at {}",
+                parent.display()
+            )
+        })?;
+    }
+
+    let mut line = serde_json::to_string(entry).context("failed to serialize history entry")?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("unable to open history file at {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("unable to write history file at {}", path.display()))?;
+    Ok(())
+}
+
+/// Loads up to `limit` history entries, newest first. Malformed lines are skipped.
+///
+/// # Errors
+///
+/// Returns an error when an existing history file cannot be read.
+pub fn load_recent(path: &Path, limit: usize) -> anyhow::Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let history_data = fs::read_to_string(path)
+        .with_context(|| format!("unable to load history file at {}", path.display()))?;
+    Ok(history_data
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .take(limit)
+        .collect())
+}
+
+/// Runs a command and appends the outcome to the history file.
+///
+/// Failing to write the history only prints a warning so the run result is never lost.
+///
+/// # Errors
+///
+/// Returns an error when the command cannot be run.
+pub fn run_and_record(
+    request: &CommandRequest,
+    source: Option<&CommandOption>,
+) -> anyhow::Result<ExitStatus> {
+    let timestamp = Local::now();
+    let timer = Instant::now();
+    let result = run_request(request);
+
+    let entry = HistoryEntry {
+        timestamp,
+        command_id: source.map(|option| option.id.clone()),
+        display_name: source.map(|option| option.display_name.clone()),
+        command: request.display(),
+        argv: request.argv.clone(),
+        cwd: request.cwd.clone(),
+        exit_code: result.as_ref().ok().and_then(ExitStatus::code),
+        duration_ms: u64::try_from(timer.elapsed().as_millis()).unwrap_or(u64::MAX),
+    };
+    if let Err(e) = history_file_path()
+        .map_err(anyhow::Error::msg)
+        .and_then(|path| append_entry(&path, &entry))
+    {
+        eprintln!("⚠️  Could not record run history: {e}");
+    }
+
+    result
+}
+
+/// Formats a duration in milliseconds for display in the history list.
+#[must_use]
+pub fn format_duration(duration_ms: u64) -> String {
+    match duration_ms {
+        0..1_000 => format!("{duration_ms}ms"),
+        1_000..60_000 => format!("{:.1}s", duration_ms as f64 / 1_000.0),
+        _ => format!(
+            "{}m{:02}s",
+            duration_ms / 60_000,
+            duration_ms % 60_000 / 1_000
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, exit_code: Option<i32>) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: Local::now(),
+            command_id: None,
+            display_name: None,
+            command: command.into(),
+            argv: Vec::new(),
+            cwd: None,
+            exit_code,
+            duration_ms: 5,
+        }
+    }
+
+    #[test]
+    fn test_append_and_load_recent_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("history.jsonl");
+
+        append_entry(&path, &entry("echo one", Some(0))).unwrap();
+        append_entry(&path, &entry("echo two", Some(1))).unwrap();
+        append_entry(&path, &entry("echo three", None)).unwrap();
+
+        let recent = load_recent(&path, 2).unwrap();
+
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].command, "echo three");
+        assert_eq!(recent[1].exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_load_recent_skips_malformed_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        append_entry(&path, &entry("echo ok", Some(0))).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "not json").unwrap();
+
+        let recent = load_recent(&path, 10).unwrap();
+
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].label(), "echo ok");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(250), "250ms");
+        assert_eq!(format_duration(1_500), "1.5s");
+        assert_eq!(format_duration(125_000), "2m05s");
+    }
+}
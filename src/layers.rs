@@ -0,0 +1,674 @@
+//This file contains the read-only layers a config can include, such as a shared team catalog.
+//Included commands and groups are merged beneath the config's own entries when it is loaded
+//and stripped again before it is saved, so an included file is never written.
+
+use crate::config::{
+    CommandGroup, CommandOption, Config, expand_path, generate_command_id, global_config_path,
+    is_project_config, read_config_file, read_config_file_with_ids,
+};
+use anyhow::{Context, bail};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns the short name shown for a layer file, such as `team` for `team.json`.
+#[must_use]
+pub fn layer_name(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the ` [team]` suffix shown after entries from an included layer, or nothing for own entries.
+#[must_use]
+pub fn layer_suffix(layer: Option<&Path>) -> String {
+    layer.map_or_else(String::new, |layer| format!(" [{}]", layer_name(layer)))
+}
+
+/// Returns the first included layer that supplies the group or anything inside it.
+#[must_use]
+pub fn group_layer(group: &CommandGroup) -> Option<&Path> {
+    group
+        .layer
+        .as_deref()
+        .or_else(|| {
+            group
+                .commands
+                .iter()
+                .find_map(|command| command.layer.as_deref())
+        })
+        .or_else(|| group.groups.iter().find_map(group_layer))
+}
+
+/// Merges the files listed in `config.include` beneath the config's own entries.
+///
+/// The config's own commands win, then each include in the order listed. A command
+/// is overridden when a higher layer has a command with the same ID anywhere, both
+/// set in their files, or one with the same display name in the same group. A project
+/// `.cli_menu.json` also gets the global config as its lowest layer unless it sets
+/// `includeGlobal` to false. The `env`, `envFile` and `shell` of an included file apply
+/// to that file's own commands.
+///
+/// Every command ID of `config` counts as set in its file; see [`merge_includes_with_ids`].
+///
+/// # Errors
+///
+/// Returns an error when an include cannot be resolved, read or parsed, or when
+/// files include each other in a cycle.
+pub fn merge_includes(config: &mut Config, path: &Path) -> anyhow::Result<()> {
+    merge_includes_with_ids(config, path, HashSet::new())
+}
+
+/// Merges includes like [`merge_includes`]; `generated_ids` are the IDs of `config` that
+/// were generated on load because its file left them out, so they never override by ID.
+///
+/// # Errors
+///
+/// Returns an error when an include cannot be resolved, read or parsed, or when
+/// files include each other in a cycle.
+pub fn merge_includes_with_ids(
+    config: &mut Config,
+    path: &Path,
+    mut generated_ids: HashSet<String>,
+) -> anyhow::Result<()> {
+    let canonical = fs::canonicalize(path)
+        .with_context(|| format!("unable to resolve config path {}", path.display()))?;
+    let mut stack = vec![canonical];
+    merge_level_includes(config, &mut generated_ids, &mut stack)?;
+
+    if is_project_config(path) && config.include_global != Some(false) {
+        let global = global_config_path().map_err(anyhow::Error::msg)?;
+        if global.is_file() {
+            let layer =
+                load_layer(&global, &mut stack).context("unable to include the global config")?;
+            merge_layer(config, &mut generated_ids, layer);
+        }
+    }
+    Ok(())
+}
+
+fn merge_level_includes(
+    config: &mut Config,
+    generated_ids: &mut HashSet<String>,
+    stack: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+    let base_dir = config.base_dir().map(Path::to_path_buf);
+    for include in config.include.clone() {
+        let path = expand_path(&include, base_dir.as_deref()).map_err(anyhow::Error::msg)?;
+        let layer =
+            load_layer(&path, stack).with_context(|| format!("unable to include '{include}'"))?;
+        merge_layer(config, generated_ids, layer);
+    }
+    Ok(())
+}
+
+/// Returns the config file and every file it includes, directly or through other includes.
+///
+/// # Errors
+///
+/// Returns an error when a file cannot be resolved, read or parsed.
+pub fn config_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    collect_config_files(path, &mut files)?;
+    Ok(files)
+}
+
+fn collect_config_files(path: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let canonical = fs::canonicalize(path)
+        .with_context(|| format!("unable to find config file {}", path.display()))?;
+    if files.contains(&canonical) {
+        return Ok(());
+    }
+    files.push(canonical);
+
+    let config = read_config_file(path)?;
+    let base_dir = config.base_dir().map(Path::to_path_buf);
+    for include in &config.include {
+        let include_path = expand_path(include, base_dir.as_deref()).map_err(anyhow::Error::msg)?;
+        collect_config_files(&include_path, files)?;
+    }
+    Ok(())
+}
+
+/// Returns the env files named in the config file at `path` itself, file-wide or by a command.
+///
+/// Includes are not followed, and files that do not exist or name an unset variable are skipped.
+///
+/// # Errors
+///
+/// Returns an error when the config file cannot be read or parsed.
+pub fn env_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let config = read_config_file(path)?;
+    let named = std::iter::once(&config.env_file).chain(
+        config
+            .all_commands()
+            .into_iter()
+            .map(|location| &location.command.env_file),
+    );
+
+    let mut files = Vec::new();
+    for env_file in named.flatten() {
+        let resolved = expand_path(env_file, config.base_dir())
+            .ok()
+            .and_then(|env_path| fs::canonicalize(env_path).ok());
+        if let Some(env_path) = resolved.filter(|env_path| !files.contains(env_path)) {
+            files.push(env_path);
+        }
+    }
+    Ok(files)
+}
+
+// An included file merged with its own includes, and the command IDs generated while loading it
+struct Layer {
+    config: Config,
+    generated_ids: HashSet<String>,
+}
+
+// The command IDs already merged: those set in their files can override, all of them are taken
+struct MergedIds<'a> {
+    set: HashSet<String>,
+    taken: HashSet<String>,
+    generated: &'a mut HashSet<String>,
+}
+
+fn merge_layer(config: &mut Config, generated_ids: &mut HashSet<String>, layer: Layer) {
+    let taken = config.command_ids();
+    let mut ids = MergedIds {
+        set: taken.difference(generated_ids).cloned().collect(),
+        taken,
+        generated: generated_ids,
+    };
+    ids.generated.extend(layer.generated_ids);
+    merge_level(
+        &mut config.commands,
+        &mut config.groups,
+        layer.config.commands,
+        layer.config.groups,
+        &mut ids,
+    );
+}
+
+// Loads an included file together with its own includes and marks every entry with the file it came from
+fn load_layer(path: &Path, stack: &mut Vec<PathBuf>) -> anyhow::Result<Layer> {
+    let canonical = fs::canonicalize(path)
+        .with_context(|| format!("unable to find config layer {}", path.display()))?;
+    if stack.contains(&canonical) {
+        bail!("{} is included in a cycle", path.display());
+    }
+
+    let (mut config, generated_ids) = read_config_file_with_ids(path)?;
+    let mut generated_ids = regenerate_layer_ids(&mut config, path, &generated_ids);
+    apply_layer_settings(&mut config, path)?;
+    stack.push(canonical);
+    merge_level_includes(&mut config, &mut generated_ids, stack)?;
+    stack.pop();
+
+    mark_layer(&mut config.commands, &mut config.groups, path);
+    Ok(Layer {
+        config,
+        generated_ids,
+    })
+}
+
+// Seeds generated IDs with the layer and group path too, so a command left without an ID
+// does not take the ID of a same-named command elsewhere; returns the new IDs
+fn regenerate_layer_ids(
+    config: &mut Config,
+    path: &Path,
+    generated_ids: &HashSet<String>,
+) -> HashSet<String> {
+    fn regenerate(
+        commands: &mut [CommandOption],
+        groups: &mut [CommandGroup],
+        prefix: &str,
+        generated_ids: &HashSet<String>,
+        taken: &mut HashSet<String>,
+        regenerated: &mut HashSet<String>,
+    ) {
+        for command in commands
+            .iter_mut()
+            .filter(|command| generated_ids.contains(&command.id))
+        {
+            let seed = format!("{prefix}/{}", command.display_name.trim());
+            command.id = generate_command_id(&seed, taken);
+            taken.insert(command.id.clone());
+            regenerated.insert(command.id.clone());
+        }
+        for group in groups {
+            let prefix = format!("{prefix}/{}", group.name.trim());
+            regenerate(
+                &mut group.commands,
+                &mut group.groups,
+                &prefix,
+                generated_ids,
+                taken,
+                regenerated,
+            );
+        }
+    }
+
+    let mut taken = config
+        .command_ids()
+        .difference(generated_ids)
+        .cloned()
+        .collect();
+    let mut regenerated = HashSet::new();
+    regenerate(
+        &mut config.commands,
+        &mut config.groups,
+        &layer_name(path),
+        generated_ids,
+        &mut taken,
+        &mut regenerated,
+    );
+    regenerated
+}
+
+// Moves the file-wide `env`, `envFile` and `shell` of an included file into its commands,
+// beneath their own settings, as the including config only applies its own
+fn apply_layer_settings(config: &mut Config, path: &Path) -> anyhow::Result<()> {
+    let env = std::mem::take(&mut config.env);
+    let env_file = config.env_file.take();
+    let shell = config.shell.take();
+    let mut conflict = None;
+    config.for_each_command_mut(|command| {
+        for (name, value) in &env {
+            command
+                .env
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        match (&command.env_file, &env_file) {
+            (None, Some(_)) => command.env_file.clone_from(&env_file),
+            (Some(_), Some(_)) => {
+                conflict.get_or_insert_with(|| command.display_name.clone());
+            }
+            _ => {}
+        }
+        if command.shell.is_none() {
+            command.shell.clone_from(&shell);
+        }
+    });
+    if let Some(name) = conflict {
+        bail!(
+            "{} sets an envFile for all its commands and '{name}' has its own; \
+             an included file can only use one of them",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+// Entries already marked came from a deeper include and keep that file
+fn mark_layer(commands: &mut [CommandOption], groups: &mut [CommandGroup], path: &Path) {
+    for command in commands
+        .iter_mut()
+        .filter(|command| command.layer.is_none())
+    {
+        command.layer = Some(path.to_path_buf());
+    }
+    for group in groups {
+        if group.layer.is_none() {
+            group.layer = Some(path.to_path_buf());
+        }
+        mark_layer(&mut group.commands, &mut group.groups, path);
+    }
+}
+
+// Appends the layer's entries that are not overridden, joining groups that share a name
+fn merge_level(
+    commands: &mut Vec<CommandOption>,
+    groups: &mut Vec<CommandGroup>,
+    layer_commands: Vec<CommandOption>,
+    layer_groups: Vec<CommandGroup>,
+    ids: &mut MergedIds,
+) {
+    for mut command in layer_commands {
+        let name = command.display_name.trim();
+        let id_set = !ids.generated.contains(&command.id);
+        let overridden = (id_set && ids.set.contains(&command.id))
+            || commands
+                .iter()
+                .any(|own| own.display_name.trim().eq_ignore_ascii_case(name));
+        if overridden {
+            continue;
+        }
+        // Two different commands cannot share an ID once they are in one menu
+        if ids.taken.contains(&command.id) {
+            command.id = generate_command_id(&command.id, &ids.taken);
+            ids.generated.insert(command.id.clone());
+        } else if id_set {
+            ids.set.insert(command.id.clone());
+        }
+        ids.taken.insert(command.id.clone());
+        commands.push(command);
+    }
+
+    for layer_group in layer_groups {
+        let index = if let Some(index) = groups.iter().position(|group| {
+            group
+                .name
+                .trim()
+                .eq_ignore_ascii_case(layer_group.name.trim())
+        }) {
+            index
+        } else {
+            groups.push(CommandGroup {
+                name: layer_group.name.clone(),
+                layer: layer_group.layer.clone(),
+                ..Default::default()
+            });
+            groups.len() - 1
+        };
+        let group = &mut groups[index];
+        merge_level(
+            &mut group.commands,
+            &mut group.groups,
+            layer_group.commands,
+            layer_group.groups,
+            ids,
+        );
+    }
+}
+
+/// Returns a copy of the config with only its own entries, as written by `save_config`.
+///
+/// Groups that only exist in an included layer are dropped once they hold none of
+/// the config's own commands.
+#[must_use]
+pub fn own_layer(config: &Config) -> Config {
+    let mut own = config.clone();
+    retain_layer(&mut own.commands, &mut own.groups, false);
+    own
+}
+
+/// Removes the config's own commands and groups, keeping those from included layers.
+///
+/// Returns `true` when anything was removed.
+pub fn clear_own_entries(config: &mut Config) -> bool {
+    let before = config.clone();
+    retain_layer(&mut config.commands, &mut config.groups, true);
+    *config != before
+}
+
+// Keeps the commands from layers when `layered` is set, otherwise the config's own commands
+fn retain_layer(commands: &mut Vec<CommandOption>, groups: &mut Vec<CommandGroup>, layered: bool) {
+    commands.retain(|command| command.layer.is_some() == layered);
+    groups.retain_mut(|group| {
+        retain_layer(&mut group.commands, &mut group.groups, layered);
+        group.layer.is_some() == layered || !group.commands.is_empty() || !group.groups.is_empty()
+    });
+}
+
+/// Reads `include` either as a single path or as a list of paths.
+///
+/// # Errors
+///
+/// Returns a deserialization error for any other JSON value.
+pub fn deserialize_include<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Include {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Include::deserialize(deserializer)? {
+        Include::One(path) => vec![path],
+        Include::Many(paths) => paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{load_config, save_config_in};
+
+    fn write(path: &Path, json: &serde_json::Value) {
+        fs::write(path, serde_json::to_string_pretty(json).unwrap()).unwrap();
+    }
+
+    fn team_catalog(dir: &Path) {
+        write(
+            &dir.join("team.json"),
+            &serde_json::json!({
+                "commands": [
+                    {"id": "deploy", "display_name": "Deploy", "command": "make deploy"},
+                    {"id": "lint", "display_name": "Lint", "command": "make lint"}
+                ],
+                "groups": [{
+                    "name": "Ops",
+                    "commands": [{"id": "logs", "display_name": "Logs", "command": "tail log"}]
+                }]
+            }),
+        );
+    }
+
+    #[test]
+    fn test_includes_merge_beneath_own_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        team_catalog(dir.path());
+        let path = dir.path().join("menu.json");
+        write(
+            &path,
+            &serde_json::json!({
+                "extends": "team.json",
+                "commands": [
+                    {"id": "mine", "display_name": "Deploy", "command": "./deploy.sh"},
+                    {"id": "lint", "display_name": "My lint", "command": "cargo clippy"}
+                ]
+            }),
+        );
+
+        let config = load_config(&path).unwrap();
+
+        let names: Vec<String> = config
+            .all_commands()
+            .iter()
+            .map(crate::config::CommandLocation::path)
+            .collect();
+        assert_eq!(names, ["Deploy", "My lint", "Ops/Logs"]);
+        assert_eq!(config.commands[0].layer, None);
+        assert_eq!(config.groups[0].layer, Some(dir.path().join("team.json")));
+        assert_eq!(
+            config.groups[0].commands[0].layer,
+            Some(dir.path().join("team.json"))
+        );
+    }
+
+    #[test]
+    fn test_same_name_in_another_group_is_not_overridden() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            &dir.path().join("team.json"),
+            &serde_json::json!({
+                "groups": [{
+                    "name": "Ops",
+                    "commands": [{"display_name": "Deploy", "command": "make deploy"}]
+                }]
+            }),
+        );
+        let path = dir.path().join("menu.json");
+        write(
+            &path,
+            &serde_json::json!({
+                "include": "team.json",
+                "commands": [{"display_name": "Deploy", "command": "./deploy.sh"}]
+            }),
+        );
+
+        let config = load_config(&path).unwrap();
+
+        let names: Vec<String> = config
+            .all_commands()
+            .iter()
+            .map(crate::config::CommandLocation::path)
+            .collect();
+        assert_eq!(names, ["Deploy", "Ops/Deploy"]);
+        assert_ne!(config.commands[0].id, config.groups[0].commands[0].id);
+    }
+
+    #[test]
+    fn test_ids_set_in_both_layers_override() {
+        let dir = tempfile::tempdir().unwrap();
+        team_catalog(dir.path());
+        let path = dir.path().join("menu.json");
+        write(
+            &path,
+            &serde_json::json!({
+                "include": "team.json",
+                "groups": [{
+                    "name": "Mine",
+                    "commands": [{"id": "logs", "display_name": "My logs", "command": "less log"}]
+                }]
+            }),
+        );
+
+        let config = load_config(&path).unwrap();
+
+        let names: Vec<String> = config
+            .all_commands()
+            .iter()
+            .map(crate::config::CommandLocation::path)
+            .collect();
+        assert_eq!(names, ["Deploy", "Lint", "Mine/My logs"]);
+    }
+
+    #[test]
+    fn test_layer_settings_apply_to_its_own_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            &dir.path().join("team.json"),
+            &serde_json::json!({
+                "env": {"STAGE": "team", "REGION": "eu"},
+                "envFile": "team.env",
+                "shell": "bash",
+                "commands": [
+                    {"id": "deploy", "display_name": "Deploy", "command": "make deploy",
+                     "env": {"STAGE": "prod"}, "shell": "zsh"},
+                    {"id": "lint", "display_name": "Lint", "command": "make lint"}
+                ]
+            }),
+        );
+        fs::write(dir.path().join("team.env"), "TOKEN=team\n").unwrap();
+        let path = dir.path().join("menu.json");
+        write(
+            &path,
+            &serde_json::json!({
+                "include": "team.json",
+                "commands": [{"id": "mine", "display_name": "Mine", "command": "true"}]
+            }),
+        );
+
+        let config = load_config(&path).unwrap();
+
+        let [mine, deploy, lint] = &config.commands[..] else {
+            panic!("expected three commands: {:?}", config.commands);
+        };
+        assert_eq!(config.shell_for(mine), None);
+        assert_eq!(config.command_env(mine).unwrap().get("STAGE"), None);
+        assert_eq!(
+            deploy.shell.as_ref().map(|shell| shell.program()),
+            Some("zsh")
+        );
+        assert_eq!(
+            lint.shell.as_ref().map(|shell| shell.program()),
+            Some("bash")
+        );
+        let env = config.command_env(deploy).unwrap();
+        assert_eq!(env["STAGE"], "prod");
+        assert_eq!(env["REGION"], "eu");
+        assert_eq!(env["TOKEN"], "team");
+    }
+
+    #[test]
+    fn test_save_writes_only_own_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        team_catalog(dir.path());
+        let path = dir.path().join("menu.json");
+        write(&path, &serde_json::json!({"include": ["team.json"]}));
+        let mut config = load_config(&path).unwrap();
+        let mut moved = config.commands.remove(0);
+        moved.layer = None;
+        config.groups[0].commands.push(moved);
+
+        save_config_in(dir.path(), &path, &config).unwrap();
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["include"], serde_json::json!(["team.json"]));
+        assert_eq!(saved["commands"], serde_json::json!([]));
+        assert_eq!(saved["groups"][0]["name"], "Ops");
+        assert_eq!(saved["groups"][0]["commands"][0]["id"], "deploy");
+        assert_eq!(
+            saved["groups"][0]["commands"].as_array().map(Vec::len),
+            Some(1)
+        );
+        let reloaded = load_config(&path).unwrap();
+        assert_eq!(reloaded.command_count(), 3);
+    }
+
+    #[test]
+    fn test_config_files_lists_nested_includes_once() {
+        let dir = tempfile::tempdir().unwrap();
+        team_catalog(dir.path());
+        write(
+            &dir.path().join("shared.json"),
+            &serde_json::json!({"include": "team.json"}),
+        );
+        let path = dir.path().join("menu.json");
+        write(
+            &path,
+            &serde_json::json!({"include": ["shared.json", "team.json"]}),
+        );
+
+        let files = config_files(&path).unwrap();
+
+        let names: Vec<String> = files.iter().map(|file| layer_name(file)).collect();
+        assert_eq!(names, ["menu", "shared", "team"]);
+    }
+
+    #[test]
+    fn test_include_cycles_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            &dir.path().join("a.json"),
+            &serde_json::json!({"include": "b.json"}),
+        );
+        write(
+            &dir.path().join("b.json"),
+            &serde_json::json!({"include": "a.json"}),
+        );
+
+        let error = load_config(&dir.path().join("a.json")).unwrap_err();
+
+        assert!(format!("{error:#}").contains("cycle"), "{error:#}");
+    }
+
+    #[test]
+    fn test_clear_own_entries_keeps_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        team_catalog(dir.path());
+        let path = dir.path().join("menu.json");
+        write(
+            &path,
+            &serde_json::json!({
+                "include": "team.json",
+                "commands": [{"id": "mine", "display_name": "Mine", "command": "true"}],
+                "groups": [{"name": "Empty"}]
+            }),
+        );
+        let mut config = load_config(&path).unwrap();
+
+        assert!(clear_own_entries(&mut config));
+        assert!(!clear_own_entries(&mut config));
+
+        assert_eq!(config.command_count(), 3);
+        assert!(config.groups.iter().all(|group| group.name != "Empty"));
+    }
+}
@@ -1,5 +1,23 @@
+pub mod backups;
+pub mod cli;
+pub mod completions;
 pub mod config;
+pub mod config_diff;
 pub mod csv;
+pub mod dotenv;
+pub mod edit_history;
+pub mod formats;
+pub mod history;
+pub mod layers;
+pub mod lock;
 pub mod menu_edit;
 pub mod menu_main;
+pub mod merge;
+pub mod migrations;
+pub mod placeholders;
+pub mod recovery;
+pub mod report;
+pub mod schema;
+pub mod state;
+pub mod trust;
 pub mod utils;
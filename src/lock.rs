@@ -0,0 +1,207 @@
+//This file contains the advisory lock held while a config is saved and the stamp of its contents.
+//Every save locks a file in the data directory named after the config, so two instances never write
+//the same config at once, and an edit session keeps the stamp of the file it loaded to notice edits
+//made elsewhere in the meantime.
+
+use crate::config::{config_file_key, get_data_dir};
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, TryLockError};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a save waits for another instance to finish saving the same config.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+// Define what a config file held when it was read
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileStamp {
+    pub modified: Option<SystemTime>, // None when the file system does not record it
+    pub sha256: String,
+}
+
+impl FileStamp {
+    /// Reads the stamp of the file at `path`, or `None` when the file does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file exists but cannot be read.
+    pub fn read(path: &Path) -> anyhow::Result<Option<Self>> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("unable to read config file {}", path.display()));
+            }
+        };
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        Ok(Some(Self {
+            modified,
+            sha256: format!("{:x}", Sha256::digest(&contents)),
+        }))
+    }
+
+    /// Returns whether the file held the same contents; a touched but unchanged file is the same.
+    #[must_use]
+    pub fn same_contents(&self, other: &Self) -> bool {
+        self.sha256 == other.sha256
+    }
+
+    /// Returns when the file was last modified, such as `2024-01-01 08:00:00`.
+    #[must_use]
+    pub fn modified_label(&self) -> String {
+        self.modified.map_or_else(
+            || "an unknown time".to_string(),
+            |modified| {
+                DateTime::<Local>::from(modified)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            },
+        )
+    }
+}
+
+// Define the lock on one config file, released when it is dropped
+//
+// The lock file itself is kept: deleting it on release would let a waiting instance lock the
+// deleted file while a third one creates and locks a new file, so both would save at once.
+// That leaves one empty file per config path in the data directory.
+#[derive(Debug)]
+pub struct ConfigLock {
+    _file: File,
+    data_dir: PathBuf, // Where the lock was taken, and where the save keeps its backups and trust record
+}
+
+impl ConfigLock {
+    /// Locks the config at `config_path` for saving, waiting up to [`LOCK_TIMEOUT`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the data directory cannot be resolved or another instance
+    /// keeps the config locked.
+    pub fn acquire(config_path: &Path) -> anyhow::Result<Self> {
+        let data_dir = get_data_dir().map_err(anyhow::Error::msg)?;
+        Self::acquire_in(&data_dir, config_path, LOCK_TIMEOUT)
+    }
+
+    /// Locks the config at `config_path` with a lock file in `data_dir`, waiting up to `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the lock file cannot be opened, locking is not supported,
+    /// or another instance still keeps the config locked after `timeout`.
+    pub fn acquire_in(
+        data_dir: &Path,
+        config_path: &Path,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let file = lock_file(&lock_path(data_dir, config_path), timeout).with_context(|| {
+            format!(
+                "{} is being saved by another shell_command_menu; try again in a moment",
+                config_path.display()
+            )
+        })?;
+        Ok(Self {
+            _file: file,
+            data_dir: data_dir.to_path_buf(),
+        })
+    }
+
+    /// Returns the data directory the lock was taken in.
+    #[must_use]
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+}
+
+// Locks the lock file at `path`, waiting up to `timeout` for whoever holds it
+fn lock_file(path: &Path, timeout: Duration) -> anyhow::Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("unable to create lock directory {}", parent.display()))?;
+    }
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("unable to open lock file {}", path.display()))?;
+
+    let started = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(file),
+            Err(TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+            Err(TryLockError::WouldBlock) => {
+                anyhow::bail!("lock file {} is held", path.display());
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("unable to lock {}", path.display()));
+            }
+        }
+    }
+}
+
+/// Returns the lock file of the config at `config_path` inside `data_dir`.
+///
+/// The lock lives in the data directory so project folders do not collect lock files.
+#[must_use]
+pub fn lock_path(data_dir: &Path, config_path: &Path) -> PathBuf {
+    data_dir
+        .join("locks")
+        .join(format!("{}.lock", config_file_key(config_path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("menu.json");
+
+        let lock = ConfigLock::acquire_in(dir.path(), &config, Duration::ZERO).unwrap();
+        assert_eq!(lock.data_dir(), dir.path());
+        assert!(ConfigLock::acquire_in(dir.path(), &config, Duration::from_millis(100)).is_err());
+
+        drop(lock);
+        assert!(ConfigLock::acquire_in(dir.path(), &config, Duration::ZERO).is_ok());
+        assert!(lock_path(dir.path(), &config).exists());
+    }
+
+    #[test]
+    fn test_stamp_tracks_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("menu.json");
+        assert_eq!(FileStamp::read(&config).unwrap(), None);
+
+        fs::write(&config, "{}").unwrap();
+        let loaded = FileStamp::read(&config).unwrap().unwrap();
+        fs::write(&config, "{}").unwrap();
+        assert!(
+            FileStamp::read(&config)
+                .unwrap()
+                .unwrap()
+                .same_contents(&loaded)
+        );
+
+        fs::write(&config, "{\"commands\": []}").unwrap();
+        assert!(
+            !FileStamp::read(&config)
+                .unwrap()
+                .unwrap()
+                .same_contents(&loaded)
+        );
+    }
+}
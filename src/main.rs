@@ -1,51 +1,62 @@
-use shell_command_menu::{config, menu_main, utils};
-use std::path::PathBuf;
+use shell_command_menu::{
+    cli::{self, Action},
+    config, history, menu_main, schema, trust, utils,
+};
 
 #[tokio::main]
 async fn main() {
     // Print the version
     let version = utils::get_version();
-    let mut args = std::env::args().skip(1);
-    let mut config_override: Option<PathBuf> = None;
+    let args = cli::parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
 
-    while let Some(arg) = args.next() {
-        match arg.as_str() {
-            "--version" | "-V" => {
-                println!("{version}");
-                return;
-            }
-            "--run-once" => {
-                let Some(command) = args.next() else {
-                    eprintln!("Missing command for --run-once");
-                    std::process::exit(2);
-                };
-                match utils::run_command(&command) {
-                    Ok(status) => std::process::exit(status.code().unwrap_or(1)),
-                    Err(e) => {
-                        eprintln!("Failed to run command: {e}");
-                        std::process::exit(1);
-                    }
+    match &args.action {
+        Action::Version => {
+            println!("{version}");
+            return;
+        }
+        Action::Help => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Action::PrintSchema => {
+            match serde_json::to_string_pretty(&schema::config_schema()) {
+                Ok(schema) => println!("{schema}"),
+                Err(e) => {
+                    eprintln!("Failed to print the config schema: {e}");
+                    std::process::exit(1);
                 }
             }
-            "--config" | "-c" => {
-                let Some(path) = args.next() else {
-                    eprintln!("Missing path for {arg}");
-                    std::process::exit(2);
-                };
-                config_override = Some(PathBuf::from(path));
-            }
-            _ => {
-                eprintln!("Unknown argument: {arg}");
-                std::process::exit(2);
+            return;
+        }
+        Action::Completions(shell) => {
+            print!("{}", shell.script());
+            return;
+        }
+        Action::ShellInit(shell) => {
+            print!("{}", shell.widget_script());
+            return;
+        }
+        Action::RunOnce(command) => {
+            match history::run_and_record(&utils::CommandRequest::new(command), None) {
+                Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+                Err(e) => {
+                    eprintln!("Failed to run command: {e}");
+                    std::process::exit(1);
+                }
             }
         }
+        Action::Menu => println!("Welcome to CLI_Menu v{version}!"),
+        Action::Subcommand(_) => {}
     }
 
-    println!("Welcome to CLI_Menu v{version}!");
-    // Execute the config::get_config_file_path function to get the config file path and load it; else create it
-    let config_path_result = match config_override {
-        Some(path) => config::ensure_config_file_path(path),
-        None => config::get_config_file_path(),
+    // Use --config when given, else discover the config (env, project, global); create it when missing
+    let config_given = args.config.is_some();
+    let config_path_result = match args.config {
+        Some(path) => config::ensure_config_file_path(path, args.action == Action::Menu),
+        None => config::get_config_file_path(args.action == Action::Menu),
     };
     let config_path = match config_path_result {
         Ok(path) => {
@@ -56,6 +67,26 @@ async fn main() {
             std::process::exit(1); // Exit if unable to get the config path
         }
     };
+
+    if args.action == Action::Menu && config::is_project_config(&config_path) {
+        println!("📂 Using project config: {}", config_path.display());
+    }
+    if let Action::Subcommand(subcommand) = &args.action {
+        std::process::exit(cli::run_subcommand(
+            &config_path,
+            subcommand,
+            args.format,
+            config_given,
+        ));
+    }
+    match trust::ensure_trusted(&config_path, true) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("❌  {e:#}");
+            std::process::exit(1);
+        }
+    }
     //Execute the display_menu function from the menu module with the config file from previous function
     menu_main::display_menu(&config_path).await;
 }
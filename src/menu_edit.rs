@@ -1,26 +1,44 @@
+use crate::backups::{Backup, backup_dir, list_backups, restore_diff};
 use crate::config::{
-    CommandOption, Config, edit_cmd_sound, edit_window_title, save_config, validate_config,
+    CommandGroup, CommandOption, Config, edit_cmd_sound, edit_pause_after, edit_window_title,
+    generate_command_id, load_config, save_config_locked, serialize_config, validate_config,
 };
+use crate::config_diff::ConfigDiff;
 use crate::csv::{export_commands, import_commands};
+use crate::edit_history::EditHistory;
+use crate::layers::{clear_own_entries, group_layer, layer_name, layer_suffix, own_layer};
+use crate::lock::{ConfigLock, FileStamp};
 use crate::menu_main::prompt_or_return;
+use crate::merge::three_way_merge;
+use crate::recovery::{Recovery, read_recovery, recovery_path, remove_recovery, write_recovery};
 use crate::utils::pause;
 use inquire::Select;
 use prettytable::{Cell, Row, Table, row};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
 use textwrap::fill;
 
+const TOP_LEVEL_LABEL: &str = "Top level";
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum EditMenuChoice {
     Add,
     Edit,
     Reorder,
     Delete,
+    OpenGroup,
+    CreateGroup,
+    RenameGroup,
+    MoveToGroup,
     Reset,
+    Restore,
     Import,
     Export,
     Sound,
     WindowTitle,
+    PauseAfter,
+    Undo,
+    Redo,
     Quit,
 }
 
@@ -28,12 +46,20 @@ impl EditMenuChoice {
     const ADD: &'static str = "a. ADD a new command";
     const EDIT: &'static str = "e. EDIT a command";
     const REORDER: &'static str = "o. REORDER a command";
-    const DELETE: &'static str = "d. DELETE a command";
-    const RESET: &'static str = "r. RESET (clear all commands)";
+    const DELETE: &'static str = "d. DELETE a command or group";
+    const OPEN_GROUP: &'static str = "g. GO to another group";
+    const CREATE_GROUP: &'static str = "c. CREATE a group";
+    const RENAME_GROUP: &'static str = "n. RENAME a group";
+    const MOVE_TO_GROUP: &'static str = "m. MOVE a command to another group";
+    const RESET: &'static str = "r. RESET (clear all commands and groups)";
+    const RESTORE: &'static str = "b. RESTORE from backup";
     const IMPORT: &'static str = "i. IMPORT from .csv";
     const EXPORT: &'static str = "x. EXPORT to .csv";
     const SOUND: &'static str = "s. SET sound file path";
     const WINDOW_TITLE: &'static str = "t. SET Window Title settings";
+    const PAUSE_AFTER: &'static str = "p. SET Pause after run";
+    const UNDO: &'static str = "u. UNDO the last change";
+    const REDO: &'static str = "y. REDO the last undone change";
     const QUIT: &'static str = "q. Return to Main Menu (prompt to save changes)";
 
     fn labels() -> Vec<&'static str> {
@@ -42,11 +68,19 @@ impl EditMenuChoice {
             Self::EDIT,
             Self::REORDER,
             Self::DELETE,
+            Self::OPEN_GROUP,
+            Self::CREATE_GROUP,
+            Self::RENAME_GROUP,
+            Self::MOVE_TO_GROUP,
             Self::RESET,
+            Self::RESTORE,
             Self::IMPORT,
             Self::EXPORT,
             Self::SOUND,
             Self::WINDOW_TITLE,
+            Self::PAUSE_AFTER,
+            Self::UNDO,
+            Self::REDO,
             Self::QUIT,
         ]
     }
@@ -57,44 +91,160 @@ impl EditMenuChoice {
             Self::EDIT => Some(Self::Edit),
             Self::REORDER => Some(Self::Reorder),
             Self::DELETE => Some(Self::Delete),
+            Self::OPEN_GROUP => Some(Self::OpenGroup),
+            Self::CREATE_GROUP => Some(Self::CreateGroup),
+            Self::RENAME_GROUP => Some(Self::RenameGroup),
+            Self::MOVE_TO_GROUP => Some(Self::MoveToGroup),
             Self::RESET => Some(Self::Reset),
+            Self::RESTORE => Some(Self::Restore),
             Self::IMPORT => Some(Self::Import),
             Self::EXPORT => Some(Self::Export),
             Self::SOUND => Some(Self::Sound),
             Self::WINDOW_TITLE => Some(Self::WindowTitle),
+            Self::PAUSE_AFTER => Some(Self::PauseAfter),
+            Self::UNDO => Some(Self::Undo),
+            Self::REDO => Some(Self::Redo),
             Self::QUIT => Some(Self::Quit),
             _ => None,
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ConflictChoice {
+    Merge,
+    Abort,
+}
+
+impl ConflictChoice {
+    const MERGE: &'static str = "Merge my changes into the file";
+    const ABORT: &'static str = "Abort saving and go back to editing";
+
+    fn labels() -> Vec<&'static str> {
+        vec![Self::MERGE, Self::ABORT]
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            Self::MERGE => Some(Self::Merge),
+            Self::ABORT => Some(Self::Abort),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RecoveryChoice {
+    Resume,
+    Save,
+    Discard,
+}
+
+impl RecoveryChoice {
+    const RESUME: &'static str = "Resume editing";
+    const SAVE: &'static str = "Save them";
+    const DISCARD: &'static str = "Throw them away";
+
+    fn labels() -> Vec<&'static str> {
+        vec![Self::RESUME, Self::SAVE, Self::DISCARD]
+    }
+
+    fn from_label(label: &str) -> Option<Self> {
+        match label {
+            Self::RESUME => Some(Self::Resume),
+            Self::SAVE => Some(Self::Save),
+            Self::DISCARD => Some(Self::Discard),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum SaveChoice {
-    Yes,
-    No,
+    Save,
+    Back,
+    Discard,
 }
 
 impl SaveChoice {
+    const SAVE: &'static str = "Save changes";
+    const BACK: &'static str = "Go back to editing";
+    const DISCARD: &'static str = "Discard changes";
+
+    fn labels() -> Vec<&'static str> {
+        vec![Self::SAVE, Self::BACK, Self::DISCARD]
+    }
+
     fn from_label(label: &str) -> Option<Self> {
         match label {
-            "Yes" => Some(Self::Yes),
-            "No" => Some(Self::No),
+            Self::SAVE => Some(Self::Save),
+            Self::BACK => Some(Self::Back),
+            Self::DISCARD => Some(Self::Discard),
             _ => None,
         }
     }
 }
 
 pub fn edit_menu(config_path: &Path) {
-    let mut config = crate::config::load_config(config_path).unwrap_or_else(|e| {
+    // Only approved contents are edited, so saving cannot approve changes nobody reviewed
+    match crate::trust::ensure_trusted(config_path, true) {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            process::exit(1);
+        }
+    }
+    // A session that ended without saving comes first, so a new session cannot replace it
+    if offer_recovery(config_path) {
+        return;
+    }
+    // Stamped before loading, so an edit made while loading is noticed rather than missed
+    let loaded_stamp = FileStamp::read(config_path).unwrap_or(None);
+    let config = load_config(config_path).unwrap_or_else(|e| {
         eprintln!("Error: {e}");
         process::exit(1);
     });
     let original_config = config.clone();
-    let mut changes_made = false;
+    run_edit_session(config_path, config, original_config, loaded_stamp);
+}
+
+// Runs the edit menu on `config`, which was loaded as `original_config`
+fn run_edit_session(
+    config_path: &Path,
+    mut config: Config,
+    mut original_config: Config,
+    mut loaded_stamp: Option<FileStamp>,
+) {
+    let mut changes_made = config != original_config;
+    let mut group: Vec<usize> = Vec::new();
+    let mut history = EditHistory::default();
+    let journal_path = recovery_path(config_path).ok();
+    let mut journaled = original_config.clone();
 
     loop {
+        // Every change is journaled, so closing the terminal does not lose the session
+        if config != journaled {
+            if let Some(path) = &journal_path {
+                journal_session(path, &original_config, &config, loaded_stamp.as_ref());
+            }
+            journaled.clone_from(&config);
+        }
+        // Resetting or discarding can remove the group being edited
+        if config.level(&group).is_none() {
+            group.clear();
+        }
         println!("\n🛠️ Welcome to the Edit Menu 🛠️");
-        print_commands(&config.commands);
+        if !group.is_empty() {
+            println!("Editing group: {}", group_label(&config, &group));
+        }
+        if let Some(description) = history.next_undo() {
+            println!("Last change: {description}");
+        }
+        if let Some((commands, groups)) = config.level(&group) {
+            print_commands(commands);
+            print_groups(groups);
+        }
 
         let menu_prompt = prompt_or_return(|| {
             Select::new("Select an option: ", EditMenuChoice::labels()).prompt()
@@ -108,14 +258,44 @@ pub fn edit_menu(config_path: &Path) {
             continue;
         };
 
+        // Every change made by the choice becomes one step that UNDO can revert
+        let before = config.clone();
         match choice {
-            EditMenuChoice::Add => add_command(&mut config, &mut changes_made),
-            EditMenuChoice::Edit => edit_command(&mut config, &mut changes_made),
-            EditMenuChoice::Reorder => reorder_command(&mut config, &mut changes_made),
-            EditMenuChoice::Delete => delete_command(&mut config, &mut changes_made),
+            EditMenuChoice::Add => add_command(&mut config, &group, &mut changes_made),
+            EditMenuChoice::Edit => edit_command(&mut config, &group, &mut changes_made),
+            EditMenuChoice::Reorder => reorder_command(&mut config, &group, &mut changes_made),
+            EditMenuChoice::Delete => delete_command(&mut config, &group, &mut changes_made),
+            EditMenuChoice::OpenGroup => {
+                if let Some(path) = select_group(&config, "Select a group to edit:", None) {
+                    group = path;
+                }
+            }
+            EditMenuChoice::CreateGroup => create_group(&mut config, &group, &mut changes_made),
+            EditMenuChoice::RenameGroup => rename_group(&mut config, &group, &mut changes_made),
+            EditMenuChoice::MoveToGroup => move_command(&mut config, &group, &mut changes_made),
             EditMenuChoice::Reset => clear_all_commands(&mut config, &mut changes_made),
+            EditMenuChoice::Restore => {
+                restore_from_backup(config_path, &mut config, &mut changes_made);
+            }
             EditMenuChoice::Sound => edit_cmd_sound(&mut config, &mut changes_made),
             EditMenuChoice::WindowTitle => edit_window_title(&mut config, &mut changes_made),
+            EditMenuChoice::PauseAfter => edit_pause_after(&mut config, &mut changes_made),
+            EditMenuChoice::Undo => {
+                match history.undo(&mut config) {
+                    Some(description) => println!("↩️  Undid: {description}"),
+                    None => println!("❌  Nothing to undo."),
+                }
+                changes_made = config != original_config;
+                continue;
+            }
+            EditMenuChoice::Redo => {
+                match history.redo(&mut config) {
+                    Some(description) => println!("↪️  Redid: {description}"),
+                    None => println!("❌  Nothing to redo."),
+                }
+                changes_made = config != original_config;
+                continue;
+            }
             EditMenuChoice::Import => {
                 import_commands(&mut config, &mut changes_made);
                 print!("Press any key to return to Edit Command Menu...");
@@ -127,18 +307,29 @@ pub fn edit_menu(config_path: &Path) {
                 pause();
             }
             EditMenuChoice::Quit => {
-                if changes_made {
+                // Edits that cancel each other out leave nothing to save
+                if changes_made && config != original_config {
+                    println!("\nPending changes:");
+                    println!(
+                        "{}",
+                        ConfigDiff::between(&original_config, &config).render()
+                    );
                     let save_prompt = prompt_or_return(|| {
-                        Select::new("Save changes?", vec!["Yes", "No"]).prompt()
+                        Select::new("Save these changes?", SaveChoice::labels()).prompt()
                     });
                     let save_choice = save_prompt.and_then(SaveChoice::from_label);
                     match save_choice {
-                        Some(SaveChoice::Yes) => {
-                            if !save_current_config(config_path, &config) {
+                        Some(SaveChoice::Save) => {
+                            if !save_current_config(
+                                config_path,
+                                &mut config,
+                                &mut original_config,
+                                &mut loaded_stamp,
+                            ) {
                                 continue;
                             }
                         }
-                        Some(SaveChoice::No) => {
+                        Some(SaveChoice::Discard) => {
                             discard_edit_session(&mut config, &original_config, &mut changes_made);
                             println!(
                                 "❌  Changes not saved. Press any key to return to Main Menu..."
@@ -151,13 +342,134 @@ pub fn edit_menu(config_path: &Path) {
                 break;
             }
         }
+        history.record(before, &config);
+    }
+
+    // The session was saved or discarded, so there is nothing left to recover
+    if let Some(path) = &journal_path {
+        remove_journal(path);
+    }
+}
+
+// Writes the session to the recovery file, or removes the file when nothing is left unsaved
+fn journal_session(
+    path: &Path,
+    original_config: &Config,
+    config: &Config,
+    loaded_stamp: Option<&FileStamp>,
+) {
+    let result = if config == original_config {
+        remove_recovery(path)
+    } else {
+        write_recovery(path, &Recovery::new(original_config, config, loaded_stamp))
+    };
+    if let Err(e) = result {
+        eprintln!("⚠️  Unable to update the recovery file: {e:#}");
+    }
+}
+
+/// Offers to resume, save or discard an edit session of `config_path` that ended without
+/// saving, such as when the terminal was closed.
+///
+/// Returns `true` when the session was resumed or saved, or left for later, so no new
+/// session should start now.
+pub fn offer_recovery(config_path: &Path) -> bool {
+    let Ok(path) = recovery_path(config_path) else {
+        return false;
+    };
+    let recovery = match read_recovery(&path) {
+        Ok(Some(recovery)) => recovery,
+        Ok(None) => return false,
+        Err(e) => {
+            eprintln!("⚠️  Unable to read the unsaved edit session: {e:#}");
+            return false;
+        }
+    };
+    let loaded_stamp = recovery.loaded_stamp.clone();
+    let label = recovery.label();
+    let (mut config, mut original_config) = match recovery.into_configs(config_path) {
+        Ok(configs) => configs,
+        Err(e) => {
+            eprintln!("⚠️  Unable to restore the unsaved edit session: {e:#}");
+            return false;
+        }
+    };
+
+    println!(
+        "\n🩹 An edit session of {} ended at {label} without saving these changes:",
+        config_path.display()
+    );
+    println!(
+        "{}",
+        ConfigDiff::between(&original_config, &config).render()
+    );
+    let choice = prompt_or_return(|| {
+        Select::new(
+            "What do you want to do with them?",
+            RecoveryChoice::labels(),
+        )
+        .prompt()
+    })
+    .and_then(RecoveryChoice::from_label);
+    match choice {
+        Some(RecoveryChoice::Resume) => {
+            run_edit_session(config_path, config, original_config, loaded_stamp);
+        }
+        Some(RecoveryChoice::Save) => {
+            let mut loaded_stamp = loaded_stamp;
+            if save_current_config(
+                config_path,
+                &mut config,
+                &mut original_config,
+                &mut loaded_stamp,
+            ) {
+                remove_journal(&path);
+            }
+        }
+        Some(RecoveryChoice::Discard) => {
+            remove_journal(&path);
+            println!("🗑️  Threw away the unsaved changes.");
+            return false;
+        }
+        None => println!("The unsaved changes are kept; you are asked again when you edit."),
+    }
+    true
+}
+
+fn remove_journal(path: &Path) {
+    if let Err(e) = remove_recovery(path) {
+        eprintln!("⚠️  Unable to remove the recovery file: {e:#}");
     }
 }
 
-fn save_current_config(config_path: &Path, config: &Config) -> bool {
+// Saves the session, merging in changes made to the file since `loaded_stamp` was taken
+fn save_current_config(
+    config_path: &Path,
+    config: &mut Config,
+    original_config: &mut Config,
+    loaded_stamp: &mut Option<FileStamp>,
+) -> bool {
+    // Held until the file is written, so no other instance can save in between
+    let lock = match ConfigLock::acquire(config_path) {
+        Ok(lock) => lock,
+        Err(e) => {
+            println!("❌  Error saving config: {e:#}");
+            return false;
+        }
+    };
+    match merge_external_changes(config_path, config, original_config, loaded_stamp) {
+        Ok(true) => {}
+        Ok(false) => return false,
+        Err(e) => {
+            println!("❌  Error merging changes made outside this session: {e:#}");
+            return false;
+        }
+    }
+
     match validate_config(config) {
-        Ok(()) => match save_config(config_path, config) {
+        Ok(()) => match save_config_locked(config_path, config, &lock) {
             Ok(()) => {
+                *loaded_stamp = FileStamp::read(config_path).unwrap_or(None);
                 println!("✅  Changes Saved. Press any key to return to Main Menu...");
                 pause();
                 true
@@ -179,7 +491,104 @@ fn save_current_config(config_path: &Path, config: &Config) -> bool {
     }
 }
 
-pub fn add_command(config: &mut Config, changes_made: &mut bool) {
+/// Checks whether the file changed since the session loaded it, and merges the session into it.
+///
+/// On a merge, `config` becomes the merged config and `original_config` and `loaded_stamp` the
+/// file as it is now. Returns `false` when the user aborts the save.
+///
+/// # Errors
+///
+/// Returns an error when the changed file cannot be read or the merge cannot be applied.
+pub fn merge_external_changes(
+    config_path: &Path,
+    config: &mut Config,
+    original_config: &mut Config,
+    loaded_stamp: &mut Option<FileStamp>,
+) -> anyhow::Result<bool> {
+    let current_stamp = FileStamp::read(config_path)?;
+    let Some(current_stamp) = current_stamp else {
+        if loaded_stamp.take().is_some() {
+            println!(
+                "⚠️  {} was deleted outside this session; saving writes it again.",
+                config_path.display()
+            );
+        }
+        return Ok(true);
+    };
+    if loaded_stamp
+        .as_ref()
+        .is_some_and(|loaded| loaded.same_contents(&current_stamp))
+    {
+        return Ok(true);
+    }
+
+    println!(
+        "\n⚠️  {} was changed outside this session at {}.",
+        config_path.display(),
+        current_stamp.modified_label()
+    );
+    // The file's changes are saved along with yours, so they are reviewed like any other change
+    if !crate::trust::ensure_trusted(config_path, true)? {
+        println!(
+            "🔒  Merging saves the file without approving it; it is reviewed again before its commands are used."
+        );
+    }
+    let theirs = load_config(config_path)?;
+    let merge = three_way_merge(original_config, config, &theirs);
+    println!("Changed in the file:");
+    println!(
+        "{}",
+        ConfigDiff::between(&own_layer(original_config), &own_layer(&theirs)).render()
+    );
+    println!("Your changes:");
+    println!(
+        "{}",
+        ConfigDiff::between(&own_layer(original_config), &own_layer(config)).render()
+    );
+    if !merge.conflicts.is_empty() {
+        println!(
+            "{} of your changes conflict with the file; you choose which version to keep.",
+            merge.conflicts.len()
+        );
+    }
+    let choice = prompt_or_return(|| {
+        Select::new("How do you want to save?", ConflictChoice::labels()).prompt()
+    })
+    .and_then(ConflictChoice::from_label);
+    if choice != Some(ConflictChoice::Merge) {
+        println!("❌  Save aborted; the file was not changed.");
+        return Ok(false);
+    }
+
+    let mut merged = merge.config;
+    for conflict in &merge.conflicts {
+        println!("⚠️  {}", conflict.describe());
+        let keep_ours = format!("Keep mine: {}", conflict.ours_label());
+        let keep_theirs = format!("Keep the file's: {}", conflict.theirs_label());
+        let Some(kept) = prompt_or_return(|| {
+            Select::new(
+                "Which version do you want?",
+                vec![keep_ours.as_str(), keep_theirs.as_str()],
+            )
+            .prompt()
+        }) else {
+            println!("❌  Save aborted; the file was not changed.");
+            return Ok(false);
+        };
+        if kept == keep_ours {
+            conflict.keep_ours(&mut merged)?;
+        }
+    }
+    crate::layers::merge_includes(&mut merged, config_path)?;
+
+    *config = merged;
+    *original_config = theirs;
+    *loaded_stamp = Some(current_stamp);
+    println!("🔀  Merged your changes with the changes made to the file.");
+    Ok(true)
+}
+
+pub fn add_command(config: &mut Config, group: &[usize], changes_made: &mut bool) {
     let Some(display_name) = prompt_or_return(|| {
         inquire::Text::new("Enter the display name for the command:")
             .with_help_message("This name will be displayed in the menu")
@@ -196,22 +605,16 @@ pub fn add_command(config: &mut Config, changes_made: &mut bool) {
         return;
     };
 
-    add_command_to_config(config, display_name, command, changes_made);
+    add_command_to_config(config, group, display_name, command, changes_made);
 }
 
-pub fn edit_command(config: &mut Config, changes_made: &mut bool) {
-    if config.commands.is_empty() {
+pub fn edit_command(config: &mut Config, group: &[usize], changes_made: &mut bool) {
+    let command_names = command_choices(config, group);
+    if command_names.is_empty() {
         println!("❌  No commands to edit. Please add a command first.");
         return;
     }
 
-    let command_names: Vec<String> = config
-        .commands
-        .iter()
-        .enumerate()
-        .map(|(i, cmd)| format!("{}. {}", i + 1, cmd.display_name))
-        .collect();
-
     let Some(command_index) =
         prompt_or_return(|| Select::new("Select a command to edit:", command_names).prompt())
     else {
@@ -221,7 +624,13 @@ pub fn edit_command(config: &mut Config, changes_made: &mut bool) {
         println!("❌  Invalid choice, please try again.");
         return;
     };
-    let Some(existing_command) = config.commands.get(command_number) else {
+    if !ensure_own_command(config, group, command_number, changes_made) {
+        return;
+    }
+    let Some(existing_command) = config
+        .level(group)
+        .and_then(|(commands, _)| commands.get(command_number))
+    else {
         println!("❌  Invalid choice, please try again.");
         return;
     };
@@ -244,21 +653,23 @@ pub fn edit_command(config: &mut Config, changes_made: &mut bool) {
         return;
     };
 
-    let _ = edit_command_at(config, command_number, display_name, command, changes_made);
+    let _ = edit_command_at(
+        config,
+        group,
+        command_number,
+        display_name,
+        command,
+        changes_made,
+    );
 }
 
-pub fn reorder_command(config: &mut Config, changes_made: &mut bool) {
-    if config.commands.is_empty() {
+pub fn reorder_command(config: &mut Config, group: &[usize], changes_made: &mut bool) {
+    let command_names = command_choices(config, group);
+    if command_names.is_empty() {
         println!("❌  No commands to reorder. Please add a command first.");
         return;
     }
-
-    let command_names: Vec<String> = config
-        .commands
-        .iter()
-        .enumerate()
-        .map(|(i, cmd)| format!("{}. {}", i + 1, cmd.display_name))
-        .collect();
+    let command_count = command_names.len();
 
     let Some(command_index) =
         prompt_or_return(|| Select::new("Select a command to reorder:", command_names).prompt())
@@ -270,6 +681,9 @@ pub fn reorder_command(config: &mut Config, changes_made: &mut bool) {
         println!("❌  Invalid choice, please try again.");
         return;
     };
+    if !ensure_own_command(config, group, command_number, changes_made) {
+        return;
+    }
 
     let new_position: usize = match prompt_or_return(|| {
         inquire::Text::new("Enter the new position for this command:")
@@ -280,48 +694,289 @@ pub fn reorder_command(config: &mut Config, changes_made: &mut bool) {
         None => return,
     };
 
-    if reorder_command_to_position(config, command_number, new_position, changes_made) {
+    if reorder_command_to_position(config, group, command_number, new_position, changes_made) {
         println!("✅  Command moved to position {new_position}.");
     } else {
-        println!(
-            "❌  Invalid position. Please enter a number between 1 and {}.",
-            config.commands.len()
-        );
+        println!("❌  Invalid position. Please enter a number between 1 and {command_count}.");
     }
 }
 
-pub fn delete_command(config: &mut Config, changes_made: &mut bool) {
-    if config.commands.is_empty() {
+pub fn delete_command(config: &mut Config, group: &[usize], changes_made: &mut bool) {
+    let mut choices = command_choices(config, group);
+    let command_count = choices.len();
+    choices.extend(group_choices(config, group, command_count));
+    if choices.is_empty() {
         println!("❌  No commands to delete. Please add a command first.");
         return;
     }
 
-    let command_names: Vec<String> = config
-        .commands
-        .iter()
-        .enumerate()
-        .map(|(i, cmd)| format!("{}. {}", i + 1, cmd.display_name))
-        .collect();
+    let Some(selection) =
+        prompt_or_return(|| Select::new("Select a command or group to delete:", choices).prompt())
+    else {
+        return;
+    };
 
-    let Some(command_index) =
-        prompt_or_return(|| Select::new("Select a command to delete:", command_names).prompt())
+    let Some(number) = selected_command_index(&selection) else {
+        println!("❌  Invalid choice, please try again.");
+        return;
+    };
+
+    // Groups are listed after the commands
+    let Some(group_index) = number.checked_sub(command_count) else {
+        if let Some(layer) = command_layer(config, group, number) {
+            println!(
+                "🔒  This command comes from the read-only layer '{}' and can only be removed there.",
+                layer_name(&layer)
+            );
+            return;
+        }
+        if let Some(deleted) = delete_command_at(config, group, number, changes_made) {
+            println!(
+                "✅  Command '{}' deleted successfully.",
+                deleted.display_name
+            );
+        }
+        return;
+    };
+
+    let mut group_path = group.to_vec();
+    group_path.push(group_index);
+    if refuse_layered_group(config, &group_path, "deleted") {
+        return;
+    }
+    let command_total = config
+        .level(group)
+        .and_then(|(_, groups)| groups.get(group_index))
+        .map_or(0, CommandGroup::command_count);
+    if command_total > 0 {
+        let confirmed = prompt_or_return(|| {
+            inquire::Confirm::new(&format!(
+                "Delete the group and the {command_total} commands in it?"
+            ))
+            .with_default(false)
+            .prompt()
+        });
+        if confirmed != Some(true) {
+            return;
+        }
+    }
+
+    if let Some(deleted) = delete_group_at(config, &group_path, changes_made) {
+        println!("✅  Group '{}' deleted successfully.", deleted.name);
+    }
+}
+
+pub fn create_group(config: &mut Config, parent: &[usize], changes_made: &mut bool) {
+    let Some(name) = prompt_or_return(|| {
+        inquire::Text::new("Enter the name for the new group:")
+            .with_help_message("Groups are shown as submenus after the commands")
+            .prompt()
+    }) else {
+        return;
+    };
+
+    match add_group_to_config(config, parent, &name, changes_made) {
+        Ok(()) => println!("✅  Group '{}' created.", name.trim()),
+        Err(e) => println!("❌  {e}"),
+    }
+}
+
+pub fn rename_group(config: &mut Config, parent: &[usize], changes_made: &mut bool) {
+    let choices = group_choices(config, parent, 0);
+    if choices.is_empty() {
+        println!("❌  No groups to rename. Please create a group first.");
+        return;
+    }
+
+    let Some(selection) =
+        prompt_or_return(|| Select::new("Select a group to rename:", choices).prompt())
     else {
         return;
     };
+    let Some(group_index) = selected_command_index(&selection) else {
+        println!("❌  Invalid choice, please try again.");
+        return;
+    };
+    let mut group_path = parent.to_vec();
+    group_path.push(group_index);
+    if refuse_layered_group(config, &group_path, "renamed") {
+        return;
+    }
+    let current_name = config
+        .group_names(&group_path)
+        .last()
+        .map(ToString::to_string);
+
+    let Some(name) = prompt_or_return(|| {
+        inquire::Text::new("Enter the new name for the group:")
+            .with_initial_value(current_name.as_deref().unwrap_or_default())
+            .prompt()
+    }) else {
+        return;
+    };
 
-    let Some(command_number) = selected_command_index(&command_index) else {
+    match rename_group_at(config, &group_path, &name, changes_made) {
+        Ok(()) => println!("✅  Group renamed to '{}'.", name.trim()),
+        Err(e) => println!("❌  {e}"),
+    }
+}
+
+pub fn move_command(config: &mut Config, group: &[usize], changes_made: &mut bool) {
+    let command_names = command_choices(config, group);
+    if command_names.is_empty() {
+        println!("❌  No commands to move. Please add a command first.");
+        return;
+    }
+
+    let Some(selection) =
+        prompt_or_return(|| Select::new("Select a command to move:", command_names).prompt())
+    else {
+        return;
+    };
+    let Some(command_number) = selected_command_index(&selection) else {
         println!("❌  Invalid choice, please try again.");
         return;
     };
+    if !ensure_own_command(config, group, command_number, changes_made) {
+        return;
+    }
+    let Some(target) = select_group(config, "Select the destination group:", Some(group)) else {
+        return;
+    };
 
-    if let Some(deleted) = delete_command_at(config, command_number, changes_made) {
-        println!(
-            "✅  Command '{}' deleted successfully.",
-            deleted.display_name
-        );
+    if move_command_to_group(config, group, command_number, &target, changes_made) {
+        println!("✅  Command moved to {}.", group_label(config, &target));
+    } else {
+        println!("❌  Invalid choice, please try again.");
     }
 }
 
+// Offers to copy a command from a read-only layer into the config before it is changed
+fn ensure_own_command(
+    config: &mut Config,
+    group: &[usize],
+    index: usize,
+    changes_made: &mut bool,
+) -> bool {
+    let Some(layer) = command_layer(config, group, index) else {
+        return true;
+    };
+    println!(
+        "🔒  This command comes from the read-only layer '{}'.",
+        layer_name(&layer)
+    );
+    let copy = prompt_or_return(|| {
+        inquire::Confirm::new("Copy it into your config so you can change it?")
+            .with_default(true)
+            .prompt()
+    });
+    copy == Some(true) && copy_command_to_own_layer(config, group, index, changes_made)
+}
+
+fn command_layer(config: &Config, group: &[usize], index: usize) -> Option<PathBuf> {
+    config
+        .level(group)
+        .and_then(|(commands, _)| commands.get(index))
+        .and_then(|command| command.layer.clone())
+}
+
+// Groups holding entries from a layer would come back on the next load, so they stay as they are
+fn refuse_layered_group(config: &Config, path: &[usize], action: &str) -> bool {
+    let Some((&index, parent)) = path.split_last() else {
+        return false;
+    };
+    let Some(group) = config
+        .level(parent)
+        .and_then(|(_, groups)| groups.get(index))
+    else {
+        return false;
+    };
+    let Some(layer) = group_layer(group) else {
+        return false;
+    };
+    println!(
+        "🔒  Group '{}' holds commands from the read-only layer '{}' and cannot be {action}.",
+        group.name,
+        layer_name(layer)
+    );
+    true
+}
+
+// Lists every group as an indented tree and returns the chosen path, skipping `exclude`
+fn select_group(config: &Config, message: &str, exclude: Option<&[usize]>) -> Option<Vec<usize>> {
+    let paths: Vec<Vec<usize>> = config
+        .group_paths()
+        .into_iter()
+        .filter(|path| Some(path.as_slice()) != exclude)
+        .collect();
+    let choices: Vec<String> = paths
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let name = config
+                .group_names(path)
+                .last()
+                .map_or_else(|| TOP_LEVEL_LABEL.to_string(), |name| format!("📁 {name}"));
+            format!("{}. {}{name}", index + 1, "  ".repeat(path.len()))
+        })
+        .collect();
+
+    let selection = prompt_or_return(|| Select::new(message, choices).prompt())?;
+    let index = selected_command_index(&selection)?;
+    paths.into_iter().nth(index)
+}
+
+/// Returns the `Group / Subgroup` label of a group path, or "Top level" for the root.
+#[must_use]
+pub fn group_label(config: &Config, path: &[usize]) -> String {
+    if path.is_empty() {
+        TOP_LEVEL_LABEL.to_string()
+    } else {
+        config.group_names(path).join(" / ")
+    }
+}
+
+fn command_choices(config: &Config, group: &[usize]) -> Vec<String> {
+    config
+        .level(group)
+        .map(|(commands, _)| {
+            commands
+                .iter()
+                .enumerate()
+                .map(|(i, cmd)| {
+                    format!(
+                        "{}. {}{}",
+                        i + 1,
+                        cmd.display_name,
+                        layer_suffix(cmd.layer.as_deref())
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Numbers the groups of a level starting after `offset` entries
+fn group_choices(config: &Config, parent: &[usize], offset: usize) -> Vec<String> {
+    config
+        .level(parent)
+        .map(|(_, groups)| {
+            groups
+                .iter()
+                .enumerate()
+                .map(|(i, group)| {
+                    format!(
+                        "{}. 📁 {}{}",
+                        offset + i + 1,
+                        group.name,
+                        layer_suffix(group.layer.as_deref())
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn selected_command_index(selection: &str) -> Option<usize> {
     selection
         .split('.')
@@ -340,36 +995,138 @@ pub fn clear_all_commands(config: &mut Config, changes_made: &mut bool) {
     }
 }
 
+fn restore_from_backup(config_path: &Path, config: &mut Config, changes_made: &mut bool) {
+    let backups = match backup_dir(config_path)
+        .map_err(anyhow::Error::msg)
+        .and_then(|dir| list_backups(&dir))
+    {
+        Ok(backups) => backups,
+        Err(e) => {
+            println!("❌  Unable to list backups: {e:#}");
+            return;
+        }
+    };
+    if backups.is_empty() {
+        println!("❌  No backups yet. One is kept each time the config is saved.");
+        return;
+    }
+
+    let labels: Vec<String> = backups
+        .iter()
+        .map(|backup| match backup.load() {
+            Ok(saved) => format!("{} ({} commands)", backup.label(), saved.command_count()),
+            Err(_) => format!("{} (unreadable)", backup.label()),
+        })
+        .collect();
+    let Some(label) =
+        prompt_or_return(|| Select::new("Select a backup to restore:", labels.clone()).prompt())
+    else {
+        return;
+    };
+    let Some(backup) = labels
+        .iter()
+        .position(|candidate| *candidate == label)
+        .map(|index| &backups[index])
+    else {
+        return;
+    };
+
+    let diff = backup.load().and_then(|saved| {
+        Ok(restore_diff(
+            &serialize_config(config_path, config)?,
+            &serialize_config(config_path, &saved)?,
+            &backup.label(),
+        ))
+    });
+    match diff {
+        Ok(diff) if diff.is_empty() => {
+            println!("✅  This backup matches the current config.");
+            return;
+        }
+        Ok(diff) => println!("{diff}"),
+        Err(e) => {
+            println!("❌  Unable to read the backup: {e:#}");
+            return;
+        }
+    }
+
+    let confirmed = prompt_or_return(|| {
+        inquire::Confirm::new("Restore this backup?")
+            .with_default(false)
+            .prompt()
+    });
+    if confirmed != Some(true) {
+        return;
+    }
+    match restore_backup(config_path, config, backup, changes_made) {
+        Ok(()) => println!(
+            "✅  Restored the backup from {}. Save changes when you return to the Main Menu to keep it.",
+            backup.label()
+        ),
+        Err(e) => println!("❌  Unable to restore the backup: {e:#}"),
+    }
+}
+
+/// Replaces the edited config with a backup, keeping the layers included by `config_path`.
+///
+/// # Errors
+///
+/// Returns an error when the backup or one of the includes cannot be loaded.
+pub fn restore_backup(
+    config_path: &Path,
+    config: &mut Config,
+    backup: &Backup,
+    changes_made: &mut bool,
+) -> anyhow::Result<()> {
+    let mut restored = backup.load()?;
+    // Relative paths and includes resolve against the config file, not the backup folder
+    restored.source_path = Some(config_path.to_path_buf());
+    crate::layers::merge_includes(&mut restored, config_path)?;
+    *config = restored;
+    *changes_made = true;
+    Ok(())
+}
+
 pub fn add_command_to_config(
     config: &mut Config,
+    group: &[usize],
     display_name: String,
     command: String,
     changes_made: &mut bool,
-) {
-    config.commands.push(CommandOption {
+) -> bool {
+    let existing_ids = config.command_ids();
+    let Some((commands, _)) = config.level_mut(group) else {
+        return false;
+    };
+    commands.push(CommandOption {
+        id: generate_command_id(&display_name, &existing_ids),
         display_name,
         command,
+        ..Default::default()
     });
     *changes_made = true;
+    true
 }
 
 pub fn edit_command_at(
     config: &mut Config,
+    group: &[usize],
     index: usize,
     display_name: String,
     command: String,
     changes_made: &mut bool,
 ) -> bool {
-    let Some(existing) = config.commands.get_mut(index) else {
+    let Some(existing) = config
+        .level_mut(group)
+        .and_then(|(commands, _)| commands.get_mut(index))
+    else {
         return false;
     };
 
-    let updated = CommandOption {
-        display_name,
-        command,
-    };
-    if *existing != updated {
-        *existing = updated;
+    if existing.display_name != display_name || existing.command != command {
+        // Update the fields in place so the command keeps its stable ID
+        existing.display_name = display_name;
+        existing.command = command;
         *changes_made = true;
     }
     true
@@ -377,11 +1134,15 @@ pub fn edit_command_at(
 
 pub fn reorder_command_to_position(
     config: &mut Config,
+    group: &[usize],
     index: usize,
     new_position: usize,
     changes_made: &mut bool,
 ) -> bool {
-    if index >= config.commands.len() || new_position == 0 || new_position > config.commands.len() {
+    let Some((commands, _)) = config.level_mut(group) else {
+        return false;
+    };
+    if index >= commands.len() || new_position == 0 || new_position > commands.len() {
         return false;
     }
 
@@ -390,31 +1151,172 @@ pub fn reorder_command_to_position(
         return true;
     }
 
-    let command_to_move = config.commands.remove(index);
-    config.commands.insert(new_index, command_to_move);
+    let command_to_move = commands.remove(index);
+    commands.insert(new_index, command_to_move);
     *changes_made = true;
     true
 }
 
 pub fn delete_command_at(
     config: &mut Config,
+    group: &[usize],
     index: usize,
     changes_made: &mut bool,
 ) -> Option<CommandOption> {
-    if index >= config.commands.len() {
+    let (commands, _) = config.level_mut(group)?;
+    if index >= commands.len() {
         return None;
     }
 
     *changes_made = true;
-    Some(config.commands.remove(index))
+    Some(commands.remove(index))
 }
 
+/// Adds an empty group to the level at `parent`.
+///
+/// # Errors
+///
+/// Returns a user-facing message when the name is empty, contains '/' or is already used.
+pub fn add_group_to_config(
+    config: &mut Config,
+    parent: &[usize],
+    name: &str,
+    changes_made: &mut bool,
+) -> Result<(), String> {
+    let name = name.trim();
+    let Some((_, groups)) = config.level_mut(parent) else {
+        return Err("The parent group no longer exists.".to_string());
+    };
+    check_group_name(groups, name, None)?;
+    groups.push(CommandGroup::new(name));
+    *changes_made = true;
+    Ok(())
+}
+
+/// Renames the group at `path`, keeping its commands and nested groups.
+///
+/// # Errors
+///
+/// Returns a user-facing message when the group does not exist or the name cannot be used.
+pub fn rename_group_at(
+    config: &mut Config,
+    path: &[usize],
+    name: &str,
+    changes_made: &mut bool,
+) -> Result<(), String> {
+    let name = name.trim();
+    let Some((&index, parent)) = path.split_last() else {
+        return Err("The top level cannot be renamed.".to_string());
+    };
+    let Some((_, groups)) = config.level_mut(parent) else {
+        return Err("The group no longer exists.".to_string());
+    };
+    check_group_name(groups, name, Some(index))?;
+    let Some(group) = groups.get_mut(index) else {
+        return Err("The group no longer exists.".to_string());
+    };
+    if group.name != name {
+        group.name = name.to_string();
+        *changes_made = true;
+    }
+    Ok(())
+}
+
+pub fn delete_group_at(
+    config: &mut Config,
+    path: &[usize],
+    changes_made: &mut bool,
+) -> Option<CommandGroup> {
+    let (&index, parent) = path.split_last()?;
+    let (_, groups) = config.level_mut(parent)?;
+    if index >= groups.len() {
+        return None;
+    }
+
+    *changes_made = true;
+    Some(groups.remove(index))
+}
+
+/// Turns a command from an included layer into one of the config's own commands.
+///
+/// The copy keeps its ID, so it overrides the layer's command the next time the config is loaded.
+pub fn copy_command_to_own_layer(
+    config: &mut Config,
+    group: &[usize],
+    index: usize,
+    changes_made: &mut bool,
+) -> bool {
+    let Some(command) = config
+        .level_mut(group)
+        .and_then(|(commands, _)| commands.get_mut(index))
+    else {
+        return false;
+    };
+    if command.layer.take().is_some() {
+        *changes_made = true;
+    }
+    true
+}
+
+/// Moves a command from the level at `from` to the end of the level at `to`.
+pub fn move_command_to_group(
+    config: &mut Config,
+    from: &[usize],
+    index: usize,
+    to: &[usize],
+    changes_made: &mut bool,
+) -> bool {
+    if from == to {
+        return config
+            .level(from)
+            .is_some_and(|(commands, _)| index < commands.len());
+    }
+    if config.level(to).is_none() {
+        return false;
+    }
+    let Some((commands, _)) = config.level_mut(from) else {
+        return false;
+    };
+    if index >= commands.len() {
+        return false;
+    }
+
+    // Removing a command never shifts group indexes, so `to` stays valid
+    let command = commands.remove(index);
+    if let Some((commands, _)) = config.level_mut(to) {
+        commands.push(command);
+    }
+    *changes_made = true;
+    true
+}
+
+fn check_group_name(
+    groups: &[CommandGroup],
+    name: &str,
+    skip: Option<usize>,
+) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Group name cannot be empty.".to_string());
+    }
+    if name.contains('/') {
+        return Err("Group name cannot contain '/'.".to_string());
+    }
+    let taken = groups
+        .iter()
+        .enumerate()
+        .any(|(index, group)| Some(index) != skip && group.name.eq_ignore_ascii_case(name));
+    if taken {
+        return Err(format!("A group named '{name}' already exists here."));
+    }
+    Ok(())
+}
+
+// Commands from included layers are read-only, so they survive a reset
 pub fn clear_commands(config: &mut Config, changes_made: &mut bool) -> bool {
-    if config.commands.is_empty() {
+    if !clear_own_entries(config) {
         return false;
     }
 
-    config.commands.clear();
     *changes_made = true;
     true
 }
@@ -439,8 +1341,11 @@ pub fn print_commands(commands: &[CommandOption]) {
         for (i, option) in commands.iter().enumerate() {
             table.add_row(Row::new(vec![
                 Cell::new(&(i + 1).to_string()),
-                Cell::new(&fill(&option.display_name, terminal_width / 3)),
-                Cell::new(&fill(&option.command, terminal_width / 3 * 2)),
+                Cell::new(&fill(
+                    &(option.display_name.clone() + &layer_suffix(option.layer.as_deref())),
+                    terminal_width / 3,
+                )),
+                Cell::new(&fill(&option.command_text(), terminal_width / 3 * 2)),
             ]));
         }
 
@@ -448,6 +1353,17 @@ pub fn print_commands(commands: &[CommandOption]) {
     }
 }
 
+pub fn print_groups(groups: &[CommandGroup]) {
+    for group in groups {
+        println!(
+            "📁 {} ({} commands){}",
+            group.name,
+            group.command_count(),
+            layer_suffix(group.layer.as_deref())
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -457,8 +1373,10 @@ mod tests {
             commands: names
                 .iter()
                 .map(|name| CommandOption {
+                    id: format!("id-{name}"),
                     display_name: (*name).into(),
                     command: format!("echo {name}"),
+                    ..Default::default()
                 })
                 .collect(),
             ..Default::default()
@@ -470,10 +1388,11 @@ mod tests {
         let mut config = Config::default();
         let mut changed = false;
 
-        add_command_to_config(&mut config, "List".into(), "ls".into(), &mut changed);
+        add_command_to_config(&mut config, &[], "List".into(), "ls".into(), &mut changed);
 
         assert_eq!(config.commands.len(), 1);
         assert_eq!(config.commands[0].display_name, "List");
+        assert!(!config.commands[0].id.is_empty());
         assert!(changed);
     }
 
@@ -482,11 +1401,19 @@ mod tests {
         let mut config = config_with_commands(&["Old"]);
         let mut changed = false;
 
-        let edited = edit_command_at(&mut config, 0, "New".into(), "date".into(), &mut changed);
+        let edited = edit_command_at(
+            &mut config,
+            &[],
+            0,
+            "New".into(),
+            "date".into(),
+            &mut changed,
+        );
 
         assert!(edited);
         assert_eq!(config.commands[0].display_name, "New");
         assert_eq!(config.commands[0].command, "date");
+        assert_eq!(config.commands[0].id, "id-Old");
         assert!(changed);
     }
 
@@ -497,6 +1424,7 @@ mod tests {
 
         let edited = edit_command_at(
             &mut config,
+            &[],
             0,
             "Same".into(),
             "echo Same".into(),
@@ -512,7 +1440,7 @@ mod tests {
         let mut config = config_with_commands(&["A", "B", "C"]);
         let mut changed = false;
 
-        let moved = reorder_command_to_position(&mut config, 0, 3, &mut changed);
+        let moved = reorder_command_to_position(&mut config, &[], 0, 3, &mut changed);
 
         assert!(moved);
         assert_eq!(
@@ -526,12 +1454,23 @@ mod tests {
         assert!(changed);
     }
 
+    #[test]
+    fn reorder_command_to_position_keeps_stable_ids() {
+        let mut config = config_with_commands(&["A", "B", "C"]);
+        let mut changed = false;
+
+        reorder_command_to_position(&mut config, &[], 2, 1, &mut changed);
+
+        assert_eq!(config.commands[0].id, "id-C");
+        assert_eq!(config.commands[1].id, "id-A");
+    }
+
     #[test]
     fn reorder_command_to_same_position_stays_clean() {
         let mut config = config_with_commands(&["A", "B"]);
         let mut changed = false;
 
-        let moved = reorder_command_to_position(&mut config, 1, 2, &mut changed);
+        let moved = reorder_command_to_position(&mut config, &[], 1, 2, &mut changed);
 
         assert!(moved);
         assert_eq!(config.commands[1].display_name, "B");
@@ -544,25 +1483,152 @@ mod tests {
         let original = config.clone();
         let mut changed = false;
 
-        let moved = reorder_command_to_position(&mut config, 0, 3, &mut changed);
+        let moved = reorder_command_to_position(&mut config, &[], 0, 3, &mut changed);
 
         assert!(!moved);
         assert_eq!(config, original);
         assert!(!changed);
     }
 
+    #[test]
+    fn restore_backup_brings_back_cleared_commands() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let config_path = dir.path().join("menu.json");
+        let backups = dir.path().join("backups");
+        let mut config = config_with_commands(&["A", "B"]);
+        std::fs::write(
+            &config_path,
+            serialize_config(&config_path, &config).expect("serialized"),
+        )
+        .expect("config written");
+        crate::backups::snapshot_config(&backups, &config_path).expect("snapshot");
+        let mut changed = false;
+        clear_commands(&mut config, &mut changed);
+        changed = false;
+
+        let backup = list_backups(&backups).expect("backups").remove(0);
+        restore_backup(&config_path, &mut config, &backup, &mut changed).expect("restored");
+
+        assert_eq!(config.commands.len(), 2);
+        assert_eq!(config.source_path.as_deref(), Some(config_path.as_path()));
+        assert!(changed);
+    }
+
     #[test]
     fn delete_command_at_removes_existing_command() {
         let mut config = config_with_commands(&["A", "B"]);
         let mut changed = false;
 
-        let deleted = delete_command_at(&mut config, 0, &mut changed).expect("deleted command");
+        let deleted =
+            delete_command_at(&mut config, &[], 0, &mut changed).expect("deleted command");
 
         assert_eq!(deleted.display_name, "A");
         assert_eq!(config.commands[0].display_name, "B");
         assert!(changed);
     }
 
+    #[test]
+    fn add_group_to_config_rejects_duplicate_and_invalid_names() {
+        let mut config = Config::default();
+        let mut changed = false;
+
+        add_group_to_config(&mut config, &[], " Deploy ", &mut changed).expect("group added");
+        add_group_to_config(&mut config, &[0], "Web", &mut changed).expect("nested group added");
+
+        assert_eq!(config.groups[0].name, "Deploy");
+        assert_eq!(config.groups[0].groups[0].name, "Web");
+        assert!(add_group_to_config(&mut config, &[], "deploy", &mut changed).is_err());
+        assert!(add_group_to_config(&mut config, &[], "a/b", &mut changed).is_err());
+        assert!(add_group_to_config(&mut config, &[5], "Missing", &mut changed).is_err());
+        assert!(changed);
+    }
+
+    #[test]
+    fn rename_group_at_keeps_commands() {
+        let mut config = config_with_commands(&[]);
+        config.groups = vec![CommandGroup::new("Old"), CommandGroup::new("Other")];
+        config.groups[0].commands = config_with_commands(&["A"]).commands;
+        let mut changed = false;
+
+        assert!(rename_group_at(&mut config, &[0], "other", &mut changed).is_err());
+        rename_group_at(&mut config, &[0], "New", &mut changed).expect("group renamed");
+
+        assert_eq!(config.groups[0].name, "New");
+        assert_eq!(config.groups[0].commands[0].id, "id-A");
+        assert!(changed);
+    }
+
+    #[test]
+    fn move_command_to_group_keeps_stable_id() {
+        let mut config = config_with_commands(&["A", "B"]);
+        config.groups = vec![CommandGroup::new("Tools")];
+        let mut changed = false;
+
+        assert!(move_command_to_group(
+            &mut config,
+            &[],
+            1,
+            &[0],
+            &mut changed
+        ));
+
+        assert_eq!(config.commands.len(), 1);
+        assert_eq!(config.groups[0].commands[0].id, "id-B");
+        assert!(config.command_by_id("id-B").is_some());
+        assert!(changed);
+    }
+
+    #[test]
+    fn move_command_to_missing_group_does_not_change_config() {
+        let mut config = config_with_commands(&["A"]);
+        let original = config.clone();
+        let mut changed = false;
+
+        assert!(!move_command_to_group(
+            &mut config,
+            &[],
+            0,
+            &[3],
+            &mut changed
+        ));
+
+        assert_eq!(config, original);
+        assert!(!changed);
+    }
+
+    #[test]
+    fn add_command_to_config_adds_to_nested_group() {
+        let mut config = config_with_commands(&["A"]);
+        config.groups = vec![CommandGroup::new("Tools")];
+        let mut changed = false;
+
+        assert!(add_command_to_config(
+            &mut config,
+            &[0],
+            "A".into(),
+            "echo nested".into(),
+            &mut changed
+        ));
+
+        assert_eq!(config.groups[0].commands[0].display_name, "A");
+        assert_eq!(config.commands.len(), 1);
+        assert!(changed);
+    }
+
+    #[test]
+    fn delete_group_at_removes_nested_commands() {
+        let mut config = config_with_commands(&[]);
+        config.groups = vec![CommandGroup::new("Tools")];
+        config.groups[0].commands = config_with_commands(&["A"]).commands;
+        let mut changed = false;
+
+        let deleted = delete_group_at(&mut config, &[0], &mut changed).expect("deleted group");
+
+        assert_eq!(deleted.command_count(), 1);
+        assert!(config.groups.is_empty());
+        assert!(changed);
+    }
+
     #[test]
     fn clear_commands_empty_config_stays_clean() {
         let mut config = Config::default();
@@ -574,6 +1640,32 @@ mod tests {
         assert!(!changed);
     }
 
+    #[test]
+    fn clear_commands_keeps_commands_from_layers() {
+        let mut config = config_with_commands(&["Own", "Shared"]);
+        config.commands[1].layer = Some(PathBuf::from("/team/catalog.json"));
+        let mut changed = false;
+
+        assert!(clear_commands(&mut config, &mut changed));
+
+        assert!(changed);
+        assert_eq!(config.commands.len(), 1);
+        assert_eq!(config.commands[0].display_name, "Shared");
+    }
+
+    #[test]
+    fn copy_command_to_own_layer_keeps_id() {
+        let mut config = config_with_commands(&["Shared"]);
+        config.commands[0].layer = Some(PathBuf::from("/team/catalog.json"));
+        let mut changed = false;
+
+        assert!(copy_command_to_own_layer(&mut config, &[], 0, &mut changed));
+
+        assert!(changed);
+        assert_eq!(config.commands[0].layer, None);
+        assert_eq!(config.commands[0].id, "id-Shared");
+    }
+
     #[test]
     fn discard_edit_session_restores_original_config_and_clears_dirty_flag() {
         let original = config_with_commands(&["Original"]);
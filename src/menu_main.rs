@@ -1,25 +1,41 @@
 use crate::{
-    config::CommandOption,
-    utils::{get_version, play_sound, run_command},
+    config::{CommandGroup, CommandOption, Config, PausePolicy},
+    history::{self, HistoryEntry, format_duration, run_and_record},
+    layers::layer_suffix,
+    placeholders::fill_placeholders,
+    state::{self, CommandStatus, CompletionState},
+    trust,
+    utils::{CommandRequest, get_version, pause, play_sound},
 };
 use inquire::Select;
 use std::{
+    collections::BTreeMap,
     io::{Write, stdout},
     path::Path,
-    process::exit,
+    process::{ExitStatus, exit},
 };
 use termion::{clear, cursor, terminal_size};
 
-use crate::menu_edit::edit_menu;
+use crate::menu_edit::{edit_menu, offer_recovery};
 
 use inquire::error::InquireError;
 
+const HISTORY_MENU_LABEL: &str = "h. HISTORY";
 const EDIT_MENU_LABEL: &str = "e. EDIT Commands";
 const EXIT_LABEL: &str = "q. EXIT";
+const SUCCEEDED_MARKER: &str = "✅";
+const FAILED_MARKER: &str = "❌";
+const NOT_RUN_MARKER: &str = "⬜";
+const GROUP_MARKER: &str = "📁";
+const GROUP_BACK_LABEL: &str = "b. BACK to previous menu";
+const HISTORY_BACK_LABEL: &str = "b. BACK to Main Menu";
+const HISTORY_LIMIT: usize = 50;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum MainMenuChoice {
     Command(usize),
+    Back,
+    History,
     Edit,
     Quit,
 }
@@ -41,17 +57,51 @@ pub fn prompt_or_return<T>(prompt: impl FnOnce() -> Result<T, InquireError>) ->
 /// Displays the main interactive command menu.
 ///
 pub async fn display_menu(config_path: &Path) {
-    let mut selected_commands: Vec<usize> = vec![];
-    let mut last_selected: Option<usize> = None;
+    let state_path = state::state_file_path(config_path)
+        .inspect_err(|e| eprintln!("⚠️  Today's progress will not be kept: {e}"))
+        .ok();
+    let mut completion = state_path
+        .as_deref()
+        .map_or_else(|| Ok(CompletionState::default()), state::load_state)
+        .unwrap_or_else(|e| {
+            eprintln!("⚠️  Could not restore today's progress: {e}");
+            CompletionState::default()
+        });
+    let mut last_selected: Option<String> = None;
+    let mut current_group: Vec<usize> = Vec::new();
+    offer_recovery(config_path);
 
     loop {
+        // The file may have changed since it was approved, such as after a `git pull` elsewhere;
+        // it is checked before loading, so the commands shown are the ones just approved
+        let trusted = trust::ensure_trusted(config_path, true);
         let Ok(config) = crate::config::load_config(config_path) else {
             println!("⚠️ Config does not exist or is invalid; editing new config");
             edit_menu(config_path);
-            selected_commands.clear();
-            last_selected = None;
             continue;
         };
+        match trusted {
+            Ok(true) => {}
+            Ok(false) => exit(1),
+            Err(e) => {
+                eprintln!("❌  {e:#}");
+                exit(1);
+            }
+        }
+
+        let reset_time =
+            state::parse_reset_time(config.daily_reset_time.as_deref()).unwrap_or_default();
+        if completion.roll_over(state::current_completion_day(reset_time)) {
+            persist_completion(state_path.as_deref(), &completion);
+        }
+
+        // An edit may have removed or reordered the open group
+        let (commands, groups) = if let Some(level) = config.level(&current_group) {
+            level
+        } else {
+            current_group.clear();
+            (&config.commands, &config.groups)
+        };
 
         let term_height = get_terminal_height() as usize;
         let display_height = term_height.saturating_sub(3);
@@ -63,24 +113,32 @@ pub async fn display_menu(config_path: &Path) {
         }
 
         clear_screen();
-        let mut menu_options = generate_menu(&config.commands, &selected_commands);
+        let mut menu_options = generate_level_menu(commands, groups, &completion.statuses);
 
+        if !current_group.is_empty() {
+            menu_options.push(GROUP_BACK_LABEL.to_string());
+        }
+        menu_options.push(HISTORY_MENU_LABEL.to_string());
         menu_options.push(EDIT_MENU_LABEL.to_string());
         menu_options.push(EXIT_LABEL.to_string());
 
-        let menu_prompt = if let Some(last) = last_selected {
-            Select::new(
-                "Welcome to the CLI Command Shortcut Menu! Select a command to execute:",
-                menu_options,
-            )
-            .with_starting_cursor(last)
-            .with_page_size(display_height)
+        let last_index = last_selected
+            .as_deref()
+            .and_then(|id| commands.iter().position(|command| command.id == id));
+        let message = if current_group.is_empty() {
+            "Welcome to the CLI Command Shortcut Menu! Select a command to execute:".to_string()
         } else {
-            Select::new(
-                "Welcome to the CLI Command Shortcut Menu! Select a command to execute:",
-                menu_options,
+            format!(
+                "{} - select a command to execute:",
+                config.group_names(&current_group).join(" / ")
             )
-            .with_page_size(display_height)
+        };
+        let menu_prompt = if let Some(last) = last_index {
+            Select::new(&message, menu_options)
+                .with_starting_cursor(last)
+                .with_page_size(display_height)
+        } else {
+            Select::new(&message, menu_options).with_page_size(display_height)
         };
 
         match menu_prompt.prompt() {
@@ -89,28 +147,76 @@ pub async fn display_menu(config_path: &Path) {
                     println!("Exiting CLI Menu v{}...", get_version());
                     exit(0);
                 }
+                Some(MainMenuChoice::Back) => {
+                    current_group.pop();
+                }
+                Some(MainMenuChoice::History) => {
+                    let Some(entry) = select_history_entry() else {
+                        continue;
+                    };
+                    // Re-runs still count towards today's progress while the command exists
+                    let source = entry
+                        .command_id
+                        .as_deref()
+                        .and_then(|id| config.command_by_id(id));
+                    announce_command(&config, entry.label());
+                    let result = history_request(&config, &entry, source)
+                        .map_err(anyhow::Error::msg)
+                        .and_then(|request| run_and_record(&request, source));
+                    if let Err(e) = &result {
+                        eprintln!("❌  Failed to run command: {e}");
+                    }
+                    let policy = source.map_or(config.pause_after, |command| {
+                        config.pause_policy_for(command)
+                    });
+                    if let Some(command) = source {
+                        let status = CommandStatus::from_run_result(&result);
+                        if completion.record(&command.id, status) {
+                            persist_completion(state_path.as_deref(), &completion);
+                        }
+                        last_selected = Some(command.id.clone());
+                    }
+                    pause_after_run(policy, &result);
+                }
                 Some(MainMenuChoice::Edit) => {
                     edit_menu(config_path);
-                    selected_commands.clear();
-                    last_selected = None;
                 }
                 Some(MainMenuChoice::Command(num)) => {
                     let Some(index) = num.checked_sub(1) else {
                         println!("❌  Invalid choice, please try again.");
                         continue;
                     };
-                    if let Some(command) = config.commands.get(index) {
-                        if let Some(cmd_sound) = &config.cmd_sound {
-                            tokio::spawn(play_sound(cmd_sound.clone()));
+                    if let Some(index) = index.checked_sub(commands.len()) {
+                        // Groups are numbered after the commands of the current level
+                        if index < groups.len() {
+                            current_group.push(index);
+                        } else {
+                            println!("❌  Invalid choice, please try again.");
                         }
-                        if config.window_title_support {
-                            set_window_title(&choice);
+                    } else if let Some(command) = commands.get(index) {
+                        let mut request = match config.command_request(command) {
+                            Ok(request) => request,
+                            Err(e) => {
+                                eprintln!("❌  Failed to run command: {e}");
+                                print!("Press any key to return to Main Menu...");
+                                pause();
+                                continue;
+                            }
+                        };
+                        if !fill_placeholders(command, &mut request, &BTreeMap::new()) {
+                            continue;
                         }
-                        if let Err(e) = run_command(&command.command) {
+                        announce_command(&config, &choice);
+                        let result = run_and_record(&request, Some(command));
+                        if let Err(e) = &result {
                             eprintln!("❌  Failed to run command: {e}");
                         }
-                        selected_commands.push(num);
-                        last_selected = Some(index);
+                        let status = CommandStatus::from_run_result(&result);
+                        if completion.record(&command.id, status) {
+                            persist_completion(state_path.as_deref(), &completion);
+                        }
+                        last_selected = Some(command.id.clone());
+                        pause_after_run(config.pause_policy_for(command), &result);
                     } else {
                         println!("❌  Invalid choice, please try again.");
                     }
@@ -124,10 +230,159 @@ pub async fn display_menu(config_path: &Path) {
     }
 }
 
+/// Shows the command menu, including groups, and returns the chosen command without running it.
+///
+/// Returns `None` when the user exits or cancels. The prompt is drawn on stderr, so stdout
+/// stays free for the picked command.
+#[must_use]
+pub fn pick_command<'a>(
+    config: &'a Config,
+    statuses: &BTreeMap<String, CommandStatus>,
+) -> Option<&'a CommandOption> {
+    let mut current_group: Vec<usize> = Vec::new();
+    loop {
+        let (commands, groups) = config.level(&current_group)?;
+        let mut menu_options = generate_level_menu(commands, groups, statuses);
+        if !current_group.is_empty() {
+            menu_options.push(GROUP_BACK_LABEL.to_string());
+        }
+        menu_options.push(EXIT_LABEL.to_string());
+        let page_size = (get_terminal_height() as usize).saturating_sub(3);
+
+        let choice = Select::new("Select a command to insert:", menu_options)
+            .with_page_size(page_size)
+            .prompt()
+            .ok()?;
+        match parse_main_menu_choice(&choice)? {
+            MainMenuChoice::Quit => return None,
+            MainMenuChoice::Back => {
+                current_group.pop();
+            }
+            MainMenuChoice::Command(number) => {
+                let index = number.checked_sub(1)?;
+                match index.checked_sub(commands.len()) {
+                    Some(group_index) if group_index < groups.len() => {
+                        current_group.push(group_index);
+                    }
+                    Some(_) => {}
+                    None => return commands.get(index),
+                }
+            }
+            MainMenuChoice::History | MainMenuChoice::Edit => {}
+        }
+    }
+}
+
+// Plays the command sound and updates the window title before a command runs
+fn announce_command(config: &Config, title: &str) {
+    if let Some(cmd_sound) = &config.cmd_sound {
+        tokio::spawn(play_sound(cmd_sound.clone()));
+    }
+    if config.window_title_support {
+        set_window_title(title);
+    }
+}
+
+// Keeps the command output on screen until a key is pressed when the policy asks for it
+fn pause_after_run(policy: PausePolicy, result: &anyhow::Result<ExitStatus>) {
+    let succeeded = result.as_ref().is_ok_and(ExitStatus::success);
+    if policy.should_pause(succeeded) {
+        print!("Press any key to return to Main Menu...");
+        pause();
+    }
+}
+
+// Re-runs the recorded command and directory with the command's current shell and environment
+fn history_request(
+    config: &Config,
+    entry: &HistoryEntry,
+    source: Option<&CommandOption>,
+) -> Result<CommandRequest, String> {
+    let mut request = entry.request();
+    if let Some(command) = source {
+        request.shell = config.shell_for(command).cloned();
+        request.env = config.command_env(command)?;
+    }
+    Ok(request)
+}
+
+// Lists recent runs and returns the one the user wants to run again
+fn select_history_entry() -> Option<HistoryEntry> {
+    let entries = match history::history_file_path()
+        .map_err(anyhow::Error::msg)
+        .and_then(|path| history::load_recent(&path, HISTORY_LIMIT))
+    {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("❌  Could not load run history: {e}");
+            return None;
+        }
+    };
+
+    if entries.is_empty() {
+        print!("No commands have been run yet. Press any key to return to Main Menu...");
+        pause();
+        return None;
+    }
+
+    let mut options = generate_history_menu(&entries);
+    options.push(HISTORY_BACK_LABEL.to_string());
+    let page_size = (get_terminal_height() as usize).saturating_sub(3);
+
+    let choice = prompt_or_return(|| {
+        Select::new("Recent runs - select one to run again:", options)
+            .with_page_size(page_size)
+            .prompt()
+    })?;
+    let index = choice
+        .split('.')
+        .next()?
+        .trim()
+        .parse::<usize>()
+        .ok()?
+        .checked_sub(1)?;
+    entries.into_iter().nth(index)
+}
+
+/// Renders history entries as numbered menu lines with time, outcome and duration.
+#[must_use]
+pub fn generate_history_menu(entries: &[HistoryEntry]) -> Vec<String> {
+    let max_number_width = entries.len().to_string().len();
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let number = index + 1;
+            let outcome = match entry.exit_code {
+                Some(0) => "✅".to_string(),
+                Some(code) => format!("❌ exit {code}"),
+                None => "⚠️ no exit code".to_string(),
+            };
+            format!(
+                "{number: >max_number_width$}. {} {outcome} {} ({})",
+                entry.timestamp.format("%Y-%m-%d %H:%M"),
+                entry.label(),
+                format_duration(entry.duration_ms)
+            )
+        })
+        .collect()
+}
+
+fn persist_completion(state_path: Option<&Path>, completion: &CompletionState) {
+    let Some(state_path) = state_path else {
+        return;
+    };
+    if let Err(e) = state::save_state(state_path, completion) {
+        eprintln!("⚠️  Could not save today's progress: {e}");
+    }
+}
+
 fn parse_main_menu_choice(choice: &str) -> Option<MainMenuChoice> {
     match choice {
         EXIT_LABEL => Some(MainMenuChoice::Quit),
+        HISTORY_MENU_LABEL => Some(MainMenuChoice::History),
         EDIT_MENU_LABEL => Some(MainMenuChoice::Edit),
+        GROUP_BACK_LABEL => Some(MainMenuChoice::Back),
         _ => choice
             .split('.')
             .next()?
@@ -138,20 +393,88 @@ fn parse_main_menu_choice(choice: &str) -> Option<MainMenuChoice> {
     }
 }
 
+/// Renders numbered menu lines with a marker for today's outcome of each command.
+///
+/// Succeeded commands are struck through, failed ones show their exit code, and
+/// commands without an entry in `statuses` have not run yet.
+#[must_use]
+pub fn generate_menu(
+    commands: &[CommandOption],
+    statuses: &BTreeMap<String, CommandStatus>,
+) -> Vec<String> {
+    command_lines(commands, statuses, commands.len().to_string().len())
+}
+
+/// Renders one menu level: its commands followed by its groups, numbered as one list.
+///
+/// Groups show how many of their commands, including nested ones, succeeded today.
 #[must_use]
-pub fn generate_menu(commands: &[CommandOption], selected_commands: &[usize]) -> Vec<String> {
-    let max_number_width = commands.len().to_string().len();
+pub fn generate_level_menu(
+    commands: &[CommandOption],
+    groups: &[CommandGroup],
+    statuses: &BTreeMap<String, CommandStatus>,
+) -> Vec<String> {
+    let max_number_width = (commands.len() + groups.len()).to_string().len();
+    let mut lines = command_lines(commands, statuses, max_number_width);
+    lines.extend(groups.iter().enumerate().map(|(index, group)| {
+        let number = commands.len() + index + 1;
+        let succeeded = group_succeeded_count(group, statuses);
+        format!(
+            "{number: >max_number_width$}. {GROUP_MARKER} {} ({succeeded}/{}){}",
+            group.name,
+            group.command_count(),
+            layer_suffix(group.layer.as_deref())
+        )
+    }));
+    lines
+}
+
+fn group_succeeded_count(
+    group: &CommandGroup,
+    statuses: &BTreeMap<String, CommandStatus>,
+) -> usize {
+    group
+        .commands
+        .iter()
+        .filter(|command| matches!(statuses.get(&command.id), Some(CommandStatus::Succeeded)))
+        .count()
+        + group
+            .groups
+            .iter()
+            .map(|group| group_succeeded_count(group, statuses))
+            .sum::<usize>()
+}
+
+fn command_lines(
+    commands: &[CommandOption],
+    statuses: &BTreeMap<String, CommandStatus>,
+    max_number_width: usize,
+) -> Vec<String> {
     commands
         .iter()
         .enumerate()
         .map(|(index, cmd)| {
             let number = index + 1;
             let padded_number = format!("{number: >max_number_width$}");
-            if selected_commands.contains(&number) {
-                format!("{}. {}", padded_number, strike_through(&cmd.display_name))
-            } else {
-                format!("{}. {}", padded_number, &cmd.display_name)
-            }
+            let line = match statuses.get(&cmd.id) {
+                Some(CommandStatus::Succeeded) => format!(
+                    "{}. {SUCCEEDED_MARKER} {}",
+                    padded_number,
+                    strike_through(&cmd.display_name)
+                ),
+                Some(CommandStatus::Failed {
+                    exit_code: Some(code),
+                }) => format!(
+                    "{}. {FAILED_MARKER} {} (exit {code})",
+                    padded_number, &cmd.display_name
+                ),
+                Some(CommandStatus::Failed { exit_code: None }) => format!(
+                    "{}. {FAILED_MARKER} {} (did not finish)",
+                    padded_number, &cmd.display_name
+                ),
+                None => format!("{}. {NOT_RUN_MARKER} {}", padded_number, &cmd.display_name),
+            };
+            line + &layer_suffix(cmd.layer.as_deref())
         })
         .collect()
 }
@@ -0,0 +1,524 @@
+//This file contains the three-way merge used when a config changes on disk during an edit session.
+//The changes made in the session, compared with the config it loaded, are replayed onto the file as
+//it is now: command by command through their stable IDs, and setting by setting. A change both sides
+//made differently becomes a conflict that keeps the file's version until it is resolved.
+
+use crate::config::{CommandOption, Config};
+use crate::config_diff::ConfigDiff;
+use crate::layers::own_layer;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+// Fields merged command by command rather than as settings
+const COMMAND_FIELDS: [&str; 3] = ["commands", "groups", "version"];
+
+// Where a command sits in a config: the names of its groups, and the command itself
+type Entry = (Vec<String>, CommandOption);
+
+// Define one change made differently in the session and in the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    Command {
+        id: String,
+        ours: Option<Box<Entry>>, // None when the session deleted the command
+        theirs: Option<Box<Entry>>, // None when the file no longer has it
+    },
+    Setting {
+        key: String,
+        ours: Option<Value>, // None when the setting was removed
+        theirs: Option<Value>,
+    },
+}
+
+impl Conflict {
+    /// Describes the conflict, such as `'Tools/Build' was changed here and in the file`.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Command { ours, theirs, .. } => {
+                let path = ours.as_deref().or(theirs.as_deref()).map(entry_path);
+                let path = path.unwrap_or_default();
+                match (ours, theirs) {
+                    (Some(_), None) => format!("'{path}' was changed here but deleted in the file"),
+                    (None, Some(_)) => format!("'{path}' was deleted here but changed in the file"),
+                    _ => format!("'{path}' was changed here and in the file"),
+                }
+            }
+            Self::Setting { key, .. } => format!("'{key}' was changed here and in the file"),
+        }
+    }
+
+    /// Describes the session's version, as offered when resolving the conflict.
+    #[must_use]
+    pub fn ours_label(&self) -> String {
+        match self {
+            Self::Command { ours, .. } => entry_label(ours.as_deref()),
+            Self::Setting { ours, .. } => value_label(ours.as_ref()),
+        }
+    }
+
+    /// Describes the file's version, as offered when resolving the conflict.
+    #[must_use]
+    pub fn theirs_label(&self) -> String {
+        match self {
+            Self::Command { theirs, .. } => entry_label(theirs.as_deref()),
+            Self::Setting { theirs, .. } => value_label(theirs.as_ref()),
+        }
+    }
+
+    /// Replaces the file's version in a merged config with the session's version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the setting cannot be applied to the config.
+    pub fn keep_ours(&self, config: &mut Config) -> anyhow::Result<()> {
+        match self {
+            Self::Command { id, ours, .. } => {
+                apply_entry(config, id, ours.as_deref());
+                Ok(())
+            }
+            Self::Setting { key, ours, .. } => {
+                let mut settings = serde_json::to_value(&*config)?;
+                if let Value::Object(settings) = &mut settings {
+                    apply_setting(settings, key, ours.as_ref());
+                }
+                let mut updated: Config = serde_json::from_value(settings)?;
+                updated.source_path = config.source_path.take();
+                updated.migrated_from = config.migrated_from;
+                *config = updated;
+                Ok(())
+            }
+        }
+    }
+}
+
+// Define the merged config and the conflicts left to resolve
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    pub config: Config, // The file's own entries with the session's changes; conflicts keep the file's version
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merges the changes from `base` to `ours` into `theirs`.
+///
+/// `base` is the config as the session loaded it, `ours` as the session left it, and `theirs`
+/// as the file is now. Only each config's own entries are merged; included layers are left out.
+/// Reordering a menu level in the session puts the same commands in the session's order.
+#[must_use]
+pub fn three_way_merge(base: &Config, ours: &Config, theirs: &Config) -> MergeResult {
+    let base = own_layer(base);
+    let ours = own_layer(ours);
+    let theirs = own_layer(theirs);
+    let mut conflicts = Vec::new();
+
+    let mut config = merge_settings(&base, &ours, &theirs, &mut conflicts);
+    config.source_path.clone_from(&theirs.source_path);
+    config.migrated_from = theirs.migrated_from;
+
+    let diff = ConfigDiff::between(&base, &ours);
+    for (from, to) in &diff.groups_renamed {
+        rename_group(&mut config, from, to);
+    }
+    for name in &diff.groups_added {
+        config.ensure_group_path(name);
+    }
+
+    // Commands keep the group they had when the session renamed it, so only real changes count
+    let renamed = |groups: &[String]| -> Vec<String> {
+        let path = diff
+            .groups_renamed
+            .iter()
+            .fold(groups.join("/"), |path, (from, to)| {
+                rename_path(&path, from, to)
+            });
+        split_path(&path)
+    };
+    let base_entries: BTreeMap<String, Entry> = entries(&base)
+        .into_iter()
+        .map(|(id, (groups, command))| (id, (renamed(&groups), command)))
+        .collect();
+    let our_entries = entries(&ours);
+    let their_entries = entries(&theirs);
+
+    let mut ids: Vec<&String> = our_entries.keys().collect();
+    ids.extend(
+        base_entries
+            .keys()
+            .filter(|id| !our_entries.contains_key(*id)),
+    );
+    for id in ids {
+        let base_entry = base_entries.get(id);
+        let our_entry = our_entries.get(id);
+        let their_entry = their_entries.get(id);
+        if our_entry == base_entry || our_entry == their_entry {
+            continue;
+        }
+        if their_entry == base_entry {
+            apply_entry(&mut config, id, our_entry);
+        } else {
+            conflicts.push(Conflict::Command {
+                id: id.clone(),
+                ours: our_entry.cloned().map(Box::new),
+                theirs: their_entry.cloned().map(Box::new),
+            });
+        }
+    }
+
+    for path in ours.group_paths() {
+        merge_order(&base, &ours, &path, &mut config);
+    }
+
+    // Deepest first, so a parent is empty once its removed children are gone
+    let mut removed = diff.groups_removed.clone();
+    removed.sort_by_key(|name| std::cmp::Reverse(name.matches('/').count()));
+    for name in &removed {
+        remove_empty_group(&mut config, name);
+    }
+
+    MergeResult { config, conflicts }
+}
+
+// Applies the session's changes to the settings, that is everything but the commands and groups
+fn merge_settings(
+    base: &Config,
+    ours: &Config,
+    theirs: &Config,
+    conflicts: &mut Vec<Conflict>,
+) -> Config {
+    let to_object = |config: &Config| match serde_json::to_value(config) {
+        Ok(Value::Object(object)) => object,
+        _ => serde_json::Map::new(),
+    };
+    let base_settings = to_object(base);
+    let our_settings = to_object(ours);
+    let mut merged = to_object(theirs);
+    let their_settings = merged.clone();
+
+    let keys: HashSet<&String> = base_settings.keys().chain(our_settings.keys()).collect();
+    let mut keys: Vec<&String> = keys
+        .into_iter()
+        .filter(|key| !COMMAND_FIELDS.contains(&key.as_str()))
+        .collect();
+    keys.sort();
+    for key in keys {
+        let base_value = base_settings.get(key);
+        let our_value = our_settings.get(key);
+        let their_value = their_settings.get(key);
+        if our_value == base_value || our_value == their_value {
+            continue;
+        }
+        if their_value == base_value {
+            apply_setting(&mut merged, key, our_value);
+        } else {
+            conflicts.push(Conflict::Setting {
+                key: key.clone(),
+                ours: our_value.cloned(),
+                theirs: their_value.cloned(),
+            });
+        }
+    }
+
+    // The settings came from valid configs, so they still form one
+    serde_json::from_value(Value::Object(merged)).unwrap_or_else(|_| theirs.clone())
+}
+
+fn apply_setting(settings: &mut serde_json::Map<String, Value>, key: &str, value: Option<&Value>) {
+    match value {
+        Some(value) => {
+            settings.insert(key.to_string(), value.clone());
+        }
+        None => {
+            settings.remove(key);
+        }
+    }
+}
+
+// Puts the command with `id` where `entry` says, or removes it when `entry` is None
+fn apply_entry(config: &mut Config, id: &str, entry: Option<&Entry>) {
+    let location = config
+        .all_commands()
+        .into_iter()
+        .find(|location| location.command.id == id)
+        .map(|location| (location.group, location.index));
+
+    if let (Some((path, index)), Some((groups, command))) = (&location, entry)
+        && config.group_names(path) == *groups
+    {
+        // Still in the same group, so the command keeps its place
+        if let Some((commands, _)) = config.level_mut(path) {
+            commands[*index] = command.clone();
+        }
+        return;
+    }
+    if let Some((path, index)) = location
+        && let Some((commands, _)) = config.level_mut(&path)
+    {
+        commands.remove(index);
+    }
+    if let Some((groups, command)) = entry {
+        let path = config.ensure_group_path(&groups.join("/"));
+        if let Some((commands, _)) = config.level_mut(&path) {
+            commands.push(command.clone());
+        }
+    }
+}
+
+// Sorts the commands the session has at `path` into the session's order, keeping the slots they fill
+fn merge_order(base: &Config, ours: &Config, path: &[usize], config: &mut Config) {
+    let names: Vec<String> = ours
+        .group_names(path)
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let Some((our_commands, _)) = ours.level(path) else {
+        return;
+    };
+    let our_order: Vec<&str> = our_commands
+        .iter()
+        .map(|command| command.id.as_str())
+        .collect();
+    let base_order: Vec<&str> = find_group(base, &names)
+        .and_then(|path| base.level(&path))
+        .map(|(commands, _)| commands.iter().map(|command| command.id.as_str()).collect())
+        .unwrap_or_default();
+    let shared = |order: &[&str], other: &[&str]| -> Vec<String> {
+        order
+            .iter()
+            .filter(|id| other.contains(id))
+            .map(ToString::to_string)
+            .collect()
+    };
+    if shared(&our_order, &base_order) == shared(&base_order, &our_order) {
+        return;
+    }
+
+    let Some(path) = find_group(config, &names) else {
+        return;
+    };
+    let Some((commands, _)) = config.level_mut(&path) else {
+        return;
+    };
+    let slots: Vec<usize> = (0..commands.len())
+        .filter(|&index| our_order.contains(&commands[index].id.as_str()))
+        .collect();
+    let mut sorted: Vec<CommandOption> =
+        slots.iter().map(|&index| commands[index].clone()).collect();
+    sorted.sort_by_key(|command| our_order.iter().position(|id| *id == command.id));
+    for (slot, command) in slots.into_iter().zip(sorted) {
+        commands[slot] = command;
+    }
+}
+
+fn rename_group(config: &mut Config, from: &str, to: &str) {
+    let from = split_path(from);
+    let to = split_path(to);
+    let Some((new_name, parent)) = to.split_last() else {
+        return;
+    };
+    // A group that also changed parents is moved by its commands instead
+    if from.len() != to.len()
+        || from[..parent.len()] != *parent
+        || find_group(config, &to).is_some()
+    {
+        return;
+    }
+    if let Some(path) = find_group(config, &from)
+        && let Some((index, parent_path)) = path.split_last()
+        && let Some((_, groups)) = config.level_mut(parent_path)
+    {
+        groups[*index].name.clone_from(new_name);
+    }
+}
+
+fn remove_empty_group(config: &mut Config, name: &str) {
+    let Some(path) = find_group(config, &split_path(name)) else {
+        return;
+    };
+    let Some((index, parent)) = path.split_last() else {
+        return;
+    };
+    if let Some((_, groups)) = config.level_mut(parent)
+        && groups[*index].commands.is_empty()
+        && groups[*index].groups.is_empty()
+    {
+        groups.remove(*index);
+    }
+}
+
+// Returns the group indexes for the group with exactly these names
+fn find_group(config: &Config, names: &[String]) -> Option<Vec<usize>> {
+    config
+        .group_paths()
+        .into_iter()
+        .find(|path| config.group_names(path) == names)
+}
+
+fn entries(config: &Config) -> BTreeMap<String, Entry> {
+    config
+        .all_commands()
+        .into_iter()
+        .map(|location| {
+            let groups = location.groups.iter().map(ToString::to_string).collect();
+            (
+                location.command.id.clone(),
+                (groups, location.command.clone()),
+            )
+        })
+        .collect()
+}
+
+fn entry_path((groups, command): &Entry) -> String {
+    groups
+        .iter()
+        .map(String::as_str)
+        .chain([command.display_name.as_str()])
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn entry_label(entry: Option<&Entry>) -> String {
+    entry.map_or_else(
+        || "deleted".to_string(),
+        |entry| format!("'{}' runs {}", entry_path(entry), entry.1.command_text()),
+    )
+}
+
+fn value_label(value: Option<&Value>) -> String {
+    value.map_or_else(|| "not set".to_string(), Value::to_string)
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// Replaces the `from` group at the start of `path` with `to`
+fn rename_path(path: &str, from: &str, to: &str) -> String {
+    if path == from {
+        to.to_string()
+    } else if let Some(rest) = path.strip_prefix(&format!("{from}/")) {
+        format!("{to}/{rest}")
+    } else {
+        path.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandGroup;
+
+    fn command(id: &str, name: &str) -> CommandOption {
+        CommandOption {
+            id: id.to_string(),
+            display_name: name.to_string(),
+            command: format!("echo {name}"),
+            ..CommandOption::default()
+        }
+    }
+
+    fn sample() -> Config {
+        Config {
+            commands: vec![command("a", "A"), command("b", "B")],
+            groups: vec![CommandGroup {
+                name: "Tools".to_string(),
+                commands: vec![command("c", "C")],
+                ..CommandGroup::default()
+            }],
+            ..Config::default()
+        }
+    }
+
+    fn ids(commands: &[CommandOption]) -> Vec<&str> {
+        commands.iter().map(|command| command.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_changes_to_different_commands_are_combined() {
+        let base = sample();
+        let mut ours = base.clone();
+        ours.commands[0].command = "echo ours".to_string();
+        ours.groups[0].commands.push(command("d", "D"));
+        let mut theirs = base.clone();
+        theirs.commands.remove(1);
+        theirs.window_title_support = true;
+
+        let merge = three_way_merge(&base, &ours, &theirs);
+
+        assert_eq!(merge.conflicts, []);
+        assert_eq!(ids(&merge.config.commands), ["a"]);
+        assert_eq!(merge.config.commands[0].command, "echo ours");
+        assert_eq!(ids(&merge.config.groups[0].commands), ["c", "d"]);
+        assert!(merge.config.window_title_support);
+    }
+
+    #[test]
+    fn test_same_command_changed_both_ways_is_a_conflict() {
+        let base = sample();
+        let mut ours = base.clone();
+        ours.commands[1].command = "echo ours".to_string();
+        ours.window_title = Some("Ours".to_string());
+        let mut theirs = base.clone();
+        theirs.commands[1].command = "echo theirs".to_string();
+        theirs.window_title = Some("Theirs".to_string());
+
+        let mut merge = three_way_merge(&base, &ours, &theirs);
+
+        assert_eq!(merge.config.commands[1].command, "echo theirs");
+        let descriptions: Vec<String> = merge.conflicts.iter().map(Conflict::describe).collect();
+        assert_eq!(
+            descriptions,
+            [
+                "'windowTitle' was changed here and in the file",
+                "'B' was changed here and in the file"
+            ]
+        );
+        assert_eq!(merge.conflicts[1].ours_label(), "'B' runs echo ours");
+        assert_eq!(merge.conflicts[0].theirs_label(), "\"Theirs\"");
+
+        for conflict in merge.conflicts.clone() {
+            conflict.keep_ours(&mut merge.config).unwrap();
+        }
+        assert_eq!(merge.config.commands[1].command, "echo ours");
+        assert_eq!(merge.config.window_title.as_deref(), Some("Ours"));
+    }
+
+    #[test]
+    fn test_deleting_a_command_the_file_changed_is_a_conflict() {
+        let base = sample();
+        let mut ours = base.clone();
+        ours.groups[0].commands.clear();
+        let mut theirs = base.clone();
+        theirs.groups[0].commands[0].command = "make".to_string();
+
+        let merge = three_way_merge(&base, &ours, &theirs);
+
+        assert_eq!(
+            merge.conflicts[0].describe(),
+            "'Tools/C' was deleted here but changed in the file"
+        );
+        assert_eq!(merge.conflicts[0].ours_label(), "deleted");
+        assert_eq!(ids(&merge.config.groups[0].commands), ["c"]);
+    }
+
+    #[test]
+    fn test_group_renames_and_reorders_are_replayed() {
+        let base = sample();
+        let mut ours = base.clone();
+        ours.groups[0].name = "Dev".to_string();
+        ours.commands.swap(0, 1);
+        let mut theirs = base.clone();
+        theirs.groups[0].commands[0].command = "make".to_string();
+        theirs.commands.push(command("e", "E"));
+
+        let merge = three_way_merge(&base, &ours, &theirs);
+
+        assert_eq!(merge.conflicts, []);
+        assert_eq!(ids(&merge.config.commands), ["b", "a", "e"]);
+        assert_eq!(merge.config.groups.len(), 1);
+        assert_eq!(merge.config.groups[0].name, "Dev");
+        assert_eq!(merge.config.groups[0].commands[0].command, "make");
+    }
+}
@@ -0,0 +1,246 @@
+//This file contains the upgrades from older config formats to the current one.
+//Each migration takes the JSON document one version forward, so a file written by any
+//earlier release can still be loaded, and fields this version does not know are reported.
+
+use crate::config::{CommandOption, Config, generate_command_id};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+
+/// The config format written by this version.
+pub const CONFIG_VERSION: u32 = 2;
+
+type Migration = fn(&mut Map<String, Value>);
+
+// MIGRATIONS[n] upgrades a version n + 1 document to version n + 2; version 0 is handled first
+const MIGRATIONS: [Migration; 1] = [assign_command_ids];
+
+/// Returns the format version of a config document.
+///
+/// Version 0 is the original flat list of commands, version 1 the `Config` object
+/// from before the `version` field was added.
+///
+/// # Errors
+///
+/// Returns a user-facing message when the document is neither an object nor a list,
+/// or the version is not a whole number.
+pub fn document_version(document: &Value) -> Result<u32, String> {
+    match document {
+        Value::Array(_) => Ok(0),
+        Value::Object(fields) => match fields.get("version") {
+            None => Ok(1),
+            Some(version) => version
+                .as_u64()
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or_else(|| format!("Config version must be a whole number, got {version}.")),
+        },
+        _ => Err("Config must be a JSON object.".to_string()),
+    }
+}
+
+/// Upgrades a config document to [`CONFIG_VERSION`] in place.
+///
+/// Returns the version the document had when it was upgraded, or `None` when it was current.
+///
+/// # Errors
+///
+/// Returns a user-facing message when the document is not a config or was written
+/// by a newer version.
+pub fn migrate(document: &mut Value) -> Result<Option<u32>, String> {
+    let from = document_version(document)?;
+    if from == CONFIG_VERSION {
+        return Ok(None);
+    }
+    if from > CONFIG_VERSION {
+        return Err(format!(
+            "Config format version {from} is newer than this program supports ({CONFIG_VERSION}). Please upgrade shell_command_menu."
+        ));
+    }
+
+    // Before the Config struct the file was only the list of commands
+    if let Value::Array(commands) = document {
+        let commands = std::mem::take(commands);
+        *document = Value::Object(Map::from_iter([(
+            "commands".to_string(),
+            Value::Array(commands),
+        )]));
+    }
+    let Value::Object(fields) = document else {
+        return Err("Config must be a JSON object.".to_string());
+    };
+    for migration in &MIGRATIONS[from.saturating_sub(1) as usize..] {
+        migration(fields);
+    }
+    fields.insert("version".to_string(), Value::from(CONFIG_VERSION));
+    Ok(Some(from))
+}
+
+// Version 2 stores a stable ID on every command
+fn assign_command_ids(fields: &mut Map<String, Value>) {
+    fn visit(level: &mut Map<String, Value>, seen: &mut HashSet<String>) {
+        if let Some(Value::Array(commands)) = level.get_mut("commands") {
+            for command in commands.iter_mut().filter_map(Value::as_object_mut) {
+                let id = command
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .filter(|id| !id.trim().is_empty() && !seen.contains(*id))
+                    .map(str::to_string);
+                let id = id.unwrap_or_else(|| {
+                    let display_name = command
+                        .get("display_name")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    let id = generate_command_id(display_name, seen);
+                    command.insert("id".to_string(), Value::from(id.clone()));
+                    id
+                });
+                seen.insert(id);
+            }
+        }
+        if let Some(Value::Array(groups)) = level.get_mut("groups") {
+            for group in groups.iter_mut().filter_map(Value::as_object_mut) {
+                visit(group, seen);
+            }
+        }
+    }
+
+    visit(fields, &mut HashSet::new());
+}
+
+/// Describes every field in the config that this version does not recognize.
+#[must_use]
+pub fn unknown_fields(config: &Config) -> Vec<String> {
+    let mut found = Vec::new();
+    push_unknown(&mut found, &config.unknown_fields, "");
+    for location in config.all_commands() {
+        push_command_unknown(&mut found, location.command, &location.path());
+    }
+    for path in config.group_paths().iter().skip(1) {
+        let Some((&index, parent)) = path.split_last() else {
+            continue;
+        };
+        if let Some(group) = config
+            .level(parent)
+            .and_then(|(_, groups)| groups.get(index))
+        {
+            let scope = format!("group '{}'", config.group_names(path).join("/"));
+            push_unknown(&mut found, &group.unknown_fields, &scope);
+        }
+    }
+    found
+}
+
+fn push_command_unknown(found: &mut Vec<String>, command: &CommandOption, path: &str) {
+    let scope = format!("command '{path}'");
+    push_unknown(found, &command.unknown_fields, &scope);
+    for (name, spec) in &command.placeholders {
+        push_unknown(
+            found,
+            &spec.unknown_fields,
+            &format!("placeholder '{name}' of {scope}"),
+        );
+    }
+}
+
+fn push_unknown(found: &mut Vec<String>, fields: &BTreeMap<String, Value>, scope: &str) {
+    for name in fields.keys() {
+        found.push(if scope.is_empty() {
+            format!("'{name}'")
+        } else {
+            format!("'{name}' in {scope}")
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_version_detects_older_formats() {
+        assert_eq!(document_version(&serde_json::json!([])), Ok(0));
+        assert_eq!(
+            document_version(&serde_json::json!({"commands": []})),
+            Ok(1)
+        );
+        assert_eq!(document_version(&serde_json::json!({"version": 2})), Ok(2));
+        assert!(document_version(&serde_json::json!({"version": "2"})).is_err());
+        assert!(document_version(&serde_json::json!("commands")).is_err());
+    }
+
+    #[test]
+    fn test_migrate_upgrades_flat_command_list() {
+        let mut document = serde_json::json!([
+            {"display_name": "List", "command": "ls"},
+            {"display_name": "List", "command": "ls -la"}
+        ]);
+
+        assert_eq!(migrate(&mut document), Ok(Some(0)));
+
+        let first = generate_command_id("List", &HashSet::new());
+        let second = generate_command_id("List", &HashSet::from([first.clone()]));
+        assert_eq!(
+            document,
+            serde_json::json!({
+                "version": CONFIG_VERSION,
+                "commands": [
+                    {"id": first, "display_name": "List", "command": "ls"},
+                    {"id": second, "display_name": "List", "command": "ls -la"}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_migrate_keeps_existing_ids_in_groups() {
+        let mut document = serde_json::json!({
+            "commands": [{"id": "keep", "display_name": "Keep", "command": "true"}],
+            "groups": [{"name": "Tools", "commands": [{"display_name": "Keep", "command": "true"}]}]
+        });
+
+        assert_eq!(migrate(&mut document), Ok(Some(1)));
+
+        assert_eq!(document["commands"][0]["id"], "keep");
+        let nested_id = document["groups"][0]["commands"][0]["id"].as_str().unwrap();
+        assert!(!nested_id.is_empty() && nested_id != "keep");
+        assert_eq!(migrate(&mut document), Ok(None));
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_versions() {
+        let mut document = serde_json::json!({"version": CONFIG_VERSION + 1});
+
+        let error = migrate(&mut document).unwrap_err();
+
+        assert!(error.contains("newer"), "{error}");
+    }
+
+    #[test]
+    fn test_unknown_fields_are_kept_and_reported() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "version": CONFIG_VERSION,
+            "colour": "blue",
+            "commands": [{
+                "id": "hi",
+                "display_name": "Hi",
+                "command": "echo {{name}}",
+                "timeout": 5,
+                "placeholders": {"name": {"defualt": "x"}}
+            }],
+            "groups": [{"name": "Tools", "icon": "🔧"}]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            unknown_fields(&config),
+            [
+                "'colour'",
+                "'timeout' in command 'Hi'",
+                "'defualt' in placeholder 'name' of command 'Hi'",
+                "'icon' in group 'Tools'"
+            ]
+        );
+        let saved = serde_json::to_value(&config).unwrap();
+        assert_eq!(saved["colour"], "blue");
+        assert_eq!(saved["commands"][0]["timeout"], 5);
+    }
+}
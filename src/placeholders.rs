@@ -0,0 +1,354 @@
+use crate::{
+    config::{CommandOption, ShellSpec, get_data_dir, shell_quote, write_file_atomically},
+    menu_main::prompt_or_return,
+    utils::CommandRequest,
+};
+use anyhow::Context;
+use inquire::{Text, validator::Validation};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+//This file contains the {{placeholder}} support for parameterized commands.
+
+// Define the PlaceholderSpec struct with the optional settings for one placeholder
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+#[schemars(transform = crate::schema::closed_object)]
+pub struct PlaceholderSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>, // Prompt text - the placeholder name when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>, // Initial value when nothing was remembered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>, // Regex the whole value must match
+    #[serde(flatten)]
+    pub unknown_fields: BTreeMap<String, serde_json::Value>, // Kept as written so saving does not drop them
+}
+
+// Remembered values by command ID, then placeholder name
+pub type RememberedValues = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Returns the unique `{{name}}` placeholders in `text`, in order of appearance.
+#[must_use]
+pub fn placeholder_names(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for (name, _) in placeholder_spans(text) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Returns the placeholders used by a command's `command` and `argv` fields.
+#[must_use]
+pub fn command_placeholders(command: &CommandOption) -> Vec<String> {
+    let mut names = placeholder_names(&command.command);
+    for arg in &command.argv {
+        for name in placeholder_names(arg) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Replaces each known `{{name}}` in `text` with `quote(value)`; unknown names are left as-is.
+#[must_use]
+pub fn substitute(
+    text: &str,
+    values: &BTreeMap<String, String>,
+    quote: impl Fn(&str) -> String,
+) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for (name, range) in placeholder_spans(text) {
+        if let Some(value) = values.get(&name) {
+            result.push_str(&text[last_end..range.start]);
+            result.push_str(&quote(value));
+            last_end = range.end;
+        }
+    }
+    result.push_str(&text[last_end..]);
+    result
+}
+
+/// Quotes a value so the shell running the command treats it as a single literal word.
+#[must_use]
+pub fn quote_for_shell(value: &str, shell: Option<&ShellSpec>) -> String {
+    let is_fish = shell.is_some_and(|shell| {
+        Path::new(shell.program())
+            .file_name()
+            .is_some_and(|name| name == "fish")
+    });
+    if is_fish {
+        // fish allows \\ and \' escapes inside single quotes
+        format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+    } else {
+        shell_quote(value)
+    }
+}
+
+/// Substitutes values into a request: quoted for the shell in `command`, verbatim in `argv`.
+pub fn apply_values(request: &mut CommandRequest, values: &BTreeMap<String, String>) {
+    let shell = request.shell.clone();
+    request.command = substitute(&request.command, values, |value| {
+        quote_for_shell(value, shell.as_ref())
+    });
+    for arg in &mut request.argv {
+        *arg = substitute(arg, values, str::to_string);
+    }
+}
+
+/// Checks a value against the placeholder's pattern.
+///
+/// # Errors
+///
+/// Returns a user-facing message when the pattern is invalid or does not match.
+pub fn validate_value(name: &str, spec: &PlaceholderSpec, value: &str) -> Result<(), String> {
+    let Some(pattern) = &spec.pattern else {
+        return Ok(());
+    };
+    let regex = compile_pattern(name, pattern)?;
+    if regex.is_match(value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{value}' does not match the {name} pattern {pattern}."
+        ))
+    }
+}
+
+/// Compiles a placeholder pattern so it must match the whole value.
+///
+/// # Errors
+///
+/// Returns a user-facing message when the pattern is not a valid regex.
+pub fn compile_pattern(name: &str, pattern: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{pattern})$"))
+        .map_err(|e| format!("Placeholder '{name}' has an invalid pattern: {e}"))
+}
+
+/// Returns the remembered placeholder values file path in the data directory.
+///
+/// # Errors
+///
+/// Returns an error when the data directory cannot be resolved.
+pub fn remembered_values_path() -> Result<PathBuf, String> {
+    Ok(get_data_dir()?.join("placeholders.json"))
+}
+
+/// Loads remembered placeholder values, returning none when the file does not exist yet.
+///
+/// # Errors
+///
+/// Returns an error when an existing file cannot be read or parsed.
+pub fn load_remembered(path: &Path) -> anyhow::Result<RememberedValues> {
+    if !path.exists() {
+        return Ok(RememberedValues::new());
+    }
+
+    let data = std::fs::read_to_string(path).with_context(|| {
+        format!(
+            "unable to load placeholder values located at {}",
+            path.display()
+        )
+    })?;
+    serde_json::from_str(&data).context("unable to parse placeholder values")
+}
+
+/// Saves remembered placeholder values.
+///
+/// # Errors
+///
+/// Returns an error when the values cannot be serialized or written to disk.
+pub fn save_remembered(path: &Path, values: &RememberedValues) -> anyhow::Result<()> {
+    let data =
+        serde_json::to_string_pretty(values).context("failed to serialize placeholder values")?;
+    write_file_atomically(path, &data)
+}
+
+/// Prompts for every placeholder of `command` and substitutes the answers into `request`.
+///
+/// Values in `preset` are used without prompting after being checked against the pattern.
+/// Answers are remembered per command and offered as the initial value next time.
+/// Returns `false` when the user cancels a prompt or a preset value is invalid.
+pub fn fill_placeholders(
+    command: &CommandOption,
+    request: &mut CommandRequest,
+    preset: &BTreeMap<String, String>,
+) -> bool {
+    let names = command_placeholders(command);
+    if names.is_empty() {
+        return true;
+    }
+
+    let remembered_path = remembered_values_path();
+    let mut remembered = remembered_path
+        .as_deref()
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .and_then(load_remembered)
+        .unwrap_or_else(|e| {
+            eprintln!("⚠️  Could not load remembered placeholder values: {e}");
+            RememberedValues::new()
+        });
+
+    let mut values = BTreeMap::new();
+    for name in names {
+        let spec = command.placeholders.get(&name).cloned().unwrap_or_default();
+        if let Some(value) = preset.get(&name) {
+            if let Err(e) = validate_value(&name, &spec, value) {
+                eprintln!("❌  {e}");
+                return false;
+            }
+            values.insert(name, value.clone());
+            continue;
+        }
+        let initial = remembered
+            .get(&command.id)
+            .and_then(|values| values.get(&name))
+            .or(spec.default.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        let Some(value) = prompt_for_value(&name, &spec, &initial) else {
+            return false;
+        };
+        values.insert(name, value);
+    }
+
+    remembered
+        .entry(command.id.clone())
+        .or_default()
+        .extend(values.clone());
+    if let Err(e) = remembered_path
+        .map_err(anyhow::Error::msg)
+        .and_then(|path| save_remembered(&path, &remembered))
+    {
+        eprintln!("⚠️  Could not remember placeholder values: {e}");
+    }
+
+    apply_values(request, &values);
+    true
+}
+
+fn prompt_for_value(name: &str, spec: &PlaceholderSpec, initial: &str) -> Option<String> {
+    let message = spec.prompt.clone().unwrap_or_else(|| format!("{name}:"));
+    let validator_name = name.to_string();
+    let validator_spec = spec.clone();
+    prompt_or_return(|| {
+        let mut prompt = Text::new(&message)
+            .with_initial_value(initial)
+            .with_validator(move |value: &str| {
+                Ok(
+                    match validate_value(&validator_name, &validator_spec, value) {
+                        Ok(()) => Validation::Valid,
+                        Err(error) => Validation::Invalid(error.into()),
+                    },
+                )
+            });
+        if let Some(pattern) = &spec.pattern {
+            prompt = prompt.with_help_message(pattern);
+        }
+        prompt.prompt()
+    })
+}
+
+// Finds each {{name}} and the byte range it occupies
+fn placeholder_spans(text: &str) -> Vec<(String, std::ops::Range<usize>)> {
+    let mut spans = Vec::new();
+    let mut search_from = 0;
+    while let Some(open) = text[search_from..].find("{{") {
+        let start = search_from + open;
+        let Some(close) = text[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+        let name = text[start + 2..end - 2].trim();
+        if is_placeholder_name(name) {
+            spans.push((name.to_string(), start..end));
+            search_from = end;
+        } else {
+            search_from = start + 2;
+        }
+    }
+    spans
+}
+
+fn is_placeholder_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placeholder_names_are_unique_and_ordered() {
+        assert_eq!(
+            placeholder_names(
+                "git checkout {{branch}} && echo {{ ticket }} {{branch}} {{not valid}}"
+            ),
+            vec!["branch", "ticket"]
+        );
+    }
+
+    #[test]
+    fn test_apply_values_quotes_shell_commands_but_not_argv() {
+        let values = BTreeMap::from([("msg".to_string(), "it's $(rm -rf /)".to_string())]);
+        let mut request = CommandRequest {
+            command: "git commit -m {{msg}}".into(),
+            ..Default::default()
+        };
+        apply_values(&mut request, &values);
+        assert_eq!(request.command, r#"git commit -m 'it'\''s $(rm -rf /)'"#);
+
+        let mut fish = CommandRequest {
+            command: "echo {{msg}}".into(),
+            shell: Some(ShellSpec::Program("fish".into())),
+            ..Default::default()
+        };
+        apply_values(&mut fish, &values);
+        assert_eq!(fish.command, r"echo 'it\'s $(rm -rf /)'");
+
+        let mut argv = CommandRequest {
+            argv: vec!["git".into(), "commit".into(), "-m".into(), "{{msg}}".into()],
+            ..Default::default()
+        };
+        apply_values(&mut argv, &values);
+        assert_eq!(argv.argv[3], "it's $(rm -rf /)");
+    }
+
+    #[test]
+    fn test_validate_value_matches_whole_value() {
+        let spec = PlaceholderSpec {
+            pattern: Some("[A-Z]+-[0-9]+".into()),
+            ..Default::default()
+        };
+
+        assert!(validate_value("ticket", &spec, "OPS-42").is_ok());
+        assert!(validate_value("ticket", &spec, "see OPS-42").is_err());
+        assert!(compile_pattern("ticket", "(").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_remembered_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("placeholders.json");
+        let values = RememberedValues::from([(
+            "deploy".to_string(),
+            BTreeMap::from([("branch".to_string(), "main".to_string())]),
+        )]);
+
+        save_remembered(&path, &values).unwrap();
+
+        assert_eq!(load_remembered(&path).unwrap(), values);
+    }
+}
@@ -0,0 +1,182 @@
+//This file contains the recovery journal of edit sessions.
+//While the edit menu has unsaved changes, the session is written to a file in the data directory
+//after every change, so closing the terminal mid-session loses nothing; the next launch finds the
+//file and offers to resume, save or discard the session.
+
+use crate::config::{Config, config_file_key, get_data_dir, write_file_atomically};
+use crate::layers::{merge_includes, own_layer};
+use crate::lock::FileStamp;
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Define an edit session that has not been saved yet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Recovery {
+    pub saved_at: DateTime<Local>,       // When the session last changed
+    pub loaded_stamp: Option<FileStamp>, // The config file as the session loaded it, to merge changes made since
+    pub original: Config,                // The config's own entries when the session started
+    pub config: Config,                  // The config's own entries as the session left them
+}
+
+impl Recovery {
+    /// Records the session editing `original` into `config`; included layers are left out.
+    #[must_use]
+    pub fn new(original: &Config, config: &Config, loaded_stamp: Option<&FileStamp>) -> Self {
+        Self {
+            saved_at: Local::now(),
+            loaded_stamp: loaded_stamp.cloned(),
+            original: own_layer(original),
+            config: own_layer(config),
+        }
+    }
+
+    /// Returns when the session last changed, such as `2024-01-01 08:00:00`.
+    #[must_use]
+    pub fn label(&self) -> String {
+        self.saved_at.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+
+    /// Returns the edited and original configs of the session with their includes merged again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when an include of the config cannot be read.
+    pub fn into_configs(self, config_path: &Path) -> anyhow::Result<(Config, Config)> {
+        let mut original = self.original;
+        let mut config = self.config;
+        for session_config in [&mut original, &mut config] {
+            session_config.source_path = Some(config_path.to_path_buf());
+            merge_includes(session_config, config_path)?;
+        }
+        Ok((config, original))
+    }
+}
+
+/// Returns the recovery file of edit sessions of the config at `config_path`.
+///
+/// # Errors
+///
+/// Returns an error when the data directory cannot be resolved.
+pub fn recovery_path(config_path: &Path) -> Result<PathBuf, String> {
+    Ok(get_data_dir()?
+        .join("recovery")
+        .join(format!("{}.json", config_file_key(config_path))))
+}
+
+/// Writes the session to `path`, replacing the previous state of the session.
+///
+/// # Errors
+///
+/// Returns an error when the session cannot be serialized or written.
+pub fn write_recovery(path: &Path, recovery: &Recovery) -> anyhow::Result<()> {
+    let data = serde_json::to_string_pretty(recovery).context("failed to serialize session")?;
+    write_file_atomically(path, &data)
+}
+
+/// Reads the session left in `path`, or `None` when there is none.
+///
+/// # Errors
+///
+/// Returns an error when the file exists but cannot be read or parsed.
+pub fn read_recovery(path: &Path) -> anyhow::Result<Option<Recovery>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("unable to read recovery file {}", path.display()))?;
+    let recovery = serde_json::from_str(&data)
+        .with_context(|| format!("unable to parse recovery file {}", path.display()))?;
+    Ok(Some(recovery))
+}
+
+/// Deletes the session in `path` once it was saved or discarded.
+///
+/// # Errors
+///
+/// Returns an error when the file exists but cannot be deleted.
+pub fn remove_recovery(path: &Path) -> anyhow::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("unable to delete recovery file {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandOption;
+
+    fn command(id: &str, name: &str) -> CommandOption {
+        CommandOption {
+            id: id.to_string(),
+            display_name: name.to_string(),
+            command: format!("echo {name}"),
+            ..CommandOption::default()
+        }
+    }
+
+    #[test]
+    fn test_recovery_round_trips_and_is_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recovery").join("menu.json-0123.json");
+        let original = Config {
+            commands: vec![command("a", "A")],
+            ..Config::default()
+        };
+        let mut config = original.clone();
+        config.commands.push(command("b", "B"));
+        let stamp = FileStamp {
+            modified: None,
+            sha256: "abc".to_string(),
+        };
+
+        assert_eq!(read_recovery(&path).unwrap(), None);
+        let recovery = Recovery::new(&original, &config, Some(&stamp));
+        write_recovery(&path, &recovery).unwrap();
+        assert_eq!(read_recovery(&path).unwrap(), Some(recovery));
+
+        remove_recovery(&path).unwrap();
+        assert_eq!(read_recovery(&path).unwrap(), None);
+        remove_recovery(&path).unwrap();
+    }
+
+    #[test]
+    fn test_recovered_session_merges_includes_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let catalog = dir.path().join("catalog.json");
+        fs::write(
+            &catalog,
+            r#"{"commands":[{"id":"shared","display_name":"Shared","command":"true"}]}"#,
+        )
+        .unwrap();
+        let config_path = dir.path().join("menu.json");
+        fs::write(&config_path, "{}").unwrap();
+        let original = Config {
+            include: vec![catalog.display().to_string()],
+            ..Config::default()
+        };
+        let mut config = original.clone();
+        config.commands.push(command("mine", "Mine"));
+
+        let (config, original) = Recovery::new(&original, &config, None)
+            .into_configs(&config_path)
+            .unwrap();
+
+        let names = |config: &Config| -> Vec<String> {
+            config
+                .commands
+                .iter()
+                .map(|command| command.display_name.clone())
+                .collect()
+        };
+        assert_eq!(names(&original), ["Shared"]);
+        assert_eq!(names(&config), ["Mine", "Shared"]);
+        assert_eq!(config.source_path.as_deref(), Some(config_path.as_path()));
+    }
+}
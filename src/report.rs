@@ -0,0 +1,181 @@
+use crate::{
+    config::{CommandLocation, CommandOption},
+    state::CommandStatus,
+};
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+//This file contains the JSON documents printed by the subcommands in --json mode.
+//Fields may be added in later versions, but existing fields keep their names and meaning
+//until SCHEMA_VERSION changes.
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+// Define one command as reported to other tools
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")] // Rename fields to camelCase in JSON
+pub struct CommandReport<'a> {
+    pub number: usize,        // The number accepted by `run`, as shown by `list`
+    pub path: String,         // The Group/Name path
+    pub groups: Vec<&'a str>, // Group names from the top level down
+    pub status: Option<&'a CommandStatus>, // Today's outcome - null when not run today
+    pub definition: &'a CommandOption, // The command exactly as stored in the config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layer: Option<&'a Path>, // The read-only included file the command comes from; omitted for own commands
+}
+
+impl<'a> CommandReport<'a> {
+    /// Builds the report for the command at `location`, numbered from 1 like `list`.
+    #[must_use]
+    pub fn new(
+        number: usize,
+        location: &CommandLocation<'a>,
+        statuses: &'a BTreeMap<String, CommandStatus>,
+    ) -> Self {
+        Self {
+            number,
+            path: location.path(),
+            groups: location.groups.clone(),
+            status: statuses.get(&location.command.id),
+            definition: location.command,
+            layer: location.command.layer.as_deref(),
+        }
+    }
+}
+
+// Define the document printed by `list --json`
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandListReport<'a> {
+    pub schema_version: u32,
+    pub day: Option<NaiveDate>, // The completion day the statuses belong to
+    pub commands: Vec<CommandReport<'a>>,
+}
+
+// Define the document printed by `show --json`
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandDetailReport<'a> {
+    pub schema_version: u32,
+    pub command: CommandReport<'a>,
+}
+
+// Define the change made by `add` or `remove`
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeAction {
+    Added,
+    Removed,
+}
+
+// Define the document printed by `add --json` and `remove --json`
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeReport<'a> {
+    pub schema_version: u32,
+    pub action: ChangeAction,
+    pub command: CommandReport<'a>,
+}
+
+// Define the document printed by `validate --json`
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport<'a> {
+    pub schema_version: u32,
+    pub config_path: &'a Path,
+    pub valid: bool,
+    pub errors: Vec<String>, // The validate_config messages, empty when valid
+}
+
+impl<'a> ValidationReport<'a> {
+    /// Builds the report from the result of `validate_config`.
+    #[must_use]
+    pub fn new(config_path: &'a Path, result: Result<(), Vec<String>>) -> Self {
+        let errors = result.err().unwrap_or_default();
+        Self {
+            schema_version: SCHEMA_VERSION,
+            config_path,
+            valid: errors.is_empty(),
+            errors,
+        }
+    }
+}
+
+/// Prints a report as pretty JSON on stdout.
+///
+/// # Errors
+///
+/// Returns an error when the report cannot be serialized.
+pub fn print_json(report: &impl Serialize) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(report).context("failed to serialize JSON output")?;
+    println!("{json}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_command_list_report_field_names_are_stable() {
+        let config = Config {
+            commands: vec![CommandOption {
+                id: "hello".into(),
+                display_name: "Hello".into(),
+                command: "printf hello".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let statuses = BTreeMap::from([(
+            "hello".to_string(),
+            CommandStatus::Failed { exit_code: Some(2) },
+        )]);
+        let locations = config.all_commands();
+        let report = CommandListReport {
+            schema_version: SCHEMA_VERSION,
+            day: None,
+            commands: vec![CommandReport::new(1, &locations[0], &statuses)],
+        };
+
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schemaVersion": 1,
+                "day": null,
+                "commands": [{
+                    "number": 1,
+                    "path": "Hello",
+                    "groups": [],
+                    "status": {"status": "failed", "exitCode": 2},
+                    "definition": {
+                        "id": "hello",
+                        "display_name": "Hello",
+                        "command": "printf hello"
+                    }
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_validation_report_lists_errors() {
+        let path = Path::new("menu.json");
+
+        let valid = serde_json::to_value(ValidationReport::new(path, Ok(()))).unwrap();
+        let invalid =
+            serde_json::to_value(ValidationReport::new(path, Err(vec!["bad".into()]))).unwrap();
+
+        assert_eq!(valid["valid"], true);
+        assert_eq!(valid["errors"], serde_json::json!([]));
+        assert_eq!(invalid["valid"], false);
+        assert_eq!(invalid["configPath"], "menu.json");
+        assert_eq!(invalid["errors"][0], "bad");
+    }
+}
@@ -0,0 +1,227 @@
+//This file contains the JSON Schema printed by --print-schema.
+//Most of the schema is derived from the config structs; the transforms here add the rules
+//`validate_config` checks that types alone cannot express, so editors flag the same problems.
+
+use crate::config::Config;
+use crate::migrations::CONFIG_VERSION;
+use schemars::generate::SchemaSettings;
+use schemars::{Schema, SchemaGenerator, json_schema};
+use serde_json::{Value, json};
+
+/// Matches a value with at least one non-whitespace character, like the display name check.
+pub const NON_BLANK_PATTERN: &str = r"\S";
+/// Matches a group name that is not blank and contains no `/`.
+pub const GROUP_NAME_PATTERN: &str = r"^[^/]*[^/\s][^/]*$";
+/// Matches an environment variable name accepted by `is_valid_env_name`.
+pub const ENV_NAME_PATTERN: &str = "^[A-Za-z_][A-Za-z0-9_]*$";
+/// Matches a 24-hour `HH:MM` reset time, or an empty value for midnight.
+pub const RESET_TIME_PATTERN: &str = r"^\s*(([01]?[0-9]|2[0-3]):[0-5][0-9])?\s*$";
+
+/// Returns the JSON Schema of the config file.
+///
+/// Draft 7 is used because it is the draft most editors understand.
+#[must_use]
+pub fn config_schema() -> Value {
+    let schema = SchemaSettings::draft07()
+        .for_deserialize()
+        .into_generator()
+        .into_root_schema_for::<Config>();
+    schema.to_value()
+}
+
+/// Adds the rules of the top-level config object.
+pub fn config_rules(schema: &mut Schema) {
+    closed_object(schema);
+    set_property_rule(schema, "version", "maximum", json!(CONFIG_VERSION));
+    set_property_rule(schema, "env", "propertyNames", env_names());
+    set_property_rule(
+        schema,
+        "dailyResetTime",
+        "pattern",
+        json!(RESET_TIME_PATTERN),
+    );
+
+    // `extends` is accepted as another name for `include`
+    if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut)
+        && let Some(include) = properties.get("include").cloned()
+    {
+        properties.insert("extends".to_string(), include);
+    }
+}
+
+/// Adds the rules of a command group.
+pub fn group_rules(schema: &mut Schema) {
+    closed_object(schema);
+    set_property_rule(schema, "name", "pattern", json!(GROUP_NAME_PATTERN));
+}
+
+/// Adds the rules of a command, including that it runs either `command` or `argv`.
+pub fn command_rules(schema: &mut Schema) {
+    closed_object(schema);
+    set_property_rule(schema, "display_name", "pattern", json!(NON_BLANK_PATTERN));
+    set_property_rule(schema, "env", "propertyNames", env_names());
+
+    schema.insert(
+        "if".to_string(),
+        json!({"required": ["argv"], "properties": {"argv": {"minItems": 1}}}),
+    );
+    schema.insert(
+        "then".to_string(),
+        json!({"properties": {
+            "command": {"pattern": r"^\s*$"},
+            "argv": {"items": [{"pattern": NON_BLANK_PATTERN}]}
+        }}),
+    );
+    schema.insert(
+        "else".to_string(),
+        json!({
+            "required": ["command"],
+            "properties": {"command": {"pattern": NON_BLANK_PATTERN}}
+        }),
+    );
+}
+
+/// Adds the rule that a shell names a program, in both of its forms.
+pub fn shell_rules(schema: &mut Schema) {
+    let Some(forms) = schema.get_mut("anyOf").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for form in forms {
+        let program = match form.get_mut("properties") {
+            Some(properties) => properties.get_mut("program"),
+            None => Some(form),
+        };
+        if let Some(Value::Object(program)) = program {
+            program.insert("pattern".to_string(), json!(NON_BLANK_PATTERN));
+        }
+    }
+}
+
+/// Rejects fields the config does not use, so editors flag typos; loading only warns about them.
+pub fn closed_object(schema: &mut Schema) {
+    schema.insert("additionalProperties".to_string(), Value::Bool(false));
+}
+
+/// Returns the schema of `include`, which takes one path or a list of paths.
+pub fn include_schema(_generator: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "anyOf": [
+            {"type": "string"},
+            {"type": "array", "items": {"type": "string"}}
+        ]
+    })
+}
+
+fn env_names() -> Value {
+    json!({"pattern": ENV_NAME_PATTERN})
+}
+
+fn set_property_rule(schema: &mut Schema, property: &str, rule: &str, value: Value) {
+    if let Some(Value::Object(property)) = schema
+        .get_mut("properties")
+        .and_then(|properties| properties.get_mut(property))
+    {
+        property.insert(rule.to_string(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotenv::is_valid_env_name;
+    use crate::state::parse_reset_time;
+    use regex::Regex;
+
+    fn matches(pattern: &str, value: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(value)
+    }
+
+    #[test]
+    fn test_patterns_agree_with_validation() {
+        for name in ["PATH", "_x1", "1X", "A-B", "", "Ä"] {
+            assert_eq!(
+                matches(ENV_NAME_PATTERN, name),
+                is_valid_env_name(name),
+                "{name:?}"
+            );
+        }
+        for time in [
+            "04:00", "4:00", "23:59", " 07:30 ", "", "24:00", "7", "07:60",
+        ] {
+            assert_eq!(
+                matches(RESET_TIME_PATTERN, time),
+                parse_reset_time(Some(time)).is_ok(),
+                "{time:?}"
+            );
+        }
+        for name in ["Tools", "  Dev tools ", "", "   ", "a/b"] {
+            let valid = !name.trim().is_empty() && !name.contains('/');
+            assert_eq!(matches(GROUP_NAME_PATTERN, name), valid, "{name:?}");
+        }
+        for display_name in ["List", " x ", "", " \t"] {
+            assert_eq!(
+                matches(NON_BLANK_PATTERN, display_name),
+                !display_name.trim().is_empty(),
+                "{display_name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_schema_describes_commands_and_their_rules() {
+        let schema = config_schema();
+
+        assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#");
+        assert_eq!(schema["additionalProperties"], false);
+        assert!(schema["properties"]["$schema"].is_object());
+        assert!(schema["properties"]["extends"].is_object());
+        assert_eq!(schema["properties"]["version"]["maximum"], CONFIG_VERSION);
+
+        let command = &schema["definitions"]["CommandOption"];
+        let required: Vec<&str> = command["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(Value::as_str)
+            .collect();
+        assert_eq!(required, ["display_name"]);
+        assert_eq!(command["else"]["required"], json!(["command"]));
+        assert_eq!(
+            command["properties"]["display_name"]["pattern"],
+            NON_BLANK_PATTERN
+        );
+        assert_eq!(
+            command["else"]["properties"]["command"]["pattern"],
+            NON_BLANK_PATTERN
+        );
+        assert_eq!(
+            schema["definitions"]["CommandGroup"]["properties"]["name"]["pattern"],
+            GROUP_NAME_PATTERN
+        );
+    }
+
+    #[test]
+    fn test_schema_allows_argv_commands_without_a_command_line() {
+        let schema = config_schema();
+        let command = &schema["definitions"]["CommandOption"];
+
+        // An argv entry selects the `then` branch, which does not require `command`
+        assert_eq!(command["if"]["required"], json!(["argv"]));
+        assert!(command["then"].get("required").is_none());
+    }
+
+    #[test]
+    fn test_schema_reference_is_kept_and_not_reported() {
+        let config: Config = serde_json::from_value(json!({
+            "$schema": "./cli_menu.schema.json",
+            "version": CONFIG_VERSION
+        }))
+        .unwrap();
+
+        assert!(crate::migrations::unknown_fields(&config).is_empty());
+        assert_eq!(
+            serde_json::to_value(&config).unwrap()["$schema"],
+            "./cli_menu.schema.json"
+        );
+    }
+}
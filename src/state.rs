@@ -0,0 +1,337 @@
+use crate::config::{config_file_key, get_data_dir, write_file_atomically};
+use anyhow::Context;
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+//This file contains the daily completion state that is persisted in the data directory for each config file.
+
+// Define the CompletionState struct saved between menu sessions
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)] // Default values for the struct
+#[serde(rename_all = "camelCase")] // Rename fields to camelCase in JSON
+pub struct CompletionState {
+    pub day: Option<NaiveDate>, // The completion day the marks belong to
+    #[serde(alias = "completed", deserialize_with = "deserialize_statuses")]
+    pub statuses: BTreeMap<String, CommandStatus>, // The latest run outcome by command ID
+}
+
+// Reads the outcomes, or the `completed` list of IDs older versions kept, which only held successes
+fn deserialize_statuses<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<String, CommandStatus>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Statuses {
+        Outcomes(BTreeMap<String, CommandStatus>),
+        Completed(Vec<serde_json::Value>),
+    }
+
+    Ok(match Statuses::deserialize(deserializer)? {
+        Statuses::Outcomes(statuses) => statuses,
+        // Command numbers kept before IDs no longer match any command and are dropped
+        Statuses::Completed(completed) => completed
+            .iter()
+            .filter_map(serde_json::Value::as_str)
+            .map(|id| (id.to_string(), CommandStatus::Succeeded))
+            .collect(),
+    })
+}
+
+// Define the CommandStatus enum recorded for each command run today
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(
+    tag = "status",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum CommandStatus {
+    Succeeded,
+    Failed { exit_code: Option<i32> }, // None when the command could not start or was killed
+}
+
+impl CommandStatus {
+    /// Converts the result of running a command into a status.
+    #[must_use]
+    pub fn from_run_result(result: &anyhow::Result<ExitStatus>) -> Self {
+        match result {
+            Ok(status) if status.success() => Self::Succeeded,
+            Ok(status) => Self::Failed {
+                exit_code: status.code(),
+            },
+            Err(_) => Self::Failed { exit_code: None },
+        }
+    }
+}
+
+impl CompletionState {
+    /// Clears the completion marks when `today` is a different completion day.
+    ///
+    /// Returns `true` when the state changed and should be saved.
+    pub fn roll_over(&mut self, today: NaiveDate) -> bool {
+        if self.day == Some(today) {
+            return false;
+        }
+
+        self.day = Some(today);
+        self.statuses.clear();
+        true
+    }
+
+    /// Records the latest run outcome of a command, returning `true` when it changed.
+    pub fn record(&mut self, id: &str, status: CommandStatus) -> bool {
+        self.statuses.insert(id.to_string(), status) != Some(status)
+    }
+
+    /// Returns today's run outcome for the command, or `None` when it has not run yet.
+    #[must_use]
+    pub fn status(&self, id: &str) -> Option<CommandStatus> {
+        self.statuses.get(id).copied()
+    }
+}
+
+/// Returns the state file of the config at `config_path` in the data directory.
+///
+/// A state file left next to the config by older versions is moved there the first time.
+///
+/// # Errors
+///
+/// Returns an error when the data directory cannot be resolved.
+pub fn state_file_path(config_path: &Path) -> Result<PathBuf, String> {
+    let path = state_file_in(&get_data_dir()?, config_path);
+    move_legacy_state(config_path, &path);
+    Ok(path)
+}
+
+/// Returns the state file of the config at `config_path` inside `data_dir`.
+///
+/// The file is keyed by the config's full path, so `menu.json` and `menu.toml` keep their own
+/// progress and project folders do not collect state files.
+#[must_use]
+pub fn state_file_in(data_dir: &Path, config_path: &Path) -> PathBuf {
+    data_dir
+        .join("state")
+        .join(format!("{}.json", config_file_key(config_path)))
+}
+
+// Moves `<stem>.state.json` from next to the config, where older versions kept it, to `path`;
+// a failed move only costs today's marks, so it is not reported
+fn move_legacy_state(config_path: &Path, path: &Path) {
+    let Some(stem) = config_path.file_stem().and_then(|stem| stem.to_str()) else {
+        return;
+    };
+    let legacy = config_path.with_file_name(format!("{stem}.state.json"));
+    if path.exists() || !legacy.is_file() {
+        return;
+    }
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::rename(&legacy, path);
+}
+
+/// Loads the completion state, returning an empty state when the file does not exist yet.
+///
+/// # Errors
+///
+/// Returns an error when an existing state file cannot be read or parsed.
+pub fn load_state(path: &Path) -> anyhow::Result<CompletionState> {
+    if !path.exists() {
+        return Ok(CompletionState::default());
+    }
+
+    let state_data = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to load state file located at {}", path.display()))?;
+    let state = serde_json::from_str(&state_data).context("unable to parse state")?;
+    Ok(state)
+}
+
+/// Saves the completion state.
+///
+/// # Errors
+///
+/// Returns an error when the state cannot be serialized or written to disk.
+pub fn save_state(path: &Path, state: &CompletionState) -> anyhow::Result<()> {
+    let state_data = serde_json::to_string_pretty(state).context("failed to serialize state")?;
+    write_file_atomically(path, &state_data)
+}
+
+/// Records one command outcome in the state file of `config_path`, rolling over to
+/// the current completion day first. Used for runs outside the interactive menu.
+///
+/// # Errors
+///
+/// Returns an error when the data directory cannot be resolved or the state file cannot be
+/// loaded or saved.
+pub fn record_completion(
+    config_path: &Path,
+    daily_reset_time: Option<&str>,
+    command_id: &str,
+    status: CommandStatus,
+) -> anyhow::Result<()> {
+    let path = state_file_path(config_path).map_err(anyhow::Error::msg)?;
+    let mut state = load_state(&path)?;
+    let reset_time = parse_reset_time(daily_reset_time).unwrap_or_default();
+    let rolled_over = state.roll_over(current_completion_day(reset_time));
+    if state.record(command_id, status) || rolled_over {
+        save_state(&path, &state)?;
+    }
+    Ok(())
+}
+
+/// Parses the configured daily reset time, defaulting to midnight.
+///
+/// # Errors
+///
+/// Returns a user-facing message when the value is not a valid `HH:MM` time.
+pub fn parse_reset_time(value: Option<&str>) -> Result<NaiveTime, String> {
+    match value.map(str::trim) {
+        None | Some("") => Ok(NaiveTime::MIN),
+        Some(value) => NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| {
+            format!("Daily reset time '{value}' must be a 24-hour HH:MM time, such as 04:00.")
+        }),
+    }
+}
+
+/// Returns the completion day for `now`; times before `reset_time` belong to the previous day.
+#[must_use]
+pub fn completion_day(now: NaiveDateTime, reset_time: NaiveTime) -> NaiveDate {
+    (now - reset_time.signed_duration_since(NaiveTime::MIN)).date()
+}
+
+/// Returns the current local completion day for the configured reset time.
+#[must_use]
+pub fn current_completion_day(reset_time: NaiveTime) -> NaiveDate {
+    completion_day(Local::now().naive_local(), reset_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_state_file_is_kept_per_config_in_the_data_dir() {
+        let data_dir = Path::new("/tmp/data");
+        let json = state_file_in(data_dir, Path::new("/tmp/menus/menu.json"));
+        let toml = state_file_in(data_dir, Path::new("/tmp/menus/menu.toml"));
+
+        assert!(json.starts_with("/tmp/data/state"), "{}", json.display());
+        assert_ne!(json, toml);
+    }
+
+    #[test]
+    fn test_legacy_state_is_moved_into_the_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join(".cli_menu.json");
+        let legacy = dir.path().join(".cli_menu.state.json");
+        std::fs::write(&legacy, r#"{"day":"2026-03-02","statuses":{}}"#).unwrap();
+        let path = state_file_in(&dir.path().join("data"), &config);
+
+        move_legacy_state(&config, &path);
+
+        assert!(!legacy.exists());
+        assert_eq!(
+            load_state(&path).unwrap().day,
+            NaiveDate::from_ymd_opt(2026, 3, 2)
+        );
+    }
+
+    #[test]
+    fn test_parse_reset_time_defaults_to_midnight() {
+        assert_eq!(parse_reset_time(None), Ok(NaiveTime::MIN));
+        assert_eq!(
+            parse_reset_time(Some("04:30")),
+            Ok(NaiveTime::from_hms_opt(4, 30, 0).unwrap())
+        );
+        assert!(parse_reset_time(Some("4pm")).is_err());
+    }
+
+    #[test]
+    fn test_completion_day_before_reset_time_uses_previous_day() {
+        let reset = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+
+        assert_eq!(
+            completion_day(at("2026-03-02", "03:59"), reset),
+            NaiveDate::from_ymd_opt(2026, 3, 1).unwrap()
+        );
+        assert_eq!(
+            completion_day(at("2026-03-02", "04:00"), reset),
+            NaiveDate::from_ymd_opt(2026, 3, 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_roll_over_clears_marks_on_new_day_only() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let mut state = CompletionState {
+            day: Some(today),
+            statuses: BTreeMap::from([("a1".into(), CommandStatus::Succeeded)]),
+        };
+
+        assert!(!state.roll_over(today));
+        assert_eq!(state.status("a1"), Some(CommandStatus::Succeeded));
+
+        assert!(state.roll_over(today.succ_opt().unwrap()));
+        assert!(state.statuses.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load_state_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cli_menu_cmd.state.json");
+        let mut state = CompletionState::default();
+        state.roll_over(NaiveDate::from_ymd_opt(2026, 3, 2).unwrap());
+        state.record("b2", CommandStatus::Succeeded);
+        assert!(!state.record("b2", CommandStatus::Succeeded));
+        assert!(state.record("c3", CommandStatus::Failed { exit_code: Some(2) }));
+
+        save_state(&path, &state).expect("Should save state");
+
+        assert_eq!(load_state(&path).expect("Should load state"), state);
+    }
+
+    #[test]
+    fn test_command_status_from_run_result() {
+        assert_eq!(
+            CommandStatus::from_run_result(&Err(anyhow::anyhow!("spawn failed"))),
+            CommandStatus::Failed { exit_code: None }
+        );
+        assert_eq!(
+            serde_json::to_string(&CommandStatus::Failed { exit_code: Some(2) }).unwrap(),
+            r#"{"status":"failed","exitCode":2}"#
+        );
+    }
+
+    #[test]
+    fn test_load_state_reads_completed_ids_from_older_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.json");
+        std::fs::write(&path, r#"{"day":"2026-03-02","completed":["a1","c3"]}"#).unwrap();
+
+        let state = load_state(&path).unwrap();
+
+        assert_eq!(state.day, NaiveDate::from_ymd_opt(2026, 3, 2));
+        assert_eq!(state.status("a1"), Some(CommandStatus::Succeeded));
+        assert_eq!(state.status("c3"), Some(CommandStatus::Succeeded));
+        assert_eq!(state.status("b2"), None);
+
+        std::fs::write(&path, r#"{"day":"2026-03-02","completed":[1,3]}"#).unwrap();
+        assert!(load_state(&path).unwrap().statuses.is_empty());
+    }
+
+    #[test]
+    fn test_load_state_missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = load_state(&dir.path().join("missing.state.json")).unwrap();
+        assert_eq!(state, CompletionState::default());
+    }
+}
@@ -0,0 +1,403 @@
+//This file contains the trust database for config files other than the global config.
+//Like `direnv allow`, a config from a cloned repository has to be approved before its
+//commands are shown or run, and approved again whenever it changes.
+
+use crate::config::{get_data_dir, global_config_path, write_file_atomically};
+use crate::menu_main::prompt_or_return;
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TRUST_FILE_NAME: &str = "trusted_configs.json";
+
+// Define the approved config files, keyed by canonical path
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustStore {
+    pub configs: BTreeMap<String, TrustedConfig>,
+}
+
+// Define one approved config file
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedConfig {
+    pub sha256: String,               // Hash of the approved contents
+    pub approved_at: DateTime<Local>, // When the contents were approved or last saved by the menu
+    pub contents: String,             // The approved contents, kept to show what changed
+}
+
+// Define a config file that still needs approval
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingFile {
+    pub path: PathBuf,
+    pub contents: String,
+    pub previous: Option<String>, // The last approved contents; None when never approved
+}
+
+impl PendingFile {
+    /// Returns a unified diff from the approved contents to the current ones.
+    #[must_use]
+    pub fn diff(&self) -> String {
+        let previous = self.previous.as_deref().unwrap_or_default();
+        TextDiff::from_lines(previous, &self.contents)
+            .unified_diff()
+            .context_radius(3)
+            .header("approved", &self.path.display().to_string())
+            .to_string()
+    }
+
+    fn describe(&self) -> &'static str {
+        if self.previous.is_some() {
+            "has changed since you approved it"
+        } else {
+            "is not trusted yet"
+        }
+    }
+}
+
+impl TrustStore {
+    /// Returns `true` when `contents` are the approved version of the file at `path`.
+    #[must_use]
+    pub fn is_approved(&self, path: &Path, contents: &str) -> bool {
+        self.configs
+            .get(&store_key(path))
+            .is_some_and(|trusted| trusted.sha256 == sha256_hex(contents))
+    }
+
+    /// Returns the file when its contents do not match the approved hash.
+    #[must_use]
+    pub fn pending(&self, path: &Path, contents: String) -> Option<PendingFile> {
+        if self.is_approved(path, &contents) {
+            return None;
+        }
+        let trusted = self.configs.get(&store_key(path));
+        Some(PendingFile {
+            path: path.to_path_buf(),
+            contents,
+            previous: trusted.map(|trusted| trusted.contents.clone()),
+        })
+    }
+
+    /// Records `contents` as the approved version of the file at `path`.
+    pub fn approve(&mut self, path: &Path, contents: &str) {
+        self.configs.insert(
+            store_key(path),
+            TrustedConfig {
+                sha256: sha256_hex(contents),
+                approved_at: Local::now(),
+                contents: contents.to_string(),
+            },
+        );
+    }
+}
+
+/// Returns the path of the trust database in the data directory.
+///
+/// # Errors
+///
+/// Returns an error when the data directory cannot be resolved.
+pub fn trust_store_path() -> Result<PathBuf, String> {
+    Ok(trust_store_in(&get_data_dir()?))
+}
+
+/// Returns the path of the trust database inside `data_dir`.
+#[must_use]
+pub fn trust_store_in(data_dir: &Path) -> PathBuf {
+    data_dir.join(TRUST_FILE_NAME)
+}
+
+/// Loads the trust database, treating a missing file as empty.
+///
+/// # Errors
+///
+/// Returns an error when the file exists but cannot be read or parsed.
+pub fn load_trust_store(path: &Path) -> anyhow::Result<TrustStore> {
+    if !path.exists() {
+        return Ok(TrustStore::default());
+    }
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("unable to read trust database at {}", path.display()))?;
+    serde_json::from_str(&data)
+        .with_context(|| format!("unable to parse trust database at {}", path.display()))
+}
+
+/// Saves the trust database.
+///
+/// # Errors
+///
+/// Returns an error when the database cannot be serialized or written.
+pub fn save_trust_store(path: &Path, store: &TrustStore) -> anyhow::Result<()> {
+    let data = serde_json::to_string_pretty(store).context("failed to serialize trust database")?;
+    write_file_atomically(path, &data)
+}
+
+/// Returns the config file, its includes and the env files they name that are not approved
+/// in their current form.
+///
+/// Env files are checked too, as their variables, such as `PATH` or `BASH_ENV`, can change
+/// what a command runs. The global config is the user's own and never needs approval,
+/// and neither do the env files it names.
+///
+/// # Errors
+///
+/// Returns an error when a file or the trust database cannot be read.
+pub fn pending_files(config_path: &Path) -> anyhow::Result<Vec<PendingFile>> {
+    let store = load_trust_store(&trust_store_path().map_err(anyhow::Error::msg)?)?;
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path in crate::layers::config_files(config_path)? {
+        if is_global_config(&path) {
+            continue;
+        }
+        let env_files = crate::layers::env_files(&path)?;
+        files.push(path);
+        files.extend(env_files);
+    }
+
+    let mut pending = Vec::new();
+    for (index, path) in files.iter().enumerate() {
+        if files[..index].contains(path) {
+            continue;
+        }
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("unable to read {}", path.display()))?;
+        pending.extend(store.pending(path, contents));
+    }
+    Ok(pending)
+}
+
+/// Checks that the config can be used, asking for approval when `interactive` is set.
+///
+/// Returns `false` when a file is still untrusted; a message has been printed.
+///
+/// # Errors
+///
+/// Returns an error when a file or the trust database cannot be read or written.
+pub fn ensure_trusted(config_path: &Path, interactive: bool) -> anyhow::Result<bool> {
+    let pending = pending_files(config_path)?;
+    if pending.is_empty() {
+        return Ok(true);
+    }
+    if interactive {
+        return review_and_approve(&pending, false);
+    }
+
+    for file in &pending {
+        eprintln!("🔒  {} {}.", file.path.display(), file.describe());
+    }
+    eprintln!("Run `shell_command_menu allow` with the same config to review and approve it.");
+    Ok(false)
+}
+
+/// Shows the changes to each untrusted file and records the ones the user approves.
+///
+/// Returns the exit code for the `allow` subcommand: 0 once everything is trusted.
+///
+/// # Errors
+///
+/// Returns an error when a file or the trust database cannot be read or written.
+pub fn allow(config_path: &Path, assume_yes: bool) -> anyhow::Result<i32> {
+    let pending = pending_files(config_path)?;
+    if pending.is_empty() {
+        println!("✅ {} is trusted.", config_path.display());
+        return Ok(0);
+    }
+    Ok(if review_and_approve(&pending, assume_yes)? {
+        0
+    } else {
+        1
+    })
+}
+
+fn review_and_approve(pending: &[PendingFile], assume_yes: bool) -> anyhow::Result<bool> {
+    let store_path = trust_store_path().map_err(anyhow::Error::msg)?;
+    let mut store = load_trust_store(&store_path)?;
+    let mut all_approved = true;
+
+    for file in pending {
+        println!("\n🔒  {} {}:", file.path.display(), file.describe());
+        println!("{}", file.diff());
+        let approved = assume_yes
+            || prompt_or_return(|| {
+                inquire::Confirm::new("Trust this file and allow the config's commands to run?")
+                    .with_default(false)
+                    .prompt()
+            }) == Some(true);
+        if approved {
+            store.approve(&file.path, &file.contents);
+            println!("✅ Trusted {}.", file.path.display());
+        } else {
+            println!("❌  {} was not approved.", file.path.display());
+            all_approved = false;
+        }
+    }
+
+    save_trust_store(&store_path, &store)?;
+    Ok(all_approved)
+}
+
+/// Updates the approval of a file the menu just wrote, so saving your own edits keeps it trusted.
+///
+/// The new `contents` are only approved when the `replaced` contents were the approved ones;
+/// otherwise changes nobody reviewed would become trusted with them, so the approval is left
+/// stale and the file is reviewed again on the next launch. Files that were never approved,
+/// such as the global config, are left alone.
+///
+/// Returns `false` when an approved file was left needing review again.
+///
+/// # Errors
+///
+/// Returns an error when the trust database at `store_path` cannot be read or written.
+pub fn record_saved_config(
+    store_path: &Path,
+    path: &Path,
+    replaced: Option<&str>,
+    contents: &str,
+) -> anyhow::Result<bool> {
+    let mut store = load_trust_store(store_path)?;
+    if !store.configs.contains_key(&store_key(path)) {
+        return Ok(true);
+    }
+    if !replaced.is_some_and(|replaced| store.is_approved(path, replaced)) {
+        return Ok(false);
+    }
+    store.approve(path, contents);
+    save_trust_store(store_path, &store)?;
+    Ok(true)
+}
+
+/// Approves a config file the menu created itself.
+///
+/// # Errors
+///
+/// Returns an error when the file or the trust database at `store_path` cannot be read or written.
+pub fn approve_created_config(store_path: &Path, path: &Path) -> anyhow::Result<()> {
+    if is_global_config(path) {
+        return Ok(());
+    }
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("unable to read config file {}", path.display()))?;
+    let mut store = load_trust_store(store_path)?;
+    store.approve(path, &contents);
+    save_trust_store(store_path, &store)
+}
+
+/// Returns `true` when `path` is the global config or its current contents are approved.
+#[must_use]
+pub fn is_trusted_file(path: &Path) -> bool {
+    if is_global_config(path) {
+        return true;
+    }
+    let Ok(contents) = fs::read_to_string(path) else {
+        return false;
+    };
+    trust_store_path()
+        .ok()
+        .and_then(|store_path| load_trust_store(&store_path).ok())
+        .is_some_and(|store| store.pending(path, contents).is_none())
+}
+
+fn is_global_config(path: &Path) -> bool {
+    let global = global_config_path()
+        .ok()
+        .and_then(|global| fs::canonicalize(global).ok());
+    global.is_some() && global == fs::canonicalize(path).ok()
+}
+
+// Canonical paths keep one entry per file however it was reached
+fn store_key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn sha256_hex(contents: &str) -> String {
+    format!("{:x}", Sha256::digest(contents.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_reports_new_and_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".cli_menu.json");
+        fs::write(&path, "{}").unwrap();
+        let mut store = TrustStore::default();
+
+        let new_file = store.pending(&path, "{}\n".into()).unwrap();
+        assert_eq!(new_file.previous, None);
+
+        store.approve(&path, "{}\n");
+        assert_eq!(store.pending(&path, "{}\n".into()), None);
+
+        let changed = store
+            .pending(&path, "{}\n{\"commands\": []}\n".into())
+            .unwrap();
+        assert_eq!(changed.previous.as_deref(), Some("{}\n"));
+        assert!(changed.diff().contains("+{\"commands\": []}"));
+    }
+
+    #[test]
+    fn test_store_round_trips_through_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join(TRUST_FILE_NAME);
+        let mut store = TrustStore::default();
+        store.approve(&dir.path().join("menu.json"), "{}");
+
+        save_trust_store(&store_path, &store).unwrap();
+
+        assert_eq!(load_trust_store(&store_path).unwrap(), store);
+        assert_eq!(
+            load_trust_store(&dir.path().join("missing.json")).unwrap(),
+            TrustStore::default()
+        );
+    }
+
+    #[test]
+    fn test_saving_over_unapproved_contents_keeps_the_approval_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_path = dir.path().join(TRUST_FILE_NAME);
+        let path = dir.path().join(".cli_menu.json");
+        fs::write(&path, "{}").unwrap();
+        let mut store = TrustStore::default();
+        store.approve(&path, "approved");
+        save_trust_store(&store_path, &store).unwrap();
+
+        assert!(record_saved_config(&store_path, &path, Some("approved"), "mine").unwrap());
+        assert!(
+            load_trust_store(&store_path)
+                .unwrap()
+                .is_approved(&path, "mine")
+        );
+
+        assert!(!record_saved_config(&store_path, &path, Some("pulled"), "merged").unwrap());
+        let store = load_trust_store(&store_path).unwrap();
+        assert!(store.is_approved(&path, "mine"));
+        assert!(store.pending(&path, "merged".into()).is_some());
+
+        let untracked = dir.path().join("other.json");
+        assert!(record_saved_config(&store_path, &untracked, None, "{}").unwrap());
+        assert!(
+            !load_trust_store(&store_path)
+                .unwrap()
+                .is_approved(&untracked, "{}")
+        );
+    }
+
+    #[test]
+    fn test_sha256_hex_matches_known_digest() {
+        assert_eq!(
+            sha256_hex("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
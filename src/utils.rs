@@ -1,4 +1,6 @@
+use crate::config::{ShellSpec, join_argv, shell_quote};
 use rodio::{Decoder, DeviceSinkBuilder, Player};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufReader, Write, stdin, stdout};
 use std::path::PathBuf;
@@ -7,20 +9,67 @@ use termion::{input::TermRead, raw::IntoRawMode}; // Importing IntoRawMode trait
 use tokio::task; // Importing task module from Tokio for asynchronous task handling // Importing stdout, stdin, and Write traits for I/O operations
 
 //This file contains the utility functions used in the project to run shell commands and other misc functions.
+
+// Define the CommandRequest struct describing what to run and where
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandRequest {
+    pub command: String,               // The shell command to run
+    pub argv: Vec<String>, // Program and arguments run without a shell, used instead of `command`
+    pub shell: Option<ShellSpec>, // The shell for `command` - sh when None
+    pub cwd: Option<PathBuf>, // The working directory - the current directory when None
+    pub env: BTreeMap<String, String>, // Extra environment variables for the child process
+}
+
+impl CommandRequest {
+    /// Creates a request that runs `command` in the current directory.
+    #[must_use]
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the command line shown to the user and recorded in the history.
+    #[must_use]
+    pub fn display(&self) -> String {
+        if self.argv.is_empty() {
+            self.command.clone()
+        } else {
+            join_argv(&self.argv)
+        }
+    }
+
+    /// Returns a line to type into an interactive shell, changing to the working directory first.
+    ///
+    /// The shell and environment settings are not included.
+    #[must_use]
+    pub fn shell_line(&self) -> String {
+        match &self.cwd {
+            Some(cwd) => format!(
+                "cd {} && {}",
+                shell_quote(&cwd.to_string_lossy()),
+                self.display()
+            ),
+            None => self.display(),
+        }
+    }
+}
+
 pub trait CommandExecutor {
     /// Executes a shell command and returns its exit status.
     ///
     /// # Errors
     ///
     /// Returns an error when the command cannot be spawned or waited on.
-    fn execute(&mut self, command: &str) -> anyhow::Result<ExitStatus>;
+    fn execute(&mut self, request: &CommandRequest) -> anyhow::Result<ExitStatus>;
 }
 
 pub struct ShellCommandExecutor;
 
 impl CommandExecutor for ShellCommandExecutor {
-    fn execute(&mut self, command: &str) -> anyhow::Result<ExitStatus> {
-        execute_command(command)
+    fn execute(&mut self, request: &CommandRequest) -> anyhow::Result<ExitStatus> {
+        execute_command(request)
     }
 }
 
@@ -31,21 +80,34 @@ impl CommandExecutor for ShellCommandExecutor {
 /// Returns an error when the shell cannot be spawned or the command status
 /// cannot be collected.
 pub fn run_command(command: &str) -> anyhow::Result<ExitStatus> {
+    run_request(&CommandRequest::new(command))
+}
+
+/// Runs a command request and prints the result.
+///
+/// # Errors
+///
+/// Returns an error when the shell cannot be spawned or the command status
+/// cannot be collected.
+pub fn run_request(request: &CommandRequest) -> anyhow::Result<ExitStatus> {
     let mut executor = ShellCommandExecutor;
-    run_command_with(command, &mut executor)
+    run_command_with(request, &mut executor)
 }
 
-/// Runs a shell command through the supplied executor.
+/// Runs a command request through the supplied executor.
 ///
 /// # Errors
 ///
 /// Returns an error when the executor cannot run the command.
 pub fn run_command_with(
-    command: &str,
+    request: &CommandRequest,
     executor: &mut impl CommandExecutor,
 ) -> anyhow::Result<ExitStatus> {
-    println!("Running command: {command}"); // Printing the command being executed
-    let status = executor.execute(command)?;
+    println!("Running command: {}", request.display()); // Printing the command being executed
+    if let Some(cwd) = &request.cwd {
+        println!("Working directory: {}", cwd.display());
+    }
+    let status = executor.execute(request)?;
 
     if status.success() {
         // Checking if the command was successful
@@ -64,8 +126,23 @@ pub fn run_command_with(
 ///
 /// Returns an error when the shell cannot be spawned or the command status
 /// cannot be collected.
-pub fn execute_command(command: &str) -> anyhow::Result<ExitStatus> {
-    let mut child = Command::new("sh").arg("-c").arg(command).spawn()?;
+pub fn execute_command(request: &CommandRequest) -> anyhow::Result<ExitStatus> {
+    let mut command = if let Some((program, args)) = request.argv.split_first() {
+        // argv commands run directly, so no shell quoting is involved
+        let mut command = Command::new(program);
+        command.args(args);
+        command
+    } else {
+        let shell = request.shell.clone().unwrap_or_default();
+        let mut command = Command::new(shell.program());
+        command.args(shell.args_for(&request.command));
+        command
+    };
+    if let Some(cwd) = &request.cwd {
+        command.current_dir(cwd);
+    }
+    command.envs(&request.env);
+    let mut child = command.spawn()?;
 
     Ok(child.wait()?)
 }
@@ -144,10 +221,10 @@ mod tests {
 
     #[cfg(unix)]
     impl CommandExecutor for FakeExecutor {
-        fn execute(&mut self, command: &str) -> anyhow::Result<ExitStatus> {
+        fn execute(&mut self, request: &CommandRequest) -> anyhow::Result<ExitStatus> {
             use std::os::unix::process::ExitStatusExt;
 
-            self.commands.push(command.to_string());
+            self.commands.push(request.command.clone());
             Ok(ExitStatus::from_raw(self.status_code << 8))
         }
     }
@@ -174,12 +251,95 @@ mod tests {
             commands: Vec::new(),
         };
 
-        let status = run_command_with("echo fake", &mut executor).expect("command should run");
+        let status = run_command_with(&CommandRequest::new("echo fake"), &mut executor)
+            .expect("command should run");
 
         assert!(status.success());
         assert_eq!(executor.commands, vec!["echo fake"]);
     }
 
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn test_run_request_uses_working_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = CommandRequest {
+            command: "touch ran_here".into(),
+            cwd: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let status = run_request(&request).expect("command should run");
+
+        assert!(status.success());
+        assert!(dir.path().join("ran_here").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn test_run_request_argv_skips_shell_parsing() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = CommandRequest {
+            argv: vec!["touch".into(), "a file; with $(chars)".into()],
+            cwd: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        let status = run_request(&request).expect("command should run");
+
+        assert!(status.success());
+        assert!(dir.path().join("a file; with $(chars)").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn test_run_request_uses_custom_shell() {
+        let request = CommandRequest {
+            command: "exit 4".into(),
+            shell: Some(ShellSpec::Custom {
+                program: "sh".into(),
+                args: vec!["-e".into(), "-c".into()],
+            }),
+            ..Default::default()
+        };
+
+        let status = run_request(&request).expect("command should run");
+
+        assert_eq!(status.code(), Some(4));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn test_run_request_exports_environment() {
+        let request = CommandRequest {
+            command: r#"test "$CLI_MENU_TEST_VALUE" = expected"#.into(),
+            env: BTreeMap::from([("CLI_MENU_TEST_VALUE".into(), "expected".into())]),
+            ..Default::default()
+        };
+
+        let status = run_request(&request).expect("command should run");
+
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_shell_line_changes_directory_first() {
+        let request = CommandRequest {
+            argv: vec!["git".into(), "log".into(), "--oneline".into()],
+            cwd: Some(PathBuf::from("/tmp/my repo")),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            request.shell_line(),
+            "cd '/tmp/my repo' && git log --oneline"
+        );
+        assert_eq!(CommandRequest::new("make").shell_line(), "make");
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_play_sound() {
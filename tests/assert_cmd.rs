@@ -1,9 +1,13 @@
 use assert_cmd::Command;
+use shell_command_menu::lock::ConfigLock;
+use std::time::Duration;
 
 #[test]
 fn run_once_executes_command_and_reports_success() {
+    let data_dir = tempfile::tempdir().expect("temp dir");
     let assert = Command::cargo_bin("shell_command_menu")
         .expect("binary should build")
+        .env("CLI_MENU_DATA_DIR", data_dir.path())
         .arg("--run-once")
         .arg("printf assert_cmd_ok")
         .assert()
@@ -14,6 +18,26 @@ fn run_once_executes_command_and_reports_success() {
     assert!(stdout.contains("Command executed successfully."));
 }
 
+#[test]
+fn run_once_records_history_entry() {
+    let data_dir = tempfile::tempdir().expect("temp dir");
+    Command::cargo_bin("shell_command_menu")
+        .expect("binary should build")
+        .env("CLI_MENU_DATA_DIR", data_dir.path())
+        .arg("--run-once")
+        .arg("exit 3")
+        .assert()
+        .code(3);
+
+    let history = std::fs::read_to_string(data_dir.path().join("history.jsonl"))
+        .expect("history file should exist");
+    let entry: serde_json::Value =
+        serde_json::from_str(history.lines().next().expect("one entry")).expect("valid JSON");
+    assert_eq!(entry["command"], "exit 3");
+    assert_eq!(entry["exitCode"], 3);
+    assert!(entry["durationMs"].is_u64());
+}
+
 #[test]
 fn config_requires_path_argument() {
     Command::cargo_bin("shell_command_menu")
@@ -23,3 +47,552 @@ fn config_requires_path_argument() {
         .code(2)
         .stderr("Missing path for --config\n");
 }
+
+fn write_config(dir: &std::path::Path) -> std::path::PathBuf {
+    let path = dir.join("cli_menu_cmd.json");
+    std::fs::write(
+        &path,
+        r#"{"version":2,"commands":[{"id":"hello","display_name":"Hello","command":"printf hello"}],
+            "groups":[{"name":"Tools","commands":[{"id":"fail","display_name":"Fail","command":"exit 4"}]}]}"#,
+    )
+    .expect("write config");
+    allow_config(&path, dir);
+    path
+}
+
+// Configs other than the global one have to be trusted before their commands are used
+fn allow_config(config: &std::path::Path, data_dir: &std::path::Path) {
+    menu_command(config, data_dir)
+        .args(["allow", "--yes"])
+        .assert()
+        .success();
+}
+
+fn menu_command(config: &std::path::Path, data_dir: &std::path::Path) -> Command {
+    let mut command = Command::cargo_bin("shell_command_menu").expect("binary should build");
+    command
+        .env("CLI_MENU_DATA_DIR", data_dir)
+        .arg("--config")
+        .arg(config);
+    command
+}
+
+#[test]
+fn list_prints_numbered_command_paths() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+
+    menu_command(&config, dir.path())
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("1. Hello\n2. Tools/Fail\n")
+        .stderr("");
+}
+
+#[test]
+fn run_exits_with_command_status_and_records_progress() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+
+    menu_command(&config, dir.path())
+        .args(["run", "tools/fail"])
+        .assert()
+        .code(4);
+    menu_command(&config, dir.path())
+        .args(["run", "1"])
+        .assert()
+        .success();
+
+    assert!(!dir.path().join("cli_menu_cmd.state.json").exists());
+    let state = std::fs::read_to_string(shell_command_menu::state::state_file_in(
+        dir.path(),
+        &config,
+    ))
+    .expect("state file should exist");
+    let state: serde_json::Value = serde_json::from_str(&state).expect("valid JSON");
+    assert_eq!(state["statuses"]["hello"]["status"], "succeeded");
+    assert_eq!(state["statuses"]["fail"]["exitCode"], 4);
+}
+
+#[test]
+fn add_and_remove_update_the_config() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+
+    menu_command(&config, dir.path())
+        .args(["add", "Date", "date", "--group", "Tools/Time"])
+        .assert()
+        .success();
+    menu_command(&config, dir.path())
+        .args(["remove", "Hello"])
+        .assert()
+        .success();
+
+    menu_command(&config, dir.path())
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("1. Tools/Fail\n2. Tools/Time/Date\n");
+}
+
+#[test]
+fn add_rejects_invalid_config_without_saving() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+    let before = std::fs::read_to_string(&config).expect("read config");
+
+    menu_command(&config, dir.path())
+        .args(["add", "hello", "echo again"])
+        .assert()
+        .code(1);
+
+    assert_eq!(
+        std::fs::read_to_string(&config).expect("read config"),
+        before
+    );
+}
+
+#[test]
+fn add_waits_for_another_instance_saving_the_config() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+    let before = std::fs::read_to_string(&config).expect("read config");
+    let lock = ConfigLock::acquire_in(dir.path(), &config, Duration::ZERO).expect("lock config");
+
+    let assert = menu_command(&config, dir.path())
+        .args(["add", "Date", "date"])
+        .assert()
+        .code(1);
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).expect("stderr is UTF-8");
+    assert!(
+        stderr.contains("is being saved by another shell_command_menu"),
+        "{stderr}"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&config).expect("read config"),
+        before
+    );
+
+    drop(lock);
+    menu_command(&config, dir.path())
+        .args(["add", "Date", "date"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn included_layer_commands_are_listed_but_not_removed() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let catalog = write_config(dir.path());
+    let config = dir.path().join("personal.json");
+    std::fs::write(
+        &config,
+        format!(
+            r#"{{"include":[{:?}],"commands":[{{"id":"mine","display_name":"Mine","command":"true"}}]}}"#,
+            catalog.display().to_string()
+        ),
+    )
+    .expect("write config");
+    allow_config(&config, dir.path());
+
+    menu_command(&config, dir.path())
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("1. Mine\n2. Hello\n3. Tools/Fail\n");
+    menu_command(&config, dir.path())
+        .args(["remove", "Hello"])
+        .assert()
+        .code(1);
+    menu_command(&config, dir.path())
+        .args(["add", "Date", "date", "--group", "Tools"])
+        .assert()
+        .success();
+
+    let saved = std::fs::read_to_string(&config).expect("read config");
+    assert!(saved.contains("Date"));
+    assert!(!saved.contains("Hello"));
+    assert!(
+        !std::fs::read_to_string(&catalog)
+            .expect("read catalog")
+            .contains("Date")
+    );
+}
+
+#[test]
+fn unknown_target_fails() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+
+    menu_command(&config, dir.path())
+        .args(["show", "Nope"])
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn list_json_reports_commands_and_todays_status() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+    menu_command(&config, dir.path())
+        .args(["run", "Fail"])
+        .assert()
+        .code(4);
+
+    let assert = menu_command(&config, dir.path())
+        .args(["list", "--json"])
+        .assert()
+        .success();
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&assert.get_output().stdout).expect("stdout is JSON");
+    assert_eq!(report["schemaVersion"], 1);
+    assert_eq!(report["commands"][0]["path"], "Hello");
+    assert_eq!(report["commands"][0]["status"], serde_json::Value::Null);
+    assert_eq!(report["commands"][1]["groups"][0], "Tools");
+    assert_eq!(report["commands"][1]["status"]["exitCode"], 4);
+    assert_eq!(report["commands"][1]["definition"]["command"], "exit 4");
+}
+
+#[test]
+fn validate_json_reports_problems() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = dir.path().join("cli_menu_cmd.json");
+    std::fs::write(
+        &config,
+        r#"{"commands":[{"display_name":"Missing dir","command":"ls","cwd":"/definitely/not/here"}]}"#,
+    )
+    .expect("write config");
+
+    let assert = menu_command(&config, dir.path())
+        .args(["--json", "validate"])
+        .assert()
+        .code(1);
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&assert.get_output().stdout).expect("stdout is JSON");
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["errors"].as_array().map(Vec::len), Some(1));
+}
+
+#[cfg(unix)]
+#[test]
+fn bash_completion_offers_stored_command_paths() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+    let binary = assert_cmd::cargo::cargo_bin("shell_command_menu");
+    let script = Command::cargo_bin("shell_command_menu")
+        .expect("binary should build")
+        .args(["completions", "bash"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    std::fs::write(dir.path().join("completion.bash"), script).expect("write script");
+
+    let path = format!(
+        "{}:{}",
+        binary.parent().expect("binary dir").display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let output = std::process::Command::new("bash")
+        .env("PATH", path)
+        .env("CLI_MENU_DATA_DIR", dir.path())
+        .arg("-c")
+        .arg(
+            r#"source "$1/completion.bash"
+COMP_WORDS=(shell_command_menu --config "$2" run T)
+COMP_CWORD=4
+_shell_command_menu
+printf '%s\n' "${COMPREPLY[@]}""#,
+        )
+        .arg("bash")
+        .arg(dir.path())
+        .arg(&config)
+        .output()
+        .expect("bash should run");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Tools/Fail\n");
+}
+
+#[test]
+fn shell_init_prints_key_binding_for_pick() {
+    let output = Command::cargo_bin("shell_command_menu")
+        .expect("binary should build")
+        .args(["shell-init", "bash"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let script = String::from_utf8(output).expect("utf-8 script");
+
+    assert!(script.contains("bind -x"));
+    assert!(script.contains("shell_command_menu pick"));
+}
+
+#[test]
+fn pick_rejects_json_output() {
+    Command::cargo_bin("shell_command_menu")
+        .expect("binary should build")
+        .args(["pick", "--json"])
+        .assert()
+        .code(2);
+}
+
+#[test]
+fn project_config_is_found_from_a_subdirectory_with_global_commands() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let xdg = dir.path().join("xdg");
+    std::fs::create_dir_all(&xdg).expect("create xdg dir");
+    write_config(&xdg);
+    let project = dir.path().join("project");
+    let nested = project.join("src");
+    std::fs::create_dir_all(&nested).expect("create project");
+    std::fs::write(
+        project.join(".cli_menu.json"),
+        r#"{"commands":[{"id":"build","display_name":"Build","command":"make"}]}"#,
+    )
+    .expect("write project config");
+    let project_command = || {
+        let mut command = Command::cargo_bin("shell_command_menu").expect("binary should build");
+        command
+            .current_dir(&nested)
+            .env_remove("CLI_MENU_CONFIG")
+            .env("XDG_CONFIG_HOME", &xdg)
+            .env("CLI_MENU_DATA_DIR", dir.path());
+        command
+    };
+
+    project_command().arg("list").assert().code(1);
+    project_command()
+        .args(["allow", "--yes"])
+        .assert()
+        .success();
+
+    project_command()
+        .args(["list", "--names"])
+        .assert()
+        .success()
+        .stdout("Build\nHello\nTools/Fail\n");
+}
+
+#[test]
+fn config_env_var_overrides_project_config() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+    std::fs::write(dir.path().join(".cli_menu.json"), r#"{"commands":[]}"#)
+        .expect("write project config");
+
+    let mut command = Command::cargo_bin("shell_command_menu").expect("binary should build");
+    command
+        .current_dir(dir.path())
+        .env("CLI_MENU_CONFIG", &config)
+        .env("CLI_MENU_DATA_DIR", dir.path())
+        .args(["list", "--names"])
+        .assert()
+        .success()
+        .stdout("Hello\nTools/Fail\n");
+}
+
+#[test]
+fn changed_config_must_be_allowed_again() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+    menu_command(&config, dir.path())
+        .args(["add", "Date", "date"])
+        .assert()
+        .success();
+    menu_command(&config, dir.path())
+        .arg("list")
+        .assert()
+        .success();
+
+    let edited = std::fs::read_to_string(&config)
+        .expect("read config")
+        .replace("printf hello", "rm -rf ~/oops");
+    std::fs::write(&config, edited).expect("edit config");
+
+    menu_command(&config, dir.path())
+        .args(["run", "Hello"])
+        .assert()
+        .code(1);
+    let output = menu_command(&config, dir.path())
+        .args(["allow", "--yes"])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let review = String::from_utf8(output).expect("utf-8 output");
+    assert!(
+        review.contains("+      \"command\": \"rm -rf ~/oops\""),
+        "{review}"
+    );
+    menu_command(&config, dir.path())
+        .arg("list")
+        .assert()
+        .success();
+}
+
+#[test]
+fn changed_env_file_must_be_allowed_again() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = dir.path().join("menu.json");
+    std::fs::write(
+        &config,
+        r#"{"version":2,"commands":[{"id":"greet","display_name":"Greet","command":"printf \"$GREETING\"","env_file":"greet.env"}]}"#,
+    )
+    .expect("write config");
+    let env_file = dir.path().join("greet.env");
+    std::fs::write(&env_file, "GREETING=hello\n").expect("write env file");
+    allow_config(&config, dir.path());
+    menu_command(&config, dir.path())
+        .args(["run", "Greet"])
+        .assert()
+        .success();
+
+    std::fs::write(&env_file, "GREETING=hello\nBASH_ENV=/tmp/evil\n").expect("edit env file");
+
+    menu_command(&config, dir.path())
+        .args(["run", "Greet"])
+        .assert()
+        .code(1);
+    let assert = menu_command(&config, dir.path())
+        .args(["allow", "--yes"])
+        .assert()
+        .success();
+    let review = String::from_utf8(assert.get_output().stdout.clone()).expect("utf-8 output");
+    assert!(review.contains("greet.env"), "{review}");
+    assert!(review.contains("+BASH_ENV=/tmp/evil"), "{review}");
+}
+
+#[test]
+fn flat_config_is_upgraded_with_a_backup() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = dir.path().join("menu.json");
+    let flat = r#"[{"display_name":"Hello","command":"printf hello","colour":"blue"}]"#;
+    std::fs::write(&config, flat).expect("write config");
+    allow_config(&config, dir.path());
+
+    menu_command(&config, dir.path())
+        .args(["run", "Hello"])
+        .assert()
+        .success();
+
+    let upgraded: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&config).expect("read config"))
+            .expect("upgraded config is JSON");
+    assert_eq!(upgraded["version"], 2);
+    assert_eq!(upgraded["commands"][0]["colour"], "blue");
+    let backups: Vec<_> = std::fs::read_dir(dir.path())
+        .expect("list dir")
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("menu.json.v0-")
+        })
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(backups[0].path()).expect("read backup"),
+        flat
+    );
+}
+
+#[test]
+fn convert_rewrites_the_config_in_another_format() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+
+    let assert = menu_command(&config, dir.path())
+        .args(["convert", "toml"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).expect("stdout is UTF-8");
+    assert!(stdout.contains("Converted"), "{stdout}");
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).expect("stderr is UTF-8");
+    assert!(
+        stderr.contains("--config still names") && stderr.contains("cli_menu_cmd.toml"),
+        "{stderr}"
+    );
+
+    let converted = dir.path().join("cli_menu_cmd.toml");
+    let text = std::fs::read_to_string(&converted).expect("read converted config");
+    assert!(text.contains("display_name = \"Hello\""), "{text}");
+    assert!(!config.exists());
+    menu_command(&converted, dir.path())
+        .args(["run", "Tools/Fail"])
+        .assert()
+        .code(4);
+    menu_command(&converted, dir.path())
+        .args(["convert", "json"])
+        .assert()
+        .success();
+    assert!(config.exists());
+}
+
+#[test]
+fn convert_warns_that_the_config_env_var_names_the_old_file() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+
+    let mut command = Command::cargo_bin("shell_command_menu").expect("binary should build");
+    let assert = command
+        .env("CLI_MENU_CONFIG", &config)
+        .env("CLI_MENU_DATA_DIR", dir.path())
+        .args(["convert", "yaml"])
+        .assert()
+        .success();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).expect("stderr is UTF-8");
+    assert!(
+        stderr.contains("CLI_MENU_CONFIG still names") && stderr.contains("cli_menu_cmd.yaml"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn print_schema_outputs_the_config_schema() {
+    let assert = Command::cargo_bin("shell_command_menu")
+        .expect("binary should build")
+        .arg("--print-schema")
+        .assert()
+        .success();
+
+    let schema: serde_json::Value =
+        serde_json::from_slice(&assert.get_output().stdout).expect("schema is JSON");
+    assert_eq!(schema["title"], "shell_command_menu config");
+    assert!(schema["definitions"]["CommandOption"].is_object());
+}
+
+#[test]
+fn saving_keeps_a_snapshot_of_the_replaced_config() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let config = write_config(dir.path());
+    let original = std::fs::read_to_string(&config).expect("read config");
+
+    menu_command(&config, dir.path())
+        .args(["add", "Date", "date"])
+        .assert()
+        .success();
+
+    let backup_dirs: Vec<_> = std::fs::read_dir(dir.path().join("backups"))
+        .expect("backups folder")
+        .filter_map(Result::ok)
+        .collect();
+    assert_eq!(backup_dirs.len(), 1);
+    let snapshots: Vec<_> = std::fs::read_dir(backup_dirs[0].path())
+        .expect("list snapshots")
+        .filter_map(Result::ok)
+        .collect();
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(snapshots[0].path()).expect("read snapshot"),
+        original
+    );
+}
@@ -1,27 +1,32 @@
 use shell_command_menu::{
-    config::{CommandOption, Config, load_config, save_config, validate_json},
+    config::{CommandGroup, CommandOption, Config, load_config, save_config_in, validate_json},
     csv::read_commands_from_csv,
     menu_edit::clear_all_commands,
-    menu_main::{generate_menu, prompt_or_return},
+    menu_main::{generate_level_menu, generate_menu, prompt_or_return},
+    state::CommandStatus,
     utils::get_version,
 };
+use std::collections::BTreeMap;
 
 #[test]
 fn config_roundtrip_save_load() {
     let temp = tempfile::NamedTempFile::new().expect("temp file");
     let path = temp.path().to_path_buf();
+    let data_dir = tempfile::tempdir().expect("temp dir");
 
     let original = Config {
         commands: vec![CommandOption {
             display_name: "List".into(),
             command: "ls -la".into(),
+            ..Default::default()
         }],
         cmd_sound: Some("sound.mp3".into()),
         window_title_support: true,
         window_title: Some("CLI Menu".into()),
+        ..Default::default()
     };
 
-    save_config(&path, &original).expect("save config");
+    save_config_in(data_dir.path(), &path, &original).expect("save config");
     let loaded = load_config(&path).expect("load config");
 
     assert_eq!(loaded.commands.len(), 1);
@@ -41,27 +46,70 @@ fn config_validate_default_json() {
 fn csv_read_commands_fixture() {
     let commands = read_commands_from_csv("tests/fixtures/commands.csv").expect("parse csv");
     assert_eq!(commands.len(), 2);
-    assert_eq!(commands[0].display_name, "List Files");
-    assert_eq!(commands[0].command, "ls -la");
+    assert_eq!(commands[0].command.display_name, "List Files");
+    assert_eq!(commands[0].command.command, "ls -la");
 }
 
 #[test]
-fn menu_generate_menu_strikes_selected() {
+fn menu_generate_menu_marks_command_status() {
     let commands = vec![
         CommandOption {
+            id: "one".into(),
             display_name: "One".into(),
             command: "echo 1".into(),
+            ..Default::default()
         },
         CommandOption {
+            id: "two".into(),
             display_name: "Two".into(),
             command: "echo 2".into(),
+            ..Default::default()
+        },
+        CommandOption {
+            id: "three".into(),
+            display_name: "Three".into(),
+            command: "exit 2".into(),
+            ..Default::default()
         },
     ];
+    let statuses = BTreeMap::from([
+        ("two".to_string(), CommandStatus::Succeeded),
+        (
+            "three".to_string(),
+            CommandStatus::Failed { exit_code: Some(2) },
+        ),
+    ]);
+
+    let rendered = generate_menu(&commands, &statuses);
+    assert_eq!(rendered.len(), 3);
+    assert_eq!(rendered[0], "1. ⬜ One");
+    assert_eq!(rendered[1], "2. ✅ T\u{0336}w\u{0336}o\u{0336}");
+    assert_eq!(rendered[2], "3. ❌ Three (exit 2)");
+}
+
+#[test]
+fn menu_generate_level_menu_numbers_groups_after_commands() {
+    let command = |id: &str| CommandOption {
+        id: id.into(),
+        display_name: id.to_uppercase(),
+        command: format!("echo {id}"),
+        ..Default::default()
+    };
+    let mut tools = CommandGroup::new("Tools");
+    tools.commands = vec![command("b"), command("c")];
+    tools.groups = vec![CommandGroup {
+        name: "Nested".into(),
+        commands: vec![command("d")],
+        ..Default::default()
+    }];
+    let statuses = BTreeMap::from([
+        ("b".to_string(), CommandStatus::Succeeded),
+        ("d".to_string(), CommandStatus::Succeeded),
+    ]);
+
+    let rendered = generate_level_menu(&[command("a")], &[tools], &statuses);
 
-    let rendered = generate_menu(&commands, &[2]);
-    assert_eq!(rendered.len(), 2);
-    assert_eq!(rendered[0], "1. One");
-    assert_eq!(rendered[1], "2. T\u{0336}w\u{0336}o\u{0336}");
+    assert_eq!(rendered, vec!["1. ⬜ A", "2. 📁 Tools (2/3)"]);
 }
 
 #[test]
@@ -84,10 +132,12 @@ fn menu_edit_clear_all_commands() {
             CommandOption {
                 display_name: "A".into(),
                 command: "echo a".into(),
+                ..Default::default()
             },
             CommandOption {
                 display_name: "B".into(),
                 command: "echo b".into(),
+                ..Default::default()
             },
         ],
         ..Default::default()